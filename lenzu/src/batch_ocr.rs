@@ -0,0 +1,625 @@
+// Headless (no desktop session needed) batch mode, so that scanned chapters can be processed from scripts and CI:
+//      $ lenzu ocr [--output-dir <dir>] [--format text|json|mokuro|hocr|alto|pdf] [--panels] [--detect-text-regions] [--preprocess <steps>] [--dump-preprocess <dir>] [--min-confidence <0.0..1.0>] [--ocr <name>] [--interpreter <name>[,<name>...]] [--lang <lang>] [--mecab-dictionary <dir>] [--dictionary <store.bin>] [--interpreter-command <kakasi|mecab|config.json>] [--output-script <script>] [--translate-to <lang>] [--translate-other <libretranslate|openai>] <files|dirs|globs>...
+// each image is OCR'ed and interpreted, and the text, hiragana and boxes are written to stdout, or (if --output-dir
+// is passed) to <dir>/<image-stem>.txt per image; with --format json, it is the result_schema.rs document instead
+// (one document per line on stdout, or <dir>/<image-stem>.json), and with --format mokuro, it is mokuro's per-page
+// JSON (see export_mokuro.rs) plus <dir>/<image-dir-name>.mokuro of all the pages when --output-dir is passed;
+// --format hocr and --format alto writes <dir>/<image-stem>.hocr and <dir>/<image-stem>.xml respectively, and
+// --format pdf writes a searchable <dir>/<image-dir-name>.pdf per chapter (directory of images, see export_pdf.rs)
+// a result saved with --format json, hocr or alto can be given instead of an image (i.e. `lenzu ocr --format hocr
+// -o out out/*.json`), which is exported to the format again without OCR'ing (nor interpreting) the image once more;
+// hOCR and ALTO do not keep the image path nor the readings, so --format pdf needs the JSON results
+// --panels OCRs each manga panel (see image_handling::find_panels()) and --detect-text-regions each text region (see
+// image_handling::find_text_regions()) rather than the whole page (or panel), and --preprocess/--dump-preprocess are the same as the interactive mode (see image_handling::PreprocessPipeline)
+// only the results go to stdout, the progress and the diagnostics (of the backends as well) go to stderr
+use crate::export_alto::{from_alto, to_alto};
+use crate::export_hocr::{from_hocr, to_hocr};
+use crate::export_mokuro::{MokuroPage, MokuroVolume};
+use crate::export_pdf::{PdfChapter, DEFAULT_SCAN_DPI};
+use crate::image_handling::{
+    evaluate_by_panels, evaluate_by_text_regions, OCRImage, PreprocessPipeline,
+};
+use crate::interpreter_traits::InterpreterTrait;
+use crate::ocr_traits::{OcrTrait, OcrTraitResult};
+use crate::reading_order::to_reading_order;
+use crate::result_schema::{InterpreterResultJson, OcrResultJson, ResultDocument};
+use anyhow::Error;
+use std::{
+    collections::HashMap,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+// images in directories (and glob matches) are filtered by these (lowercased) extensions
+const SUPPORTED_IMAGE_EXTENSIONS: [&str; 8] =
+    ["png", "jpg", "jpeg", "bmp", "gif", "tif", "tiff", "webp"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum BatchOutputFormat {
+    Text,
+    Json,
+    Mokuro,
+    Hocr,
+    Alto,
+    Pdf,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct BatchOcrArgs {
+    pub inputs: Vec<String>, // files, directories and/or glob patterns (i.e. "chapter01/*.png"), in the order given
+    pub output_dir: Option<PathBuf>,
+    pub format: BatchOutputFormat,
+    pub split_panels: bool,
+    pub detect_text_regions: bool,
+}
+
+impl BatchOcrArgs {
+    // args are whatever follows the "ocr" subcommand, without the options that are handled by main() (i.e.
+    // --min-confidence, see main's without_main_options())
+    pub fn parse(args: &[String]) -> Result<Self, Error> {
+        let mut inputs = Vec::new();
+        let mut output_dir = None;
+        let mut format = BatchOutputFormat::Text;
+        let mut split_panels = false;
+        let mut detect_text_regions = false;
+        let mut arg_iter = args.iter();
+        while let Some(arg) = arg_iter.next() {
+            match arg.as_str() {
+                "--output-dir" | "-o" => match arg_iter.next() {
+                    Some(dir) => output_dir = Some(PathBuf::from(dir)),
+                    None => return Err(anyhow::anyhow!("{} expects a directory", arg)),
+                },
+                "--format" => match arg_iter.next().map(|value| value.as_str()) {
+                    Some("text") => format = BatchOutputFormat::Text,
+                    Some("json") => format = BatchOutputFormat::Json,
+                    Some("mokuro") => format = BatchOutputFormat::Mokuro,
+                    Some("hocr") => format = BatchOutputFormat::Hocr,
+                    Some("alto") => format = BatchOutputFormat::Alto,
+                    Some("pdf") => format = BatchOutputFormat::Pdf,
+                    _ => {
+                        return Err(anyhow::anyhow!(
+                            "--format expects 'text', 'json', 'mokuro', 'hocr', 'alto' or 'pdf'"
+                        ))
+                    }
+                },
+                "--panels" => split_panels = true,
+                "--detect-text-regions" => detect_text_regions = true,
+                _ if arg.starts_with("--") => {
+                    return Err(anyhow::anyhow!("Unknown option '{}' for 'ocr'", arg))
+                }
+                _ => inputs.push(arg.clone()),
+            }
+        }
+        if inputs.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Usage: lenzu ocr [--output-dir <dir>] [--format text|json|mokuro|hocr|alto|pdf] [--panels] [--detect-text-regions] [--min-confidence <0.0..1.0>] <files|dirs|globs>..."
+            ));
+        }
+        if format == BatchOutputFormat::Pdf && output_dir.is_none() {
+            // a PDF per chapter, so there can be more than one
+            return Err(anyhow::anyhow!("--format pdf requires --output-dir"));
+        }
+        Ok(BatchOcrArgs {
+            inputs,
+            output_dir,
+            format,
+            split_panels,
+            detect_text_regions,
+        })
+    }
+}
+
+// result of a previous `lenzu ocr --format json|hocr|alto` (or i.e. `tesseract <image> <stem> hocr`) rather than an
+// image
+fn saved_result_extension(path: &Path) -> Option<String> {
+    path.extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .filter(|extension| ["json", "hocr", "xml"].contains(&extension.as_str()))
+}
+
+fn is_saved_result(path: &Path) -> bool {
+    saved_result_extension(path).is_some()
+}
+
+fn load_saved_result(path: &Path) -> Result<ResultDocument, Error> {
+    let (ocr_result, width, height, backend) = match saved_result_extension(path).as_deref() {
+        Some("hocr") => {
+            let (ocr_result, width, height) = from_hocr(&fs::read_to_string(path)?)?;
+            (ocr_result, width, height, "hocr")
+        }
+        Some("xml") => {
+            let (ocr_result, width, height) = from_alto(&fs::read_to_string(path)?)?;
+            (ocr_result, width, height, "alto")
+        }
+        _ => return ResultDocument::load(path),
+    };
+    Ok(ResultDocument::new(
+        None,
+        width,
+        height,
+        OcrResultJson::from_ocr_result(backend, 0, &ocr_result),
+        None,
+    ))
+}
+
+fn is_supported_image(path: &Path) -> bool {
+    path.is_file()
+        && path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| {
+                SUPPORTED_IMAGE_EXTENSIONS.contains(&extension.to_lowercase().as_str())
+            })
+            .unwrap_or(false)
+}
+
+// resolves files, directories (images directly in it, sorted by name so that pages stay in order) and globs into
+// a list of image paths; unlike directories and globs, an explicitly named file is taken as-is even if the
+// extension is not one we know of (the OCR will complain if it cannot be decoded), and globs match the saved
+// results (*.json, *.hocr and *.xml) as well
+pub(crate) fn expand_inputs(inputs: &[String]) -> Result<Vec<PathBuf>, Error> {
+    let mut image_paths = Vec::new();
+    for input in inputs {
+        let path = Path::new(input);
+        if path.is_dir() {
+            let mut dir_images = fs::read_dir(path)?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|entry_path| is_supported_image(entry_path))
+                .collect::<Vec<PathBuf>>();
+            dir_images.sort();
+            image_paths.extend(dir_images);
+        } else if path.is_file() {
+            image_paths.push(path.to_path_buf());
+        } else if input.contains(['*', '?', '[']) {
+            // glob() yields matches in alphabetical order already
+            let glob_images = glob::glob(input)?
+                .filter_map(|entry| entry.ok())
+                .filter(|entry_path| is_supported_image(entry_path) || is_saved_result(entry_path))
+                .collect::<Vec<PathBuf>>();
+            if glob_images.is_empty() {
+                return Err(anyhow::anyhow!("No images matched '{}'", input));
+            }
+            image_paths.extend(glob_images);
+        } else {
+            return Err(anyhow::anyhow!("No such file or directory: '{}'", input));
+        }
+    }
+    Ok(image_paths)
+}
+
+// plain-text report of a single image, which is easy enough to both read and grep/cut:
+//      # <image path>
+//      [text]
+//      <one OCR line per line>
+//      [hiragana]
+//      <one interpreted line per line>
+//      [boxes]
+//      <line index> <x_min> <y_min> <x_max> <y_max> <confidence or '-'> <text>   (tab separated)
+pub(crate) fn format_result(document: &ResultDocument) -> String {
+    let mut report = format!(
+        "# {}\n[text]\n",
+        document.image.path.as_deref().unwrap_or("-")
+    );
+    for line in document.ocr.lines.iter() {
+        report.push_str(&format!("{}\n", line.text));
+    }
+    report.push_str("[hiragana]\n");
+    if let Some(interpreter) = &document.interpreter {
+        for line in interpreter.text.lines() {
+            report.push_str(&format!("{}\n", line));
+        }
+    }
+    report.push_str("[boxes]\n");
+    for (line_index, line) in document.ocr.lines.iter().enumerate() {
+        let confidence = match line.confidence {
+            Some(confidence) => format!("{:.3}", confidence),
+            None => "-".to_string(),
+        };
+        report.push_str(&format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+            line_index,
+            line.rect.x_min,
+            line.rect.y_min,
+            line.rect.x_max,
+            line.rect.y_max,
+            confidence,
+            line.text
+        ));
+    }
+    report
+}
+
+// mokuro names the volume after the directory of the images, and so do we (and the PDF after the chapter)
+fn image_dir_name(image_path: &Path) -> String {
+    image_path
+        .canonicalize()
+        .ok()
+        .and_then(|image_path| {
+            image_path
+                .parent()
+                .and_then(|image_dir| image_dir.file_name())
+                .map(|image_dir| image_dir.to_string_lossy().into_owned())
+        })
+        .unwrap_or_else(|| "volume".to_string())
+}
+
+// OCR of a single image, with the preprocessing (and panel/text region detection) that is asked for, in the coordinates
+// of the original image and in reading order (see reading_order.rs); this is shared with the evaluation (see
+// eval_ocr.rs) so that it measures the same thing
+pub(crate) fn evaluate_image(
+    image_path: &Path,
+    ocr: &dyn OcrTrait,
+    preprocess: &PreprocessPipeline,
+    split_panels: bool,
+    detect_text_regions: bool,
+) -> Result<OcrTraitResult, Error> {
+    let ocr_result = if preprocess.steps.is_empty() && !split_panels && !detect_text_regions {
+        let image_path_str = image_path
+            .to_str()
+            .ok_or_else(|| anyhow::anyhow!("Path is not valid UTF-8: {:?}", image_path))?;
+        ocr.evaluate_by_paths(image_path_str)?
+    } else {
+        let image_name = image_path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let preprocessed = preprocess.apply(&image::open(image_path)?, &image_name)?;
+        let preprocessed_result = if split_panels {
+            evaluate_by_panels(ocr, &preprocessed.image, detect_text_regions)?
+        } else if detect_text_regions {
+            evaluate_by_text_regions(ocr, &preprocessed.image)?
+        } else {
+            ocr.evaluate(&preprocessed.image)?
+        };
+        preprocessed.to_original_result(preprocessed_result)
+    };
+    Ok(to_reading_order(ocr_result))
+}
+
+fn process_image(
+    image_path: &Path,
+    ocr: &dyn OcrTrait,
+    interpreter: &dyn InterpreterTrait,
+    preprocess: &PreprocessPipeline,
+    split_panels: bool,
+    detect_text_regions: bool,
+    min_confidence: f32,
+) -> Result<ResultDocument, Error> {
+    let (width, height) = image::image_dimensions(image_path)?;
+    let ocr_start_time = std::time::Instant::now();
+    let ocr_result = evaluate_image(
+        image_path,
+        ocr,
+        preprocess,
+        split_panels,
+        detect_text_regions,
+    )?
+    .filter_by_confidence(min_confidence);
+    let ocr_time = ocr_start_time.elapsed().as_millis() as u64;
+    let interpreter_start_time = std::time::Instant::now();
+    let interpreter_result = interpreter
+        .convert_with_language(ocr_result.text.as_str(), ocr_result.language.as_deref())?;
+    let interpreter_time = interpreter_start_time.elapsed().as_millis() as u64;
+    Ok(ResultDocument::new(
+        Some(image_path),
+        width,
+        height,
+        OcrResultJson::from_ocr_result(ocr.name(), ocr_time, &ocr_result),
+        Some(InterpreterResultJson::from_interpreter_result(
+            interpreter.name(),
+            interpreter_time,
+            &interpreter_result,
+        )),
+    ))
+}
+
+// returns the number of images that failed, so that the caller can set the exit code (one bad page should not
+// stop the whole chapter from being processed)
+pub(crate) fn run(
+    batch_args: &BatchOcrArgs,
+    ocr: &dyn OcrTrait,
+    interpreter: &dyn InterpreterTrait,
+    preprocess: &PreprocessPipeline,
+    min_confidence: f32,
+) -> Result<usize, Error> {
+    let image_paths = expand_inputs(&batch_args.inputs)?;
+    if let Some(output_dir) = &batch_args.output_dir {
+        fs::create_dir_all(output_dir)?;
+    }
+    let mut failed_count = 0;
+    let mut mokuro_pages = Vec::new();
+    let mut pdf_chapters: Vec<(String, PdfChapter)> = Vec::new(); // in the order the chapters first appear
+    let mut written_paths: HashMap<PathBuf, PathBuf> = HashMap::new(); // output path to the image it is of
+    for input_path in image_paths.iter() {
+        let document_result = if is_saved_result(input_path) {
+            load_saved_result(input_path)
+        } else {
+            process_image(
+                input_path,
+                ocr,
+                interpreter,
+                preprocess,
+                batch_args.split_panels,
+                batch_args.detect_text_regions,
+                min_confidence,
+            )
+        };
+        let document = match document_result {
+            Ok(document) => document,
+            Err(e) => {
+                eprintln!("Error: {} - {:?}", input_path.display(), e);
+                failed_count += 1;
+                continue;
+            }
+        };
+        // the image that the (saved) result is of, for its name and (PDF) pixels
+        let image_path = match (is_saved_result(input_path), &document.image.path) {
+            (true, Some(saved_image_path)) => Path::new(saved_image_path),
+            _ => input_path.as_path(),
+        };
+        let (report, extension) = match (batch_args.format, &batch_args.output_dir) {
+            (BatchOutputFormat::Text, _) => (format_result(&document), "txt"),
+            (BatchOutputFormat::Json, Some(_)) => (document.to_json()? + "\n", "json"),
+            (BatchOutputFormat::Json, None) => (document.to_json_line()? + "\n", "json"),
+            (BatchOutputFormat::Mokuro, output_dir) => {
+                let mut mokuro_page = MokuroPage::from_result_document(&document);
+                let report = match output_dir {
+                    Some(_) => serde_json::to_string_pretty(&mokuro_page)? + "\n",
+                    None => serde_json::to_string(&mokuro_page)? + "\n",
+                };
+                mokuro_page.img_path = image_path
+                    .file_name()
+                    .map(|file_name| file_name.to_string_lossy().into_owned());
+                mokuro_pages.push(mokuro_page);
+                (report, "json")
+            }
+            (BatchOutputFormat::Hocr, _) | (BatchOutputFormat::Alto, _) => {
+                let image_name = image_path
+                    .file_name()
+                    .map(|file_name| file_name.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                let to_xml = match batch_args.format {
+                    BatchOutputFormat::Hocr => to_hocr,
+                    _ => to_alto,
+                };
+                let xml = to_xml(
+                    &document.ocr.to_ocr_result(),
+                    &image_name,
+                    document.image.width,
+                    document.image.height,
+                    &document.ocr.backend,
+                );
+                match batch_args.format {
+                    BatchOutputFormat::Hocr => (xml, "hocr"),
+                    _ => (xml, "xml"),
+                }
+            }
+            (BatchOutputFormat::Pdf, _) => {
+                // pages are accumulated (as JPEG) into the chapter, and the PDF is written once all are done
+                let chapter_name = image_dir_name(image_path);
+                let chapter_index = match pdf_chapters
+                    .iter()
+                    .position(|(name, _)| *name == chapter_name)
+                {
+                    Some(chapter_index) => chapter_index,
+                    None => {
+                        let chapter = PdfChapter::new(&chapter_name, DEFAULT_SCAN_DPI);
+                        pdf_chapters.push((chapter_name, chapter));
+                        pdf_chapters.len() - 1
+                    }
+                };
+                let page_result = image::open(image_path)
+                    .map_err(Error::from)
+                    .and_then(|image| {
+                        pdf_chapters[chapter_index]
+                            .1
+                            .add_page(&OCRImage::from(image), &document.ocr.to_ocr_result())
+                    });
+                if let Err(e) = page_result {
+                    eprintln!("Error: {} - {:?}", image_path.display(), e);
+                    failed_count += 1;
+                }
+                continue;
+            }
+        };
+        match &batch_args.output_dir {
+            Some(output_dir) => {
+                let stem = image_path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| "output".to_string());
+                let output_path = output_dir.join(format!("{}.{}", stem, extension));
+                // i.e. a/p01.png and b/p01.png, or p01.png and p01.jpg, which would overwrite each other's result
+                if let Some(other_image_path) = written_paths.get(&output_path) {
+                    eprintln!(
+                        "Error: {} - {} is already the result of {}, rename one of them",
+                        image_path.display(),
+                        output_path.display(),
+                        other_image_path.display()
+                    );
+                    failed_count += 1;
+                    continue;
+                }
+                fs::write(&output_path, report)?;
+                written_paths.insert(output_path.clone(), image_path.to_path_buf());
+                eprintln!("{} -> {}", image_path.display(), output_path.display());
+            }
+            None => {
+                let stdout = std::io::stdout();
+                let mut stdout_lock = stdout.lock();
+                stdout_lock.write_all(report.as_bytes())?;
+                stdout_lock.flush()?;
+            }
+        }
+    }
+    if let (BatchOutputFormat::Mokuro, Some(output_dir)) =
+        (batch_args.format, &batch_args.output_dir)
+    {
+        let volume = image_paths
+            .first()
+            .map(|image_path| image_dir_name(image_path))
+            .unwrap_or_else(|| "volume".to_string());
+        let mokuro_volume = MokuroVolume::new(&volume, &volume, mokuro_pages);
+        let mokuro_path = output_dir.join(format!("{}.mokuro", volume));
+        fs::write(&mokuro_path, serde_json::to_string(&mokuro_volume)?)?;
+        eprintln!(
+            "{} page(s) -> {}",
+            mokuro_volume.pages.len(),
+            mokuro_path.display()
+        );
+    }
+    if let Some(output_dir) = &batch_args.output_dir {
+        for (chapter_name, chapter) in pdf_chapters {
+            let pdf_path = output_dir.join(format!("{}.pdf", chapter_name));
+            let page_count = chapter.page_count();
+            chapter.save(&pdf_path)?;
+            eprintln!("{} page(s) -> {}", page_count, pdf_path.display());
+        }
+    }
+    Ok(failed_count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter_traits::{InterpreterTrait, InterpreterTraitResult};
+    use crate::ocr_traits::{OcrLine, OcrRect, OcrWord};
+
+    #[test]
+    fn test_parse_and_expand_inputs() {
+        let args = vec![
+            "--output-dir".to_string(),
+            "out".to_string(),
+            "--min-confidence".to_string(),
+            "0.5".to_string(),
+            "--format".to_string(),
+            "json".to_string(),
+            "--detect-text-regions".to_string(),
+            "--panels".to_string(),
+            "--interpreter".to_string(),
+            "kakasi,gloss".to_string(),
+            "--lang".to_string(),
+            "ja".to_string(),
+            "../assets".to_string(),
+            "../assets/ubunchu01_02*.png".to_string(),
+        ];
+        // main's options (--min-confidence, --interpreter, --lang) are dropped before the parse
+        let batch_args = BatchOcrArgs::parse(&crate::without_main_options(&args)).unwrap();
+        assert!(BatchOcrArgs::parse(&args).is_err());
+        assert_eq!(batch_args.output_dir, Some(PathBuf::from("out")));
+        assert_eq!(batch_args.format, BatchOutputFormat::Json);
+        assert!(batch_args.split_panels && batch_args.detect_text_regions);
+        assert_eq!(batch_args.inputs.len(), 2);
+        assert!(BatchOcrArgs::parse(&["--bogus".to_string()]).is_err());
+        assert!(BatchOcrArgs::parse(&[
+            "--format".to_string(),
+            "pdf".to_string(),
+            "a.png".to_string()
+        ])
+        .is_err());
+
+        let image_paths = expand_inputs(&batch_args.inputs).unwrap();
+        let file_names = image_paths
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect::<Vec<String>>();
+        // directory first (sorted, and license_*.txt is skipped), then the glob matches
+        assert!(file_names.contains(&"ocr-comparison-table.png".to_string()));
+        assert!(!file_names.iter().any(|name| name.ends_with(".txt")));
+        assert_eq!(
+            file_names[file_names.len() - 2..],
+            [
+                "ubunchu01_02.png".to_string(),
+                "ubunchu01_02_panel01_section_02.png".to_string()
+            ]
+        );
+        assert!(expand_inputs(&["../assets/nothing_here_*.png".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_format_result() {
+        let mut ocr_result = OcrTraitResult::new();
+        ocr_result.lines = vec!["最近人気の".to_string()];
+        ocr_result.text = ocr_result.lines.join("\n");
+        ocr_result.rects = vec![OcrLine::new(vec![OcrWord::new_with_layout(
+            "最近人気の".to_string(),
+            0,
+            0,
+            0,
+            OcrRect::new(300, 20, 340, 230),
+            Some(0.98),
+        )])];
+        let interpreter_result = InterpreterTraitResult {
+            text: "さいきんにんきの".to_string(),
+            lines: vec!["さいきんにんきの".to_string()],
+            tokens: vec![],
+            translations: vec![],
+        };
+        let document = ResultDocument::new(
+            Some(Path::new("page01.png")),
+            360,
+            250,
+            OcrResultJson::from_ocr_result("tesseract", 0, &ocr_result),
+            Some(InterpreterResultJson::from_interpreter_result(
+                "kakasi",
+                0,
+                &interpreter_result,
+            )),
+        );
+        let report = format_result(&document);
+        assert_eq!(
+            report,
+            "# page01.png\n[text]\n最近人気の\n[hiragana]\nさいきんにんきの\n[boxes]\n0\t300\t20\t340\t230\t0.980\t最近人気の\n"
+        );
+
+        // the saved result is exported again without the OCR (which is not even installed in CI) being called
+        let dir = std::env::temp_dir().join(format!("lenzu_batch_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let json_path = dir.join("page01.json");
+        fs::write(&json_path, document.to_json().unwrap()).unwrap();
+        let export = |format: &str, output_dir: &str, inputs: &str| {
+            let batch_args = BatchOcrArgs::parse(&[
+                "--format".to_string(),
+                format.to_string(),
+                "--output-dir".to_string(),
+                dir.join(output_dir).to_string_lossy().into_owned(),
+                dir.join(inputs).to_string_lossy().into_owned(),
+            ])
+            .unwrap();
+            run(
+                &batch_args,
+                &crate::ocr_tesseract::OcrTesseract::new(),
+                &crate::interpreter_passthrough::InterpreterPassthrough::new(),
+                &PreprocessPipeline::parse("none").unwrap(),
+                0.0,
+            )
+            .unwrap()
+        };
+        assert_eq!(export("text", "out", "*.json"), 0);
+        assert_eq!(
+            fs::read_to_string(dir.join("out").join("page01.txt")).unwrap(),
+            report
+        );
+        // and so are the saved hOCR and ALTO, which only keep the OCR part (not the image path nor the readings)
+        assert_eq!(export("hocr", "hocr", "*.json"), 0);
+        assert_eq!(export("alto", "alto", "hocr/*.hocr"), 0);
+        assert_eq!(export("text", "alto", "alto/*.xml"), 0);
+        assert_eq!(
+            fs::read_to_string(dir.join("alto").join("page01.txt")).unwrap(),
+            "# -\n[text]\n最近人気の\n[hiragana]\n[boxes]\n0\t300\t20\t340\t230\t0.980\t最近人気の\n"
+        );
+
+        // page01.json and page01.hocr are both page01.txt, so the second one fails rather than overwrites the first
+        fs::create_dir_all(dir.join("same_stem")).unwrap();
+        fs::copy(&json_path, dir.join("same_stem").join("page01.json")).unwrap();
+        fs::copy(
+            dir.join("hocr").join("page01.hocr"),
+            dir.join("same_stem").join("page01.hocr"),
+        )
+        .unwrap();
+        assert_eq!(export("text", "same_stem_out", "same_stem/page01.*"), 1);
+        assert_eq!(fs::read_dir(dir.join("same_stem_out")).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,7 +1,5 @@
-use winapi::{
-    shared::windef::RECT,
-    um::winuser::{GetCursorPos, GetMonitorInfoW, GetWindowRect, MonitorFromPoint, PostQuitMessage, MONITORINFO, MONITOR_DEFAULTTONEAREST},
-};
+use crate::screen_capture_traits::{ScreenCaptureTrait, ScreenRect};
+use anyhow::Error;
 
 #[derive(Debug, Clone, Copy)]
 pub(crate) struct CursorData {
@@ -35,82 +33,64 @@ impl CursorData {
         }
     }
 
-    pub fn update(&mut self, application_window_handle: winapi::shared::windef::HWND) {
+    // window_width/window_height is the current dimension of the application (lens) window, which is platform
+    // (window system) specific, hence the caller is responsible for it (i.e. GetWindowRect() on Win32)
+    pub fn update(
+        &mut self,
+        screen_capture: &dyn ScreenCaptureTrait,
+        window_width: u32,
+        window_height: u32,
+    ) -> Result<(), Error> {
         // first, get cursor position so that we can dtermine which monitor we are on
-        let mut cursor_pos = winapi::shared::windef::POINT { x: 0, y: 0 };
-        if unsafe { GetCursorPos(&mut cursor_pos) } == 0 {
-            // Handle the error appropriately if necessary.
-            println!("Could not get cursor position");
-            // post quit
-            unsafe { PostQuitMessage(-1) };
-        }
-        self.x = cursor_pos.x;
-        self.y = cursor_pos.y;
-
-        // Get dimension of the monitor the cursor is currently on (see MonitorFromPoint()) via GetMonitorInfoW()
-        let h_monitor = unsafe { MonitorFromPoint(cursor_pos, MONITOR_DEFAULTTONEAREST) };
-        let mut monitor_info = MONITORINFO {
-            cbSize: std::mem::size_of::<MONITORINFO>() as u32,
-            rcMonitor: RECT {
-                // display area rectangle
-                left: 0,
-                top: 0,
-                right: 0,
-                bottom: 0,
-            },
-            rcWork: RECT {
-                // work area rectangle (rectangle not obscured by taskbar and toolbar)
-                left: 0,
-                top: 0,
-                right: 0,
-                bottom: 0,
-            },
-            dwFlags: 0,
-        };
+        let (cursor_x, cursor_y) = screen_capture.cursor_position()?;
+        self.x = cursor_x;
+        self.y = cursor_y;
 
-        unsafe {
-            GetMonitorInfoW(h_monitor, &mut monitor_info);
+        // Get dimension of the monitor the cursor is currently on (or nearest to, same as MONITOR_DEFAULTTONEAREST)
+        let monitors = screen_capture.monitors()?;
+        let possible_monitor = monitors
+            .iter()
+            .min_by_key(|monitor| monitor.rect.distance_squared(self.x, self.y));
+        if let Some(monitor) = possible_monitor {
+            // note that we use work area rectangle, so that we can ignore the taskbar and toolbar
+            // work area, unlike monitor area, is usually/should-be positive because it's the area that's not obscured by the taskbar and toolbar
+            self.monitor_x = monitor.work_area.x; // can be negative, based on being placed LEFT of the PRIMARY monitor
+            self.monitor_y = monitor.work_area.y;
+            self.monitor_width = std::cmp::max(monitor.work_area.width, 1024);
+            self.monitor_height = std::cmp::max(monitor.work_area.height, 768);
         }
-        // note that we use rcWork rectangle, so that we can ignore the taskbar and toolbar
-        // work area, unlike monitor area, is usually/should-be positive because it's the area that's not obscured by the taskbar and toolbar
-        self.monitor_x = monitor_info.rcWork.left; // can be negative, based on being placed LEFT of the PRIMARY monitor
-        self.monitor_y = monitor_info.rcWork.top;
-        self.monitor_width =
-            std::cmp::max(monitor_info.rcWork.right - monitor_info.rcWork.left, 1024) as u32;
-        self.monitor_height =
-            std::cmp::max(monitor_info.rcWork.bottom - monitor_info.rcWork.top, 768) as u32;
 
-        // get current dimension of the windown on the monitor via via GetWindowRect() (GetWindowInfo() can do the same, but it provides more info that we care...)
-        let mut window_rect = RECT {
-            left: 0,
-            top: 0,
-            right: 0,
-            bottom: 0,
-        };
-        // get the dimension of the application window
-        unsafe {
-            GetWindowRect(application_window_handle, &mut window_rect);
-        }
-        self.window_width = (window_rect.right - window_rect.left) as u32;
-        self.window_height = (window_rect.bottom - window_rect.top) as u32;
+        self.window_width = window_width;
+        self.window_height = window_height;
         // window position (upper left corner) is recalculated based off of cursor (x,y) and upper-left is offset by center of window to be where the mouse cursor will be
         // the tricky part of this is that the windows position coordinate can be negative (same as cursor position) so it's not possible to test for min()/max() for
         // edge of the monitor, and so we'll not do snap to monitor and allow windows to get beyond the edges of the monitors
         self.window_x = self.x - (self.window_width as i32 / 2) as i32;
         self.window_y = self.y - (self.window_height as i32 / 2) as i32;
+        Ok(())
     }
-    
+
     pub(crate) fn window_width(&self) -> u32 {
         self.window_width
     }
     pub(crate) fn window_height(&self) -> u32 {
         self.window_height
     }
-    
+
     pub(crate) fn window_x(&self) -> i32 {
         self.window_x
     }
     pub(crate) fn window_y(&self) -> i32 {
         self.window_y
     }
+
+    // the area (underneath the window) to be captured
+    pub(crate) fn window_rect(&self) -> ScreenRect {
+        ScreenRect::new(
+            self.window_x,
+            self.window_y,
+            self.window_width,
+            self.window_height,
+        )
+    }
 }
@@ -0,0 +1,345 @@
+// Japanese deinflection (as Rikaikun/Yomitan do it): the dictionaries only have the dictionary forms (i.e. 読む,
+// 高い), so an inflected word of the text (i.e. 読んでいた, 高くなかった) is turned back into the candidates of its
+// dictionary form by undoing the suffixes one at a time, each rule telling what kind of word it applies to and
+// what kind of word it results in; a candidate is only valid if the dictionary entry is of that kind (see
+// word_class()), which weeds out most of the nonsense candidates (i.e. 読んでいた -> 読んでいる as a v1 verb)
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+// kinds of words (as a bitmask) that a rule applies to and results in
+pub(crate) const WORD_CLASS_V1: u8 = 1; // ichidan verb (i.e. 食べる)
+pub(crate) const WORD_CLASS_V5: u8 = 2; // godan verb (i.e. 読む)
+pub(crate) const WORD_CLASS_VK: u8 = 4; // 来る
+pub(crate) const WORD_CLASS_VS: u8 = 8; // する
+pub(crate) const WORD_CLASS_ADJ_I: u8 = 16; // i-adjective (i.e. 高い), and also the negative ない of verbs
+const WORD_CLASS_IRU: u8 = 32; // te-form followed by いる (not a word on its own, only for chaining rules)
+const MAX_DEINFLECTIONS: usize = 256; // candidates of a single word, which is far more than any real word has
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Deinflection {
+    pub term: String,               // candidate of the dictionary form
+    pub word_class: u8, // what the dictionary entry must be, 0 if it is the word as-is (anything goes)
+    pub reasons: Vec<&'static str>, // outermost first, i.e. ["negative", "past"] for 読まなかった
+}
+
+struct Rule {
+    inflected: String,
+    base: String,
+    word_class_in: u8,
+    word_class_out: u8,
+    reason: &'static str,
+}
+
+// godan rows: dictionary form ending, i/a/e/o-stems, and the te/ta forms
+const GODAN_ROWS: [(&str, &str, &str, &str, &str, &str, &str); 10] = [
+    ("う", "い", "わ", "え", "お", "って", "った"),
+    ("く", "き", "か", "け", "こ", "いて", "いた"),
+    ("く", "き", "か", "け", "こ", "って", "った"), // 行く
+    ("ぐ", "ぎ", "が", "げ", "ご", "いで", "いだ"),
+    ("す", "し", "さ", "せ", "そ", "して", "した"),
+    ("つ", "ち", "た", "て", "と", "って", "った"),
+    ("ぬ", "に", "な", "ね", "の", "んで", "んだ"),
+    ("ぶ", "び", "ば", "べ", "ぼ", "んで", "んだ"),
+    ("む", "み", "ま", "め", "も", "んで", "んだ"),
+    ("る", "り", "ら", "れ", "ろ", "って", "った"),
+];
+
+fn rules() -> &'static Vec<Rule> {
+    static RULES: OnceLock<Vec<Rule>> = OnceLock::new();
+    RULES.get_or_init(|| {
+        let mut rules = vec![];
+        let mut rule =
+            |inflected: String, base: &str, word_class_in: u8, word_class_out: u8, reason| {
+                rules.push(Rule {
+                    inflected,
+                    base: base.to_string(),
+                    word_class_in,
+                    word_class_out,
+                    reason,
+                })
+            };
+
+        // forms that are built on a stem, as (suffix, kind of word the inflected form is, reason)
+        let polite = [
+            ("ます", WORD_CLASS_V1, "polite"), // not really v1, but can be inflected (i.e. ません) through the rules below
+            ("ました", 0, "polite past"),
+            ("ません", 0, "polite negative"),
+            ("ませんでした", 0, "polite past negative"),
+            ("ましょう", 0, "polite volitional"),
+            ("たい", WORD_CLASS_ADJ_I, "-tai"),
+            ("すぎる", WORD_CLASS_V1, "too much"),
+            ("なさい", 0, "polite imperative"),
+        ];
+
+        // ichidan, and the stems of kuru (来る/くる) and suru
+        for (suffix, word_class_in, reason) in polite.iter() {
+            rule(
+                suffix.to_string(),
+                "る",
+                *word_class_in,
+                WORD_CLASS_V1,
+                reason,
+            );
+            rule(
+                format!("来{}", suffix),
+                "来る",
+                *word_class_in,
+                WORD_CLASS_VK,
+                reason,
+            );
+            rule(
+                format!("き{}", suffix),
+                "くる",
+                *word_class_in,
+                WORD_CLASS_VK,
+                reason,
+            );
+            rule(
+                format!("し{}", suffix),
+                "する",
+                *word_class_in,
+                WORD_CLASS_VS,
+                reason,
+            );
+        }
+        let ichidan = [
+            ("た", 0, "past"),
+            ("て", WORD_CLASS_IRU, "-te"),
+            ("ない", WORD_CLASS_ADJ_I, "negative"),
+            ("られる", WORD_CLASS_V1, "potential or passive"),
+            ("させる", WORD_CLASS_V1, "causative"),
+            ("よう", 0, "volitional"),
+            ("ろ", 0, "imperative"),
+            ("れば", 0, "-ba"),
+            ("たら", 0, "-tara"),
+            ("たり", 0, "-tari"),
+            ("ず", 0, "-zu"),
+        ];
+        for (suffix, word_class_in, reason) in ichidan.iter() {
+            rule(
+                suffix.to_string(),
+                "る",
+                *word_class_in,
+                WORD_CLASS_V1,
+                reason,
+            );
+        }
+        let kuru = [
+            ("た", "き", 0, "past"),
+            ("て", "き", WORD_CLASS_IRU, "-te"),
+            ("ない", "こ", WORD_CLASS_ADJ_I, "negative"),
+            ("られる", "こ", WORD_CLASS_V1, "potential or passive"),
+            ("させる", "こ", WORD_CLASS_V1, "causative"),
+            ("よう", "こ", 0, "volitional"),
+            ("い", "こ", 0, "imperative"),
+            ("れば", "く", 0, "-ba"),
+            ("たら", "き", 0, "-tara"),
+        ];
+        for (suffix, stem, word_class_in, reason) in kuru.iter() {
+            rule(
+                format!("来{}", suffix),
+                "来る",
+                *word_class_in,
+                WORD_CLASS_VK,
+                reason,
+            );
+            rule(
+                format!("{}{}", stem, suffix),
+                "くる",
+                *word_class_in,
+                WORD_CLASS_VK,
+                reason,
+            );
+        }
+        let suru = [
+            ("した", 0, "past"),
+            ("して", WORD_CLASS_IRU, "-te"),
+            ("しない", WORD_CLASS_ADJ_I, "negative"),
+            ("できる", WORD_CLASS_V1, "potential"),
+            ("される", WORD_CLASS_V1, "passive"),
+            ("させる", WORD_CLASS_V1, "causative"),
+            ("しよう", 0, "volitional"),
+            ("しろ", 0, "imperative"),
+            ("すれば", 0, "-ba"),
+            ("したら", 0, "-tara"),
+        ];
+        for (inflected, word_class_in, reason) in suru.iter() {
+            rule(
+                inflected.to_string(),
+                "する",
+                *word_class_in,
+                WORD_CLASS_VS,
+                reason,
+            );
+        }
+
+        for (u, i, a, e, o, te, ta) in GODAN_ROWS.iter() {
+            for (suffix, word_class_in, reason) in polite.iter() {
+                rule(
+                    format!("{}{}", i, suffix),
+                    u,
+                    *word_class_in,
+                    WORD_CLASS_V5,
+                    reason,
+                );
+            }
+            let godan = [
+                (ta.to_string(), 0, "past"),
+                (te.to_string(), WORD_CLASS_IRU, "-te"),
+                (format!("{}ない", a), WORD_CLASS_ADJ_I, "negative"),
+                (format!("{}る", e), WORD_CLASS_V1, "potential"),
+                (format!("{}れる", a), WORD_CLASS_V1, "passive"),
+                (format!("{}せる", a), WORD_CLASS_V1, "causative"),
+                (format!("{}う", o), 0, "volitional"),
+                (e.to_string(), 0, "imperative"),
+                (format!("{}ば", e), 0, "-ba"),
+                (format!("{}ら", ta), 0, "-tara"),
+                (format!("{}り", ta), 0, "-tari"),
+                (format!("{}ず", a), 0, "-zu"),
+            ];
+            for (inflected, word_class_in, reason) in godan {
+                rule(inflected, u, word_class_in, WORD_CLASS_V5, reason);
+            }
+        }
+
+        let adjective = [
+            ("かった", 0, "past"),
+            ("くない", WORD_CLASS_ADJ_I, "negative"),
+            ("くて", 0, "-te"),
+            ("く", 0, "adverb"),
+            ("さ", 0, "noun"),
+            ("ければ", 0, "-ba"),
+            ("かったら", 0, "-tara"),
+            ("かったり", 0, "-tari"),
+            ("そう", 0, "-sou"),
+            ("すぎる", WORD_CLASS_V1, "too much"),
+        ];
+        for (suffix, word_class_in, reason) in adjective.iter() {
+            rule(
+                suffix.to_string(),
+                "い",
+                *word_class_in,
+                WORD_CLASS_ADJ_I,
+                reason,
+            );
+        }
+
+        // progressive/perfect (読んでいる) and its contraction (読んでる), back to the te-form
+        rule(
+            "いる".to_string(),
+            "",
+            WORD_CLASS_V1,
+            WORD_CLASS_IRU,
+            "progressive or perfect",
+        );
+        rule(
+            "てる".to_string(),
+            "て",
+            WORD_CLASS_V1,
+            WORD_CLASS_IRU,
+            "progressive or perfect",
+        );
+        rule(
+            "でる".to_string(),
+            "で",
+            WORD_CLASS_V1,
+            WORD_CLASS_IRU,
+            "progressive or perfect",
+        );
+        rules
+    })
+}
+
+// all the candidates of the dictionary form of the word, including the word itself (first)
+pub(crate) fn deinflect(word: &str) -> Vec<Deinflection> {
+    let mut deinflections = vec![Deinflection {
+        term: word.to_string(),
+        word_class: 0,
+        reasons: vec![],
+    }];
+    let mut seen: HashSet<(String, u8)> = HashSet::new();
+    let mut index = 0;
+    while index < deinflections.len() && deinflections.len() < MAX_DEINFLECTIONS {
+        let current = deinflections[index].clone();
+        index += 1;
+        for rule in rules().iter() {
+            if current.word_class != 0 && current.word_class & rule.word_class_in == 0 {
+                continue;
+            }
+            // the suffix must leave at least a character of the stem, or be the whole word of suru/kuru
+            let Some(stem) = current.term.strip_suffix(rule.inflected.as_str()) else {
+                continue;
+            };
+            if stem.is_empty() && rule.base.is_empty() {
+                continue;
+            }
+            let term = format!("{}{}", stem, rule.base);
+            if !seen.insert((term.clone(), rule.word_class_out)) {
+                continue;
+            }
+            let mut reasons = vec![rule.reason];
+            reasons.extend(current.reasons.iter());
+            deinflections.push(Deinflection {
+                term,
+                word_class: rule.word_class_out,
+                reasons,
+            });
+        }
+    }
+    deinflections
+}
+
+// kind of word from the part of speech of the dictionary (JMdict entity names such as "v5k", or Yomitan's rules
+// such as "v5"), 0 if it is not a word that inflects
+pub(crate) fn word_class(part_of_speech: &str) -> u8 {
+    match part_of_speech {
+        pos if pos.starts_with("v1") => WORD_CLASS_V1,
+        pos if pos.starts_with("v5") => WORD_CLASS_V5,
+        pos if pos.starts_with("vk") => WORD_CLASS_VK,
+        pos if pos.starts_with("vs") => WORD_CLASS_VS,
+        "adj-i" | "adj-ix" => WORD_CLASS_ADJ_I,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find<'a>(
+        deinflections: &'a [Deinflection],
+        term: &str,
+        word_class: u8,
+    ) -> Option<&'a Deinflection> {
+        deinflections.iter().find(|deinflection| {
+            deinflection.term == term && deinflection.word_class & word_class != 0
+        })
+    }
+
+    #[test]
+    fn test_deinflect() {
+        let deinflections = deinflect("読んでいなかった");
+        assert_eq!(deinflections[0].term, "読んでいなかった");
+        assert_eq!(
+            find(&deinflections, "読む", WORD_CLASS_V5).unwrap().reasons,
+            ["-te", "progressive or perfect", "negative", "past"]
+        );
+        assert_eq!(
+            find(&deinflect("食べられませんでした"), "食べる", WORD_CLASS_V1)
+                .unwrap()
+                .reasons,
+            ["potential or passive", "polite past negative"]
+        );
+        assert_eq!(
+            find(&deinflect("高くなかった"), "高い", WORD_CLASS_ADJ_I)
+                .unwrap()
+                .reasons,
+            ["negative", "past"]
+        );
+        assert!(find(&deinflect("来ない"), "来る", WORD_CLASS_VK).is_some());
+        assert!(find(&deinflect("しました"), "する", WORD_CLASS_VS).is_some());
+        assert!(find(&deinflect("行った"), "行く", WORD_CLASS_V5).is_some());
+        assert_eq!(word_class("v5k-s"), WORD_CLASS_V5);
+        assert_eq!(word_class("n"), 0);
+    }
+}
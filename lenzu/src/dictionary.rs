@@ -0,0 +1,710 @@
+// Offline dictionary lookup (as Yomitan/Rikaikun do it): rather than only the readings, each word of the OCR text
+// is looked up in JMdict (words) and JMnedict (names), and/or Yomitan dictionaries, for its glosses, readings,
+// JLPT/frequency tags.  The dictionaries are imported once into a local store:
+//      $ lenzu dictionary import [--output <store.bin>] <JMdict_e|JMdict|JMnedict.xml|dictionary.zip>...
+//      $ lenzu dictionary lookup [--dictionary <store.bin>] <text>
+// JMdict/JMnedict are the (uncompressed) XML from EDRDG, and the zips are Yomitan dictionaries (term banks, plus
+// term meta banks for the frequencies); the store is a bincode file of all the entries along with their index (by
+// kanji and by reading, normalized to hiragana), so that loading it does not index them again, and each store is
+// loaded once per process (the interpreters of a chain share it).  The store is --output/--dictionary, or
+// environment variable LENZU_DICTIONARY, or dictionary.bin in the current directory
+// The lookup is longest-match at each position of the text, deinflecting (see deinflect.rs) each candidate, so
+// that "読んでいた" is found as 読む (-te, progressive or perfect, past)
+use crate::deinflect::{deinflect, word_class};
+use crate::kana::to_hiragana;
+use anyhow::Error;
+use quick_xml::{events::Event, Reader};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader, BufWriter, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+pub(crate) const ENV_DICTIONARY: &str = "LENZU_DICTIONARY";
+pub(crate) const DEFAULT_DICTIONARY_STORE: &str = "dictionary.bin";
+const STORE_VERSION: u32 = 2; // 1 was JSON, without the index
+const MAX_LOOKUP_LENGTH: usize = 16; // in chars, longest word (inflected) that we look for
+const JMDICT_DEFAULT_LANGUAGE: &str = "eng"; // glosses without xml:lang
+const JMDICT_NF_RANK: u32 = 500; // nfXX is the XX-th 500 most frequent words of the newspapers
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct DictionarySense {
+    pub part_of_speech: Vec<String>, // JMdict entity names (i.e. "v5m", "n"), or Yomitan's tags and rules
+    pub language: String,            // of the glosses, i.e. "eng", "ger" for JMdict
+    pub glosses: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct DictionaryEntry {
+    pub source: String, // "JMdict", "JMnedict", or the title of the Yomitan dictionary
+    pub sequence: u64,  // ent_seq (JMdict), or sequence (Yomitan), 0 if none
+    pub kanji: Vec<String>, // empty for words that are only written in kana
+    pub readings: Vec<String>,
+    pub senses: Vec<DictionarySense>,
+    pub tags: Vec<String>, // priority (i.e. "news1", "ichi1", "nf05") and JLPT (i.e. "jlpt-n5") tags
+    pub frequency: Option<u32>, // rank, the lower the more frequent
+}
+
+// the store is the version, then the entries and their index (bincode is not self-describing, hence the version
+// first, so that a store of another version is told apart rather than misread)
+type DictionaryStore = (u32, Vec<DictionaryEntry>, HashMap<String, Vec<usize>>);
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct DictionaryMatchEntry<'a> {
+    pub base_form: String, // the deinflected form of the surface that was found (i.e. "読む")
+    pub reasons: Vec<String>, // i.e. ["past"], empty if the surface was found as-is
+    pub entry: &'a DictionaryEntry,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct DictionaryMatch<'a> {
+    pub surface: String,
+    pub start: usize, // char offsets into the text
+    pub end: usize,
+    pub entries: Vec<DictionaryMatchEntry<'a>>, // best first (found as-is, then the most frequent)
+}
+
+pub(crate) struct Dictionary {
+    entries: Vec<DictionaryEntry>,
+    index: HashMap<String, Vec<usize>>, // kanji, and readings in hiragana, to entries
+}
+
+fn hiragana(text: &str) -> String {
+    text.chars().map(to_hiragana).collect()
+}
+
+// JMdict (and JMnedict) XML, which defines its part of speech (and other tags) as DTD entities, i.e.
+// `<pos>&v5m;</pos>`, which we keep as the entity name ("v5m") rather than expanding them to their descriptions
+pub(crate) fn import_jmdict<R: BufRead>(xml: R) -> Result<Vec<DictionaryEntry>, Error> {
+    let mut reader = Reader::from_reader(xml);
+    reader.trim_text(true);
+    let mut buffer = Vec::new();
+    let mut source = "JMdict".to_string();
+    let mut entries = vec![];
+    let mut entry: Option<DictionaryEntry> = None;
+    let mut sense_pos: Vec<String> = vec![]; // JMdict: carries over to the following senses that have none
+    let mut sense_has_pos = false;
+    let mut sense_glosses: Vec<(String, String)> = vec![]; // (language, gloss)
+    let mut elements: Vec<String> = vec![];
+    let mut gloss_language = JMDICT_DEFAULT_LANGUAGE.to_string();
+    loop {
+        buffer.clear();
+        match reader.read_event_into(&mut buffer)? {
+            Event::Start(element) => {
+                let name = String::from_utf8_lossy(element.name().as_ref()).to_string();
+                match name.as_str() {
+                    "JMnedict" => source = "JMnedict".to_string(),
+                    "entry" => {
+                        entry = Some(DictionaryEntry {
+                            source: source.clone(),
+                            sequence: 0,
+                            kanji: vec![],
+                            readings: vec![],
+                            senses: vec![],
+                            tags: vec![],
+                            frequency: None,
+                        });
+                        sense_pos.clear();
+                    }
+                    "sense" | "trans" => {
+                        sense_has_pos = false;
+                        sense_glosses.clear();
+                    }
+                    "gloss" => {
+                        gloss_language = JMDICT_DEFAULT_LANGUAGE.to_string();
+                        for attribute in element.attributes() {
+                            let attribute = attribute?;
+                            if attribute.key.as_ref() == b"xml:lang" {
+                                gloss_language = attribute.unescape_value()?.to_string();
+                            }
+                        }
+                    }
+                    _ => (),
+                }
+                elements.push(name);
+            }
+            Event::Text(text) => {
+                let raw = String::from_utf8_lossy(text.as_ref()).to_string();
+                let value = match raw.strip_prefix('&').and_then(|raw| raw.strip_suffix(';')) {
+                    Some(entity) if !["lt", "gt", "amp", "quot", "apos"].contains(&entity) => {
+                        entity.to_string()
+                    }
+                    _ => text.unescape()?.to_string(),
+                };
+                let Some(entry) = entry.as_mut() else {
+                    continue;
+                };
+                match elements.last().map(|name| name.as_str()) {
+                    Some("ent_seq") => entry.sequence = value.parse().unwrap_or(0),
+                    Some("keb") => entry.kanji.push(value),
+                    Some("reb") => entry.readings.push(value),
+                    Some("ke_pri") | Some("re_pri") => {
+                        if let Some(rank) = value
+                            .strip_prefix("nf")
+                            .and_then(|nf| nf.parse::<u32>().ok())
+                        {
+                            let rank = rank * JMDICT_NF_RANK;
+                            entry.frequency = Some(
+                                entry
+                                    .frequency
+                                    .map_or(rank, |frequency| frequency.min(rank)),
+                            );
+                        }
+                        if !entry.tags.contains(&value) {
+                            entry.tags.push(value);
+                        }
+                    }
+                    Some("pos") | Some("name_type") => {
+                        if !sense_has_pos {
+                            sense_pos.clear();
+                            sense_has_pos = true;
+                        }
+                        sense_pos.push(value);
+                    }
+                    Some("gloss") => sense_glosses.push((gloss_language.clone(), value)),
+                    Some("trans_det") => {
+                        sense_glosses.push((JMDICT_DEFAULT_LANGUAGE.to_string(), value))
+                    }
+                    _ => (),
+                }
+            }
+            Event::End(_) => match elements.pop().as_deref() {
+                Some("sense") | Some("trans") => {
+                    if let Some(entry) = entry.as_mut() {
+                        // a sense per language, so that the glosses can be picked by the user's language
+                        let first_sense = entry.senses.len();
+                        for (language, gloss) in sense_glosses.drain(..) {
+                            match entry.senses[first_sense..]
+                                .iter_mut()
+                                .find(|sense| sense.language == language)
+                            {
+                                Some(sense) => sense.glosses.push(gloss),
+                                None => entry.senses.push(DictionarySense {
+                                    part_of_speech: sense_pos.clone(),
+                                    language,
+                                    glosses: vec![gloss],
+                                }),
+                            }
+                        }
+                    }
+                }
+                Some("entry") => entries.extend(entry.take()),
+                _ => (),
+            },
+            Event::Eof => break,
+            _ => (),
+        }
+    }
+    Ok(entries)
+}
+
+// text of a Yomitan glossary item: a plain string, {"type": "text", "text": ...}, or structured content (nested
+// {"tag": ..., "content": ...} elements), skipping images
+fn yomitan_glossary_text(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(text) => text.clone(),
+        serde_json::Value::Array(items) => items.iter().map(yomitan_glossary_text).collect(),
+        serde_json::Value::Object(object) => {
+            match object.get("type").and_then(|value| value.as_str()) {
+                Some("text") => object
+                    .get("text")
+                    .map(yomitan_glossary_text)
+                    .unwrap_or_default(),
+                Some("image") => String::new(),
+                _ => {
+                    let text = object
+                        .get("content")
+                        .map(yomitan_glossary_text)
+                        .unwrap_or_default();
+                    match object.get("tag").and_then(|value| value.as_str()) {
+                        Some("li") | Some("div") | Some("br") => format!("{}\n", text),
+                        _ => text,
+                    }
+                }
+            }
+        }
+        _ => String::new(),
+    }
+}
+
+// frequency of a Yomitan term meta bank entry, which is either the number as-is, or {"value": ...}, or the
+// same per reading {"reading": ..., "frequency": ...}
+fn yomitan_frequency(value: &serde_json::Value) -> (Option<String>, Option<u32>) {
+    match value {
+        serde_json::Value::Number(number) => (None, number.as_f64().map(|number| number as u32)),
+        serde_json::Value::String(text) => (
+            None,
+            text.split_whitespace()
+                .next()
+                .and_then(|text| text.parse().ok()),
+        ),
+        serde_json::Value::Object(object) => match object.get("frequency") {
+            Some(frequency) => (
+                object
+                    .get("reading")
+                    .and_then(|value| value.as_str())
+                    .map(hiragana),
+                yomitan_frequency(frequency).1,
+            ),
+            None => (
+                None,
+                object
+                    .get("value")
+                    .and_then(|value| yomitan_frequency(value).1),
+            ),
+        },
+        _ => (None, None),
+    }
+}
+
+fn split_tags(value: &serde_json::Value) -> Vec<String> {
+    value
+        .as_str()
+        .unwrap_or_default()
+        .split_whitespace()
+        .map(|tag| tag.to_string())
+        .collect()
+}
+
+// Yomitan dictionary (zip): index.json, term_bank_*.json of
+// [expression, reading, definition tags, rules, score, glossary, sequence, term tags] rows, and term_meta_bank_*.json
+// of [expression, "freq", frequency] rows; the frequencies are returned separately (by kanji or reading, and
+// optionally the reading), since they are usually a dictionary of their own to apply to the other dictionaries
+#[allow(clippy::type_complexity)]
+pub(crate) fn import_yomitan(
+    path: &Path,
+) -> Result<(Vec<DictionaryEntry>, Vec<(String, Option<String>, u32)>), Error> {
+    let mut archive = zip::ZipArchive::new(fs::File::open(path)?)?;
+    let read_json =
+        |archive: &mut zip::ZipArchive<fs::File>, name: &str| -> Result<serde_json::Value, Error> {
+            let mut content = String::new();
+            archive.by_name(name)?.read_to_string(&mut content)?;
+            Ok(serde_json::from_str(&content)?)
+        };
+    let index = read_json(&mut archive, "index.json")?;
+    let title = index["title"].as_str().unwrap_or("Yomitan").to_string();
+    let language = index["targetLanguage"]
+        .as_str()
+        .unwrap_or(JMDICT_DEFAULT_LANGUAGE)
+        .to_string();
+
+    let mut names: Vec<String> = archive.file_names().map(|name| name.to_string()).collect();
+    names.sort();
+    let mut entries = vec![];
+    let mut frequencies = vec![];
+    for name in names.iter() {
+        if name.starts_with("term_bank_") {
+            for row in read_json(&mut archive, name)?
+                .as_array()
+                .into_iter()
+                .flatten()
+            {
+                let (Some(expression), Some(reading)) = (row[0].as_str(), row[1].as_str()) else {
+                    continue;
+                };
+                let mut part_of_speech = split_tags(&row[2]);
+                part_of_speech.extend(split_tags(&row[3]));
+                let glosses: Vec<String> = row[5]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(|gloss| yomitan_glossary_text(gloss).trim().to_string())
+                    .filter(|gloss| !gloss.is_empty())
+                    .collect();
+                let (kanji, readings) = if reading.is_empty() || reading == expression {
+                    (vec![], vec![expression.to_string()])
+                } else {
+                    (vec![expression.to_string()], vec![reading.to_string()])
+                };
+                entries.push(DictionaryEntry {
+                    source: title.clone(),
+                    sequence: row[6].as_u64().unwrap_or(0),
+                    kanji,
+                    readings,
+                    senses: vec![DictionarySense {
+                        part_of_speech,
+                        language: language.clone(),
+                        glosses,
+                    }],
+                    tags: split_tags(&row[7]),
+                    frequency: None,
+                });
+            }
+        } else if name.starts_with("term_meta_bank_") {
+            for row in read_json(&mut archive, name)?
+                .as_array()
+                .into_iter()
+                .flatten()
+            {
+                if let (Some(expression), Some("freq")) = (row[0].as_str(), row[1].as_str()) {
+                    if let (reading, Some(frequency)) = yomitan_frequency(&row[2]) {
+                        frequencies.push((expression.to_string(), reading, frequency));
+                    }
+                }
+            }
+        }
+    }
+    Ok((entries, frequencies))
+}
+
+impl Dictionary {
+    pub fn new(entries: Vec<DictionaryEntry>) -> Self {
+        let mut index: HashMap<String, Vec<usize>> = HashMap::new();
+        for (entry_index, entry) in entries.iter().enumerate() {
+            let mut keys: Vec<String> = entry.kanji.clone();
+            keys.extend(entry.readings.iter().map(|reading| hiragana(reading)));
+            keys.dedup();
+            for key in keys {
+                let entry_indices = index.entry(key).or_default();
+                if !entry_indices.contains(&entry_index) {
+                    entry_indices.push(entry_index);
+                }
+            }
+        }
+        Dictionary { entries, index }
+    }
+
+    // imports JMdict/JMnedict XML and Yomitan zips (told apart by their content), applying the frequencies of
+    // the Yomitan dictionaries to all the entries
+    pub fn import(paths: &[PathBuf]) -> Result<Self, Error> {
+        let mut entries = vec![];
+        let mut frequencies = vec![];
+        for path in paths.iter() {
+            let mut magic = [0u8; 2];
+            fs::File::open(path)?.read_exact(&mut magic)?;
+            if &magic == b"PK" {
+                let (yomitan_entries, yomitan_frequencies) = import_yomitan(path)?;
+                eprintln!(
+                    "{}: {} entries, {} frequencies",
+                    path.display(),
+                    yomitan_entries.len(),
+                    yomitan_frequencies.len()
+                );
+                entries.extend(yomitan_entries);
+                frequencies.extend(yomitan_frequencies);
+            } else {
+                let jmdict_entries = import_jmdict(BufReader::new(fs::File::open(path)?))?;
+                eprintln!("{}: {} entries", path.display(), jmdict_entries.len());
+                entries.extend(jmdict_entries);
+            }
+        }
+        let mut dictionary = Dictionary::new(entries);
+        for (expression, reading, frequency) in frequencies {
+            for entry_index in dictionary
+                .index
+                .get(&expression)
+                .cloned()
+                .unwrap_or_default()
+            {
+                let entry = &mut dictionary.entries[entry_index];
+                let reading_matches = reading.as_ref().is_none_or(|reading| {
+                    entry
+                        .readings
+                        .iter()
+                        .any(|entry_reading| hiragana(entry_reading) == *reading)
+                });
+                if reading_matches && (entry.kanji.contains(&expression) || entry.kanji.is_empty())
+                {
+                    entry.frequency = Some(
+                        entry
+                            .frequency
+                            .map_or(frequency, |current| current.min(frequency)),
+                    );
+                }
+            }
+        }
+        Ok(dictionary)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let bytes = fs::read(path)?;
+        let version: u32 = bincode::deserialize(&bytes).unwrap_or(0);
+        if version != STORE_VERSION {
+            return Err(anyhow::anyhow!(
+                "{}: not a dictionary store of version {}, please import the dictionaries again",
+                path.display(),
+                STORE_VERSION
+            ));
+        }
+        let (_, entries, index): DictionaryStore = bincode::deserialize(&bytes)?;
+        Ok(Dictionary { entries, index })
+    }
+
+    // the dictionary of the store, loaded on first use and then shared (i.e. by the jmdict and gloss interpreters
+    // of a chain, or by `lenzu list-backends`)
+    pub fn load_shared(path: &Path) -> Result<Arc<Self>, Error> {
+        static LOADED: OnceLock<Mutex<HashMap<PathBuf, Arc<Dictionary>>>> = OnceLock::new();
+        let key = fs::canonicalize(path).unwrap_or(path.to_path_buf());
+        let mut loaded = LOADED.get_or_init(Default::default).lock().unwrap();
+        if let Some(dictionary) = loaded.get(&key) {
+            return Ok(dictionary.clone());
+        }
+        let dictionary = Arc::new(Dictionary::load(path)?);
+        loaded.insert(key, dictionary.clone());
+        Ok(dictionary)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        bincode::serialize_into(
+            BufWriter::new(fs::File::create(path)?),
+            &(STORE_VERSION, &self.entries, &self.index),
+        )?;
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    // entries of the word (dictionary form) of the given kind (see deinflect::word_class(), 0 for any kind)
+    fn find(&self, term: &str, word_class_filter: u8) -> Vec<usize> {
+        let mut entry_indices = self.index.get(term).cloned().unwrap_or_default();
+        let term_hiragana = hiragana(term);
+        if term_hiragana != term {
+            for entry_index in self.index.get(&term_hiragana).into_iter().flatten() {
+                if !entry_indices.contains(entry_index) {
+                    entry_indices.push(*entry_index);
+                }
+            }
+        }
+        entry_indices.retain(|entry_index| {
+            word_class_filter == 0
+                || self.entries[*entry_index].senses.iter().any(|sense| {
+                    sense
+                        .part_of_speech
+                        .iter()
+                        .any(|pos| word_class(pos) & word_class_filter != 0)
+                })
+        });
+        entry_indices
+    }
+
+    // longest word at the char offset of the text, if any
+    pub fn lookup(&self, text: &str, start: usize) -> Option<DictionaryMatch<'_>> {
+        let chars: Vec<char> = text
+            .chars()
+            .skip(start)
+            .take_while(|c| !c.is_whitespace())
+            .take(MAX_LOOKUP_LENGTH)
+            .collect();
+        for length in (1..=chars.len()).rev() {
+            let surface: String = chars[..length].iter().collect();
+            let mut entries: Vec<DictionaryMatchEntry> = vec![];
+            for deinflection in deinflect(&surface) {
+                for entry_index in self.find(&deinflection.term, deinflection.word_class) {
+                    let entry = &self.entries[entry_index];
+                    if entries.iter().any(|matched| matched.entry == entry) {
+                        continue;
+                    }
+                    entries.push(DictionaryMatchEntry {
+                        base_form: deinflection.term.clone(),
+                        reasons: deinflection
+                            .reasons
+                            .iter()
+                            .map(|reason| reason.to_string())
+                            .collect(),
+                        entry,
+                    });
+                }
+            }
+            if !entries.is_empty() {
+                // stable, so that the dictionaries keep the order they were imported in
+                entries.sort_by_key(|matched| {
+                    (
+                        matched.reasons.len(),
+                        matched.entry.frequency.unwrap_or(u32::MAX),
+                        usize::MAX - matched.entry.tags.len(),
+                    )
+                });
+                return Some(DictionaryMatch {
+                    surface,
+                    start,
+                    end: start + length,
+                    entries,
+                });
+            }
+        }
+        None
+    }
+
+    // words of the text, longest-match from left to right, skipping the characters that are not in the dictionary
+    pub fn lookup_text(&self, text: &str) -> Vec<DictionaryMatch<'_>> {
+        let length = text.chars().count();
+        let mut matches = vec![];
+        let mut start = 0;
+        while start < length {
+            match self.lookup(text, start) {
+                Some(dictionary_match) => {
+                    start = dictionary_match.end;
+                    matches.push(dictionary_match);
+                }
+                None => start += 1,
+            }
+        }
+        matches
+    }
+}
+
+impl DictionaryEntry {
+    // glosses in the first of the languages that the entry has, or all of them if it has none of the languages
+    pub fn glosses(&self, languages: &[String]) -> Vec<&str> {
+        let language = languages
+            .iter()
+            .find(|language| self.senses.iter().any(|sense| sense.language == **language));
+        self.senses
+            .iter()
+            .filter(|sense| language.is_none_or(|language| sense.language == *language))
+            .flat_map(|sense| sense.glosses.iter().map(|gloss| gloss.as_str()))
+            .collect()
+    }
+}
+
+fn default_store() -> PathBuf {
+    std::env::var(ENV_DICTIONARY)
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(DEFAULT_DICTIONARY_STORE))
+}
+
+// `lenzu dictionary import|lookup ...` (args after "dictionary")
+pub(crate) fn run(args: &[String]) -> Result<(), Error> {
+    let usage = "Usage: lenzu dictionary import [--output <store.bin>] <JMdict|JMnedict.xml|dictionary.zip>...\n       lenzu dictionary lookup [--dictionary <store.bin>] <text>";
+    let mut store = default_store();
+    let mut inputs = vec![];
+    let mut arg_iter = args.iter().skip(1);
+    while let Some(arg) = arg_iter.next() {
+        match arg.as_str() {
+            "--output" | "-o" | "--dictionary" => match arg_iter.next() {
+                Some(path) => store = PathBuf::from(path),
+                None => return Err(anyhow::anyhow!("{} expects a file", arg)),
+            },
+            _ if arg.starts_with("--") => {
+                return Err(anyhow::anyhow!("Unknown option '{}'\n{}", arg, usage))
+            }
+            _ => inputs.push(arg.clone()),
+        }
+    }
+    match args.first().map(|arg| arg.as_str()) {
+        Some("import") if !inputs.is_empty() => {
+            let paths: Vec<PathBuf> = inputs.iter().map(PathBuf::from).collect();
+            let dictionary = Dictionary::import(&paths)?;
+            dictionary.save(&store)?;
+            println!("{}: {} entries", store.display(), dictionary.len());
+            Ok(())
+        }
+        Some("lookup") if !inputs.is_empty() => {
+            let dictionary = Dictionary::load(&store)?;
+            let matches = dictionary.lookup_text(&inputs.join(" "));
+            println!("{}", serde_json::to_string_pretty(&matches)?);
+            Ok(())
+        }
+        _ => Err(anyhow::anyhow!("{}", usage)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_import_and_lookup() {
+        // a Yomitan frequency dictionary, applied to the JMdict entries
+        let zip_path = std::env::temp_dir().join(format!(
+            "lenzu_test_yomitan_frequency_{}.zip",
+            std::process::id()
+        ));
+        let mut zip_writer = zip::ZipWriter::new(fs::File::create(&zip_path).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        zip_writer.start_file("index.json", options).unwrap();
+        zip_writer
+            .write_all(br#"{"title": "Frequency", "format": 3, "revision": "1"}"#)
+            .unwrap();
+        zip_writer
+            .start_file("term_meta_bank_1.json", options)
+            .unwrap();
+        zip_writer
+            .write_all(r#"[["人気", "freq", {"reading": "にんき", "frequency": {"value": 900}}], ["読む", "freq", 300]]"#.as_bytes())
+            .unwrap();
+        zip_writer.start_file("term_bank_1.json", options).unwrap();
+        zip_writer
+            .write_all(r#"[["高い", "たかい", "adj-i", "adj-i", 0, ["high", {"type": "structured-content", "content": [{"tag": "span", "content": "expensive"}]}], 1, "jlpt-n5"]]"#.as_bytes())
+            .unwrap();
+        zip_writer.finish().unwrap();
+
+        let dictionary = Dictionary::import(&[
+            PathBuf::from("../assets/fixtures/jmdict_mini.xml"),
+            PathBuf::from("../assets/fixtures/jmnedict_mini.xml"),
+            zip_path.clone(),
+        ])
+        .unwrap();
+        fs::remove_file(&zip_path).unwrap();
+        assert_eq!(dictionary.len(), 5);
+
+        let matches = dictionary.lookup_text("人気の本を読んでいた。高くない");
+        let words: Vec<(&str, &str, &str)> = matches
+            .iter()
+            .map(|matched| {
+                (
+                    matched.surface.as_str(),
+                    matched.entries[0].base_form.as_str(),
+                    matched.entries[0].entry.readings[0].as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            words,
+            [
+                ("人気", "人気", "にんき"),
+                ("本", "本", "ほん"),
+                ("読んでいた", "読む", "よむ"),
+                ("高くない", "高い", "たかい")
+            ]
+        );
+        let reading = &matches[2].entries[0];
+        assert_eq!(reading.reasons, ["-te", "progressive or perfect", "past"]);
+        assert_eq!(reading.entry.frequency, Some(300));
+        assert_eq!(reading.entry.senses[0].part_of_speech, ["v5m", "vt"]);
+        assert_eq!(reading.entry.glosses(&["ger".to_string()]), ["lesen"]);
+        assert_eq!(
+            reading.entry.glosses(&["fre".to_string()]),
+            ["to read", "lesen"]
+        );
+        assert_eq!(matches[0].entries[0].entry.tags, ["news1", "nf01"]);
+        assert_eq!(matches[0].entries[0].entry.frequency, Some(500));
+        assert_eq!(
+            matches[3].entries[0].entry.glosses(&[]),
+            ["high", "expensive"]
+        );
+        assert_eq!(matches[3].entries[0].entry.tags, ["jlpt-n5"]);
+
+        // names (JMnedict) are found by their reading as well
+        let names = dictionary.lookup_text("やまだ");
+        assert_eq!(names[0].entries[0].entry.source, "JMnedict");
+        assert_eq!(
+            names[0].entries[0].entry.senses[0].part_of_speech,
+            ["surname"]
+        );
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dictionary =
+            Dictionary::import(&[PathBuf::from("../assets/fixtures/jmdict_mini.xml")]).unwrap();
+        let store =
+            std::env::temp_dir().join(format!("lenzu_test_dictionary_{}.bin", std::process::id()));
+        dictionary.save(&store).unwrap();
+        let loaded = Dictionary::load_shared(&store).unwrap();
+        assert_eq!(loaded.entries, dictionary.entries);
+        assert_eq!(loaded.index, dictionary.index);
+        assert!(Arc::ptr_eq(
+            &loaded,
+            &Dictionary::load_shared(&store).unwrap()
+        ));
+
+        // a store of the previous (JSON) version is not misread
+        fs::write(&store, r#"{"version":1,"entries":[]}"#).unwrap();
+        assert!(Dictionary::load(&store).is_err());
+        fs::remove_file(&store).unwrap();
+    }
+}
@@ -0,0 +1,593 @@
+// OCR accuracy evaluation against ground truth, so that OCR backends and preprocessing choices are measured rather
+// than eyeballed (see README's comparison of Tesseract PSMs and Windows.Media.Ocr):
+//      $ lenzu eval [--output-dir <dir>] [--panels] [--detect-text-regions] [--preprocess <steps>] [--min-confidence <0.0..1.0>] [--ocr <name>] [--lang <lang>] <files|dirs|globs>...
+// the ground truth is next to each image, either as <image-stem>.gt.json (the rect is optional, per line):
+//      {"lines": [{"text": "最近人気の", "rect": {"x_min": 300, "y_min": 20, "x_max": 340, "y_max": 230}}, ...]}
+// or as <image-stem>.gt.txt with one line per line; either way the lines are in reading order, and images without
+// ground truth are skipped
+// metrics, per image and in total:
+//  - CER: edit (Levenshtein) distance of the characters over the ground truth length, ignoring whitespace (which
+//    Tesseract puts between Japanese characters) and full-width versus half-width ASCII (i.e. "！" and "!")
+//  - WER: the same over whitespace separated words, which is only meaningful for space separated languages
+//  - reading order: of the adjacent ground truth lines that both have a matching OCR line, the fraction that the
+//    OCR result has in the same order
+//  - IoU: mean IoU of the ground truth rects and their matching OCR line (0.0 if there is no match)
+//  - latency: OCR (including preprocessing) time in milliseconds
+// ground truth lines are matched to OCR lines by IoU if the ground truth has a rect, otherwise by text similarity
+// the report is a Markdown table on stdout, or <dir>/eval.json and <dir>/eval.md when --output-dir is passed
+use crate::batch_ocr::{evaluate_image, expand_inputs};
+use crate::image_handling::PreprocessPipeline;
+use crate::ocr_traits::{OcrRect, OcrTrait, OcrTraitResult};
+use anyhow::Error;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+// ground truth and OCR lines with less in common (IoU, or 1.0 - CER of the line) than this are not a match
+const MIN_LINE_MATCH_SCORE: f32 = 0.1;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct EvalArgs {
+    pub inputs: Vec<String>, // files, directories and/or glob patterns, as in 'lenzu ocr'
+    pub output_dir: Option<PathBuf>,
+    pub split_panels: bool,
+    pub detect_text_regions: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct GroundTruth {
+    pub lines: Vec<GroundTruthLine>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct GroundTruthLine {
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rect: Option<OcrRect>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct ImageEvaluation {
+    pub image: String,
+    pub character_count: usize, // of the ground truth, without whitespace
+    pub character_errors: usize,
+    pub cer: f32,
+    pub word_count: usize,
+    pub word_errors: usize,
+    pub wer: f32,
+    pub reading_order: Option<f32>, // None if fewer than two lines are matched
+    pub iou: Option<f32>,           // None if the ground truth has no rects
+    pub elapsed_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>, // the OCR failed, and is scored as if nothing was recognized
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct TotalEvaluation {
+    pub image_count: usize,
+    pub failed_count: usize,
+    pub character_count: usize,
+    pub character_errors: usize,
+    pub cer: f32, // over all the characters, so that longer pages weigh more
+    pub word_count: usize,
+    pub word_errors: usize,
+    pub wer: f32,
+    pub reading_order: Option<f32>, // mean of the images that have it
+    pub iou: Option<f32>,
+    pub mean_elapsed_ms: u64,
+    pub max_elapsed_ms: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct EvaluationReport {
+    pub backend: String,    // OcrTrait::name()
+    pub preprocess: String, // PreprocessPipeline::spec()
+    pub split_panels: bool,
+    pub detect_text_regions: bool,
+    pub min_confidence: f32,
+    pub images: Vec<ImageEvaluation>,
+    pub total: TotalEvaluation,
+}
+
+impl EvalArgs {
+    // args are whatever follows the "eval" subcommand, without the options that are handled by main() (as with
+    // 'lenzu ocr')
+    pub fn parse(args: &[String]) -> Result<Self, Error> {
+        let mut inputs = Vec::new();
+        let mut output_dir = None;
+        let mut split_panels = false;
+        let mut detect_text_regions = false;
+        let mut arg_iter = args.iter();
+        while let Some(arg) = arg_iter.next() {
+            match arg.as_str() {
+                "--output-dir" | "-o" => match arg_iter.next() {
+                    Some(dir) => output_dir = Some(PathBuf::from(dir)),
+                    None => return Err(anyhow::anyhow!("{} expects a directory", arg)),
+                },
+                "--panels" => split_panels = true,
+                "--detect-text-regions" => detect_text_regions = true,
+                _ if arg.starts_with("--") => {
+                    return Err(anyhow::anyhow!("Unknown option '{}' for 'eval'", arg))
+                }
+                _ => inputs.push(arg.clone()),
+            }
+        }
+        if inputs.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Usage: lenzu eval [--output-dir <dir>] [--panels] [--detect-text-regions] [--preprocess <steps>] [--min-confidence <0.0..1.0>] [--ocr <name>] [--lang <lang>] <files|dirs|globs>..."
+            ));
+        }
+        Ok(EvalArgs {
+            inputs,
+            output_dir,
+            split_panels,
+            detect_text_regions,
+        })
+    }
+}
+
+impl GroundTruth {
+    // <image-stem>.gt.json, or else <image-stem>.gt.txt, next to the image; None if neither exists
+    pub fn load_for_image(image_path: &Path) -> Result<Option<Self>, Error> {
+        let stem = image_path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let json_path = image_path.with_file_name(format!("{}.gt.json", stem));
+        if json_path.is_file() {
+            return Ok(Some(serde_json::from_str(&fs::read_to_string(json_path)?)?));
+        }
+        let text_path = image_path.with_file_name(format!("{}.gt.txt", stem));
+        if text_path.is_file() {
+            let lines = fs::read_to_string(text_path)?
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| GroundTruthLine {
+                    text: line.to_string(),
+                    rect: None,
+                })
+                .collect();
+            return Ok(Some(GroundTruth { lines }));
+        }
+        Ok(None)
+    }
+
+    pub fn text(&self) -> String {
+        self.lines
+            .iter()
+            .map(|line| line.text.as_str())
+            .collect::<Vec<&str>>()
+            .join("\n")
+    }
+}
+
+// full-width ASCII (U+FF01..U+FF5E) and the ideographic space to their half-width counterparts
+fn normalize_char(c: char) -> char {
+    match c {
+        '\u{ff01}'..='\u{ff5e}' => char::from_u32(c as u32 - 0xfee0).unwrap_or(c),
+        '\u{3000}' => ' ',
+        _ => c,
+    }
+}
+
+fn characters(text: &str) -> Vec<char> {
+    text.chars()
+        .map(normalize_char)
+        .filter(|c| !c.is_whitespace())
+        .collect()
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.chars()
+        .map(normalize_char)
+        .collect::<String>()
+        .split_whitespace()
+        .map(|word| word.to_string())
+        .collect()
+}
+
+// Levenshtein distance (insertions, deletions and substitutions all cost 1), one row at a time
+fn edit_distance<T: PartialEq>(expected: &[T], actual: &[T]) -> usize {
+    let mut previous_row = (0..=actual.len()).collect::<Vec<usize>>();
+    let mut current_row = vec![0; actual.len() + 1];
+    for (expected_index, expected_item) in expected.iter().enumerate() {
+        current_row[0] = expected_index + 1;
+        for (actual_index, actual_item) in actual.iter().enumerate() {
+            let substitution_cost = if expected_item == actual_item { 0 } else { 1 };
+            current_row[actual_index + 1] = (previous_row[actual_index] + substitution_cost)
+                .min(previous_row[actual_index + 1] + 1)
+                .min(current_row[actual_index] + 1);
+        }
+        std::mem::swap(&mut previous_row, &mut current_row);
+    }
+    previous_row[actual.len()]
+}
+
+// errors over the length of the expected; an empty expected is 0.0 if the actual is empty as well, otherwise 1.0
+fn error_rate(errors: usize, expected_count: usize) -> f32 {
+    match expected_count {
+        0 if errors == 0 => 0.0,
+        0 => 1.0,
+        _ => errors as f32 / expected_count as f32,
+    }
+}
+
+pub(crate) fn character_error_rate(expected: &str, actual: &str) -> f32 {
+    let expected_characters = characters(expected);
+    error_rate(
+        edit_distance(&expected_characters, &characters(actual)),
+        expected_characters.len(),
+    )
+}
+
+// for each ground truth line, the index of the matching OCR line (if any); pairs are taken greedily, best first,
+// so that each OCR line matches at most one ground truth line
+fn match_lines(ground_truth: &GroundTruth, ocr_result: &OcrTraitResult) -> Vec<Option<usize>> {
+    let ocr_texts = ocr_result
+        .rects
+        .iter()
+        .map(|line| line.text())
+        .collect::<Vec<String>>();
+    let mut candidates = Vec::new();
+    for (truth_index, truth_line) in ground_truth.lines.iter().enumerate() {
+        for (ocr_index, ocr_line) in ocr_result.rects.iter().enumerate() {
+            let score = match truth_line.rect {
+                Some(rect) => rect.iou(&ocr_line.rect()),
+                None => {
+                    1.0 - character_error_rate(&truth_line.text, &ocr_texts[ocr_index]).min(1.0)
+                }
+            };
+            if score >= MIN_LINE_MATCH_SCORE {
+                candidates.push((score, truth_index, ocr_index));
+            }
+        }
+    }
+    candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+    let mut matches = vec![None; ground_truth.lines.len()];
+    let mut is_ocr_matched = vec![false; ocr_result.rects.len()];
+    for (_, truth_index, ocr_index) in candidates {
+        if matches[truth_index].is_none() && !is_ocr_matched[ocr_index] {
+            matches[truth_index] = Some(ocr_index);
+            is_ocr_matched[ocr_index] = true;
+        }
+    }
+    matches
+}
+
+pub(crate) fn evaluate_result(
+    image: &str,
+    ground_truth: &GroundTruth,
+    ocr_result: &OcrTraitResult,
+    elapsed_ms: u64,
+) -> ImageEvaluation {
+    let expected_characters = characters(&ground_truth.text());
+    let character_errors = edit_distance(&expected_characters, &characters(&ocr_result.text));
+    let expected_words = words(&ground_truth.text());
+    let word_errors = edit_distance(&expected_words, &words(&ocr_result.text));
+
+    let matches = match_lines(ground_truth, ocr_result);
+    let matched_indices = matches.iter().flatten().collect::<Vec<&usize>>();
+    let reading_order = match matched_indices.len() {
+        0 | 1 => None,
+        count => {
+            let in_order_count = matched_indices
+                .windows(2)
+                .filter(|pair| pair[0] < pair[1])
+                .count();
+            Some(in_order_count as f32 / (count - 1) as f32)
+        }
+    };
+    let ious = ground_truth
+        .lines
+        .iter()
+        .zip(matches.iter())
+        .filter_map(|(truth_line, ocr_index)| {
+            truth_line.rect.map(|rect| match ocr_index {
+                Some(ocr_index) => rect.iou(&ocr_result.rects[*ocr_index].rect()),
+                None => 0.0,
+            })
+        })
+        .collect::<Vec<f32>>();
+    let iou = match ious.is_empty() {
+        true => None,
+        false => Some(ious.iter().sum::<f32>() / ious.len() as f32),
+    };
+    ImageEvaluation {
+        image: image.to_string(),
+        character_count: expected_characters.len(),
+        character_errors,
+        cer: error_rate(character_errors, expected_characters.len()),
+        word_count: expected_words.len(),
+        word_errors,
+        wer: error_rate(word_errors, expected_words.len()),
+        reading_order,
+        iou,
+        elapsed_ms,
+        error: None,
+    }
+}
+
+fn mean(values: &[f32]) -> Option<f32> {
+    match values.is_empty() {
+        true => None,
+        false => Some(values.iter().sum::<f32>() / values.len() as f32),
+    }
+}
+
+impl TotalEvaluation {
+    pub fn from_images(images: &[ImageEvaluation]) -> Self {
+        let character_count = images.iter().map(|image| image.character_count).sum();
+        let character_errors = images.iter().map(|image| image.character_errors).sum();
+        let word_count = images.iter().map(|image| image.word_count).sum();
+        let word_errors = images.iter().map(|image| image.word_errors).sum();
+        let elapsed_ms = images
+            .iter()
+            .map(|image| image.elapsed_ms)
+            .collect::<Vec<u64>>();
+        TotalEvaluation {
+            image_count: images.len(),
+            failed_count: images.iter().filter(|image| image.error.is_some()).count(),
+            character_count,
+            character_errors,
+            cer: error_rate(character_errors, character_count),
+            word_count,
+            word_errors,
+            wer: error_rate(word_errors, word_count),
+            reading_order: mean(
+                &images
+                    .iter()
+                    .filter_map(|image| image.reading_order)
+                    .collect::<Vec<f32>>(),
+            ),
+            iou: mean(
+                &images
+                    .iter()
+                    .filter_map(|image| image.iou)
+                    .collect::<Vec<f32>>(),
+            ),
+            mean_elapsed_ms: elapsed_ms.iter().sum::<u64>()
+                / std::cmp::max(elapsed_ms.len(), 1) as u64,
+            max_elapsed_ms: elapsed_ms.iter().copied().max().unwrap_or(0),
+        }
+    }
+}
+
+impl EvaluationReport {
+    pub fn to_markdown(&self) -> String {
+        let optional = |value: Option<f32>| match value {
+            Some(value) => format!("{:.3}", value),
+            None => "-".to_string(),
+        };
+        let yes_no = |value: bool| if value { "yes" } else { "no" };
+        let mut markdown = format!(
+            "OCR: `{}`, preprocess: `{}`, panels: {}, text regions: {}, min confidence: {}\n\n",
+            self.backend,
+            self.preprocess,
+            yes_no(self.split_panels),
+            yes_no(self.detect_text_regions),
+            self.min_confidence
+        );
+        markdown.push_str("| image | CER | WER | reading order | IoU | ms |\n");
+        markdown.push_str("|---|---:|---:|---:|---:|---:|\n");
+        for image in self.images.iter() {
+            markdown.push_str(&format!(
+                "| {}{} | {:.3} | {:.3} | {} | {} | {} |\n",
+                image.image,
+                if image.error.is_some() {
+                    " (failed)"
+                } else {
+                    ""
+                },
+                image.cer,
+                image.wer,
+                optional(image.reading_order),
+                optional(image.iou),
+                image.elapsed_ms
+            ));
+        }
+        markdown.push_str(&format!(
+            "| **total** ({} images) | {:.3} | {:.3} | {} | {} | {} (max {}) |\n",
+            self.total.image_count,
+            self.total.cer,
+            self.total.wer,
+            optional(self.total.reading_order),
+            optional(self.total.iou),
+            self.total.mean_elapsed_ms,
+            self.total.max_elapsed_ms
+        ));
+        markdown
+    }
+}
+
+pub(crate) fn run(
+    eval_args: &EvalArgs,
+    ocr: &dyn OcrTrait,
+    preprocess: &PreprocessPipeline,
+    min_confidence: f32,
+) -> Result<EvaluationReport, Error> {
+    let mut images = Vec::new();
+    for image_path in expand_inputs(&eval_args.inputs)? {
+        let ground_truth = match GroundTruth::load_for_image(&image_path)? {
+            Some(ground_truth) => ground_truth,
+            None => {
+                eprintln!("Skipping {} - no ground truth", image_path.display());
+                continue;
+            }
+        };
+        let start_time = std::time::Instant::now();
+        let ocr_result = evaluate_image(
+            &image_path,
+            ocr,
+            preprocess,
+            eval_args.split_panels,
+            eval_args.detect_text_regions,
+        )
+        .map(|ocr_result| ocr_result.filter_by_confidence(min_confidence));
+        let elapsed_ms = start_time.elapsed().as_millis() as u64;
+        let image_name = image_path.display().to_string();
+        images.push(match ocr_result {
+            Ok(ocr_result) => evaluate_result(&image_name, &ground_truth, &ocr_result, elapsed_ms),
+            Err(e) => {
+                eprintln!("Error: {} - {:?}", image_name, e);
+                ImageEvaluation {
+                    error: Some(e.to_string()),
+                    ..evaluate_result(
+                        &image_name,
+                        &ground_truth,
+                        &OcrTraitResult::new(),
+                        elapsed_ms,
+                    )
+                }
+            }
+        });
+    }
+    if images.is_empty() {
+        return Err(anyhow::anyhow!(
+            "No images with ground truth (<image-stem>.gt.json or <image-stem>.gt.txt) found"
+        ));
+    }
+    let report = EvaluationReport {
+        backend: ocr.name().to_string(),
+        preprocess: preprocess.spec(),
+        split_panels: eval_args.split_panels,
+        detect_text_regions: eval_args.detect_text_regions,
+        min_confidence,
+        total: TotalEvaluation::from_images(&images),
+        images,
+    };
+    match &eval_args.output_dir {
+        Some(output_dir) => {
+            fs::create_dir_all(output_dir)?;
+            let json_path = output_dir.join("eval.json");
+            fs::write(&json_path, serde_json::to_string_pretty(&report)? + "\n")?;
+            let markdown_path = output_dir.join("eval.md");
+            fs::write(&markdown_path, report.to_markdown())?;
+            println!("{} and {}", json_path.display(), markdown_path.display());
+        }
+        None => print!("{}", report.to_markdown()),
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::FakeOcr;
+
+    #[test]
+    fn test_metrics() {
+        assert_eq!(edit_distance(&['a', 'b', 'c'], &['a', 'x', 'c', 'd']), 2);
+        assert_eq!(
+            character_error_rate("リナックスです！", "リ ナ ッ ク ス で す !"),
+            0.0
+        );
+        assert_eq!(character_error_rate("最近人気の", "最近人の"), 0.2);
+        assert_eq!(character_error_rate("", ""), 0.0);
+        assert_eq!(character_error_rate("", "NN"), 1.0);
+        assert_eq!(
+            OcrRect::new(0, 0, 10, 10).iou(&OcrRect::new(5, 0, 15, 10)),
+            1.0 / 3.0
+        );
+        assert_eq!(
+            OcrRect::new(0, 0, 10, 10).iou(&OcrRect::new(10, 0, 20, 10)),
+            0.0
+        );
+
+        // text-only ground truth, with the OCR result in the wrong order: columns read left-to-right
+        let ground_truth = GroundTruth {
+            lines: ["最近人気の", "デスクトップな", "リナックスです！"]
+                .iter()
+                .map(|text| GroundTruthLine {
+                    text: text.to_string(),
+                    rect: None,
+                })
+                .collect(),
+        };
+        let mut ocr_result = FakeOcr::gcloud_fixture()
+            .evaluate(&image::DynamicImage::new_luma8(1, 1))
+            .unwrap();
+        ocr_result.rects.reverse();
+        let evaluation = evaluate_result("page.png", &ground_truth, &ocr_result, 10);
+        assert_eq!(evaluation.reading_order, Some(0.0));
+        assert_eq!(evaluation.iou, None);
+        assert_eq!(evaluation.character_count, 20);
+    }
+
+    #[test]
+    fn test_run_with_ground_truth() {
+        let dir = std::env::temp_dir().join(format!("lenzu_eval_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for page in ["page01", "page02", "page03"] {
+            fs::copy(
+                "../assets/ubunchu01_02_panel01_section_02.png",
+                dir.join(format!("{}.png", page)),
+            )
+            .unwrap();
+        }
+        // page01 has rects (one of them off by half), page02 is text-only (with a typo), page03 has none at all
+        let ground_truth = GroundTruth {
+            lines: vec![
+                GroundTruthLine {
+                    text: "最近人気の".to_string(),
+                    rect: Some(OcrRect::new(300, 20, 340, 230)),
+                },
+                GroundTruthLine {
+                    text: "デスクトップな".to_string(),
+                    rect: Some(OcrRect::new(240, 20, 280, 314)),
+                },
+                GroundTruthLine {
+                    text: "リナックスです！".to_string(),
+                    rect: Some(OcrRect::new(200, 20, 220, 356)),
+                },
+            ],
+        };
+        fs::write(
+            dir.join("page01.gt.json"),
+            serde_json::to_string(&ground_truth).unwrap(),
+        )
+        .unwrap();
+        fs::write(
+            dir.join("page02.gt.txt"),
+            "最近人気の\nデスクトップな\nリナックスでず！\n",
+        )
+        .unwrap();
+
+        let eval_args = EvalArgs::parse(&crate::without_main_options(&[
+            "--output-dir".to_string(),
+            dir.join("report").to_string_lossy().into_owned(),
+            "--min-confidence".to_string(),
+            "0.0".to_string(),
+            "--interpreter".to_string(),
+            "kakasi".to_string(),
+            dir.to_string_lossy().into_owned(),
+        ]))
+        .unwrap();
+        let report = run(
+            &eval_args,
+            &FakeOcr::gcloud_fixture(),
+            &PreprocessPipeline::parse("none").unwrap(),
+            0.0,
+        )
+        .unwrap();
+        assert_eq!(report.images.len(), 2);
+        assert_eq!(report.images[0].cer, 0.0);
+        assert_eq!(report.images[0].reading_order, Some(1.0));
+        assert_eq!(report.images[0].iou, Some((1.0 + 1.0 + 0.5) / 3.0));
+        assert_eq!(report.images[1].character_errors, 1);
+        assert_eq!(report.images[1].iou, None);
+        assert_eq!(report.total.character_count, 40);
+        assert_eq!(report.total.character_errors, 1);
+
+        let loaded: EvaluationReport = serde_json::from_str(
+            &fs::read_to_string(dir.join("report").join("eval.json")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(loaded, report);
+        let markdown = fs::read_to_string(dir.join("report").join("eval.md")).unwrap();
+        assert!(markdown.contains("| image | CER | WER | reading order | IoU | ms |"));
+        assert!(markdown.contains("| **total** (2 images) | 0.025 |"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,312 @@
+// ALTO v4 (https://www.loc.gov/standards/alto/) writer and reader, which is what (library) digitization tools and
+// search indexers commonly expect
+// ALTO has no paragraphs, so each of our blocks is a ComposedBlock with a TextBlock per paragraph, and like
+// export_hocr.rs, vertical lines are written with ROTATION="90"
+// NOTE: ALTO ROTATION is counter-clockwise, while OcrLine::angle() is clockwise
+use crate::ocr_traits::{OcrLine, OcrRect, OcrTraitResult, OcrWord, OcrWritingDirection};
+use anyhow::Error;
+use quick_xml::{escape::escape, events::Event, Reader};
+
+// HPOS/VPOS/WIDTH/HEIGHT attributes of the rectangle
+fn position(rect: &OcrRect) -> String {
+    format!(
+        "HPOS=\"{}\" VPOS=\"{}\" WIDTH=\"{}\" HEIGHT=\"{}\"",
+        rect.x_min,
+        rect.y_min,
+        rect.width(),
+        rect.height()
+    )
+}
+
+fn union_rect(lines: &[&OcrLine]) -> OcrRect {
+    lines
+        .iter()
+        .skip(1)
+        .fold(lines[0].rect(), |acc, line| acc.union(&line.rect()))
+}
+
+fn rotation(line: &OcrLine) -> String {
+    match (line.writing_direction(), line.angle()) {
+        (OcrWritingDirection::VerticalTtbRtl, _) => " ROTATION=\"90\"".to_string(),
+        (OcrWritingDirection::HorizontalLtr, Some(angle)) if angle != 0.0 => {
+            format!(" ROTATION=\"{}\"", (360.0 - angle).rem_euclid(360.0))
+        }
+        _ => "".to_string(),
+    }
+}
+
+pub(crate) fn to_alto(
+    ocr_result: &OcrTraitResult,
+    image_name: &str,
+    width: u32,
+    height: u32,
+    ocr_system: &str,
+) -> String {
+    let language = match &ocr_result.language {
+        Some(language) => format!(" LANG=\"{}\"", escape(language.as_str())),
+        None => "".to_string(),
+    };
+    let mut alto = String::new();
+    alto.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    alto.push_str("<alto xmlns=\"http://www.loc.gov/standards/alto/ns-v4#\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" xsi:schemaLocation=\"http://www.loc.gov/standards/alto/ns-v4# http://www.loc.gov/alto/v4/alto-4-2.xsd\">\n");
+    alto.push_str("  <Description>\n    <MeasurementUnit>pixel</MeasurementUnit>\n");
+    alto.push_str(&format!(
+        "    <sourceImageInformation>\n      <fileName>{}</fileName>\n    </sourceImageInformation>\n",
+        escape(image_name)
+    ));
+    alto.push_str(&format!(
+        "    <OCRProcessing ID=\"OCR_0\">\n      <ocrProcessingStep>\n        <processingSoftware>\n          <softwareName>lenzu {}</softwareName>\n        </processingSoftware>\n      </ocrProcessingStep>\n    </OCRProcessing>\n",
+        escape(ocr_system)
+    ));
+    alto.push_str("  </Description>\n  <Layout>\n");
+    alto.push_str(&format!(
+        "    <Page ID=\"page_0\" PHYSICAL_IMG_NR=\"1\" WIDTH=\"{}\" HEIGHT=\"{}\">\n",
+        width, height
+    ));
+    alto.push_str(&format!(
+        "      <PrintSpace {}>\n",
+        position(&OcrRect::new(0, 0, width as i32, height as i32))
+    ));
+    let mut paragraph_count = 0;
+    let mut line_count = 0;
+    let mut string_count = 0;
+    for (block_count, block) in ocr_result.blocks().iter().enumerate() {
+        let block_lines = block.iter().flatten().copied().collect::<Vec<&OcrLine>>();
+        alto.push_str(&format!(
+            "        <ComposedBlock ID=\"cblock_{}\" {}>\n",
+            block_count,
+            position(&union_rect(&block_lines))
+        ));
+        for paragraph in block.iter() {
+            alto.push_str(&format!(
+                "          <TextBlock ID=\"block_{}\" {}{}>\n",
+                paragraph_count,
+                position(&union_rect(paragraph)),
+                language
+            ));
+            paragraph_count += 1;
+            for line in paragraph.iter() {
+                alto.push_str(&format!(
+                    "            <TextLine ID=\"line_{}\" {}{}>\n",
+                    line_count,
+                    position(&line.rect()),
+                    rotation(line)
+                ));
+                line_count += 1;
+                for word in line.words().iter() {
+                    let confidence = match word.confidence() {
+                        Some(confidence) => format!(" WC=\"{}\"", confidence),
+                        None => "".to_string(),
+                    };
+                    alto.push_str(&format!(
+                        "              <String ID=\"string_{}\" {} CONTENT=\"{}\"{}/>\n",
+                        string_count,
+                        position(&word.rect()),
+                        escape(word.word().as_str()),
+                        confidence
+                    ));
+                    string_count += 1;
+                }
+                alto.push_str("            </TextLine>\n");
+            }
+            alto.push_str("          </TextBlock>\n");
+        }
+        alto.push_str("        </ComposedBlock>\n");
+    }
+    alto.push_str("      </PrintSpace>\n    </Page>\n  </Layout>\n</alto>\n");
+    alto
+}
+
+// ALTO allows fractional positions (i.e. in mm10 or inch1200), so parse as float and round to pixels
+fn parse_number(value: &str) -> Result<f32, Error> {
+    value
+        .trim()
+        .parse::<f32>()
+        .map_err(|e| anyhow::anyhow!("Invalid number '{}': {}", value, e))
+}
+
+// returns the result and the page (width, height); block/paragraph indices are renumbered page-global in the
+// order they appear, and a TextBlock that is not in a ComposedBlock (i.e. from other tools) is a block of its own
+pub(crate) fn from_alto(alto: &str) -> Result<(OcrTraitResult, u32, u32), Error> {
+    let mut reader = Reader::from_str(alto);
+    let mut ocr_result = OcrTraitResult::new();
+    let (mut width, mut height) = (0, 0);
+    let (mut block_index, mut paragraph_index): (i32, i32) = (-1, -1);
+    let mut is_in_composed_block = false;
+    let mut current_line: Option<OcrLine> = None;
+    loop {
+        let (element, is_empty) = match reader.read_event()? {
+            Event::Start(element) => (element, false),
+            Event::Empty(element) => (element, true),
+            Event::End(element) => {
+                match element.local_name().as_ref() {
+                    b"TextLine" => {
+                        if let Some(line) = current_line.take() {
+                            if !line.words().is_empty() {
+                                ocr_result.lines.push(line.text());
+                                ocr_result.rects.push(line);
+                            }
+                        }
+                    }
+                    b"ComposedBlock" => is_in_composed_block = false,
+                    _ => (),
+                }
+                continue;
+            }
+            Event::Eof => break,
+            _ => continue,
+        };
+        let mut attributes = std::collections::HashMap::new();
+        for attribute in element.attributes() {
+            let attribute = attribute?;
+            attributes.insert(
+                String::from_utf8_lossy(attribute.key.local_name().as_ref()).into_owned(),
+                attribute.unescape_value()?.to_string(),
+            );
+        }
+        let rect = || -> Result<OcrRect, Error> {
+            let number = |key: &str| match attributes.get(key) {
+                Some(value) => parse_number(value),
+                None => Err(anyhow::anyhow!("Missing {} attribute", key)),
+            };
+            let (x_min, y_min) = (number("HPOS")?, number("VPOS")?);
+            Ok(OcrRect::new(
+                x_min.round() as i32,
+                y_min.round() as i32,
+                (x_min + number("WIDTH")?).round() as i32,
+                (y_min + number("HEIGHT")?).round() as i32,
+            ))
+        };
+        match element.local_name().as_ref() {
+            b"Page" => {
+                width = parse_number(attributes.get("WIDTH").map_or("0", |w| w.as_str()))? as u32;
+                height = parse_number(attributes.get("HEIGHT").map_or("0", |h| h.as_str()))? as u32;
+            }
+            b"ComposedBlock" if !is_empty => {
+                block_index += 1;
+                is_in_composed_block = true;
+            }
+            b"TextBlock" => {
+                if !is_in_composed_block {
+                    block_index += 1;
+                }
+                paragraph_index += 1;
+                if ocr_result.language.is_none() {
+                    ocr_result.language = attributes.get("LANG").cloned();
+                }
+            }
+            b"TextLine" if !is_empty => {
+                let mut line = OcrLine::new(vec![]);
+                match attributes.get("ROTATION").map(|angle| parse_number(angle)) {
+                    Some(Ok(90.0)) => {
+                        line.set_writing_direction(OcrWritingDirection::VerticalTtbRtl)
+                    }
+                    Some(Ok(angle)) => {
+                        line.set_writing_direction(OcrWritingDirection::HorizontalLtr);
+                        line.set_angle((360.0 - angle).rem_euclid(360.0));
+                    }
+                    _ => (),
+                }
+                current_line = Some(line);
+            }
+            b"String" => {
+                let confidence = match attributes.get("WC") {
+                    Some(confidence) => Some(parse_number(confidence)?),
+                    None => None,
+                };
+                let word = OcrWord::new_with_layout(
+                    attributes.get("CONTENT").cloned().unwrap_or_default(),
+                    std::cmp::max(block_index, 0) as u16,
+                    std::cmp::max(paragraph_index, 0) as u16,
+                    ocr_result.rects.len() as u16,
+                    rect()?,
+                    confidence,
+                );
+                if let Some(line) = current_line.as_mut() {
+                    line.add_word(word);
+                }
+            }
+            _ => (),
+        }
+    }
+    ocr_result.text = ocr_result.lines.join("\n");
+    Ok((ocr_result, width, height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ocr_traits::OcrTrait;
+
+    fn assert_round_trips(ocr_result: &OcrTraitResult, width: u32, height: u32) -> String {
+        let alto = to_alto(ocr_result, "ubunchu01_02.png", width, height, "tesseract");
+        assert!(alto.contains(&format!("WIDTH=\"{}\" HEIGHT=\"{}\"", width, height)));
+        let (loaded, loaded_width, loaded_height) = from_alto(&alto).unwrap();
+        assert_eq!((loaded_width, loaded_height), (width, height));
+        assert_eq!(loaded.text, ocr_result.text);
+        assert_eq!(loaded.language, ocr_result.language);
+        assert_eq!(loaded.rects.len(), ocr_result.rects.len());
+        for (loaded_line, line) in loaded.rects.iter().zip(ocr_result.rects.iter()) {
+            assert_eq!(loaded_line.rect(), line.rect());
+            assert_eq!(loaded_line.writing_direction(), line.writing_direction());
+            for (loaded_word, word) in loaded_line.words().iter().zip(line.words().iter()) {
+                assert_eq!(loaded_word.word(), word.word());
+                assert_eq!(loaded_word.rect(), word.rect());
+                // WC is written as-is (0.0..=1.0), so unlike hOCR's x_wconf, there is no rounding
+                assert_eq!(loaded_word.confidence(), word.confidence());
+            }
+        }
+        // the same lines in the same paragraphs and blocks (the indices themselves are renumbered)
+        let shape = |ocr_result: &OcrTraitResult| {
+            ocr_result
+                .blocks()
+                .iter()
+                .map(|block| block.iter().map(|paragraph| paragraph.len()).collect())
+                .collect::<Vec<Vec<usize>>>()
+        };
+        assert_eq!(shape(&loaded), shape(ocr_result));
+        alto
+    }
+
+    #[test]
+    fn test_alto_keeps_paragraphs() {
+        // a block of two (vertical) paragraphs, and a block of one
+        let line = |text: &str, block_index, paragraph_index, line_index, x_min| {
+            let mut line = OcrLine::new(vec![OcrWord::new_with_layout(
+                text.to_string(),
+                block_index,
+                paragraph_index,
+                line_index,
+                OcrRect::new(x_min, 20, x_min + 40, 230),
+                Some(0.75),
+            )]);
+            line.set_writing_direction(OcrWritingDirection::VerticalTtbRtl);
+            line
+        };
+        let mut ocr_result = OcrTraitResult::new();
+        ocr_result.rects = vec![
+            line("最近人気の", 0, 0, 0, 300),
+            line("デスクトップな", 0, 1, 1, 250),
+            line("リナックスです!", 1, 2, 2, 100),
+        ];
+        ocr_result.lines = ocr_result.rects.iter().map(|line| line.text()).collect();
+        ocr_result.text = ocr_result.lines.join("\n");
+        ocr_result.language = Some("ja".to_string());
+
+        let alto = assert_round_trips(&ocr_result, 360, 250);
+        assert!(alto.contains("ROTATION=\"90\""));
+        assert_eq!(alto.matches("<ComposedBlock ").count(), 2);
+        assert_eq!(alto.matches("<TextBlock ").count(), 3);
+    }
+
+    #[test]
+    #[ignore = "needs Tesseract (with jpn and jpn_vert), run with `cargo test -- --ignored`"]
+    fn test_alto_round_trip() {
+        let image = image::open("../assets/ubunchu01_02.png").unwrap();
+        let mut ocr = crate::ocr_tesseract::OcrTesseract::new();
+        assert!(ocr.set_language("ja"));
+        let ocr_result = ocr.evaluate(&image).unwrap();
+        assert!(!ocr_result.rects.is_empty());
+        assert_round_trips(&ocr_result, image.width(), image.height());
+    }
+}
@@ -0,0 +1,353 @@
+// hOCR (http://kba.github.io/hocr-spec/1.2/) writer and reader, so that results can be loaded into existing
+// viewers, search indexers, and PDF text-layer tools (i.e. hocr-tools, OCRmyPDF)
+// The nesting is ocr_page > ocr_carea (block) > ocr_par (paragraph) > ocr_line > ocrx_word, and like Tesseract
+// does for jpn_vert, vertical lines are written as "textangle 90"
+// NOTE: hOCR angles are counter-clockwise, while OcrLine::angle() is clockwise
+use crate::ocr_traits::{OcrLine, OcrRect, OcrTraitResult, OcrWord, OcrWritingDirection};
+use anyhow::Error;
+use quick_xml::{escape::escape, events::Event, Reader};
+
+fn bbox(rect: &OcrRect) -> String {
+    format!(
+        "bbox {} {} {} {}",
+        rect.x_min, rect.y_min, rect.x_max, rect.y_max
+    )
+}
+
+fn union_rect(lines: &[&OcrLine]) -> OcrRect {
+    lines
+        .iter()
+        .skip(1)
+        .fold(lines[0].rect(), |acc, line| acc.union(&line.rect()))
+}
+
+// counter-clockwise textangle of the line, None for plain horizontal (unrotated) lines
+fn text_angle(line: &OcrLine) -> Option<f32> {
+    match (line.writing_direction(), line.angle()) {
+        (OcrWritingDirection::VerticalTtbRtl, _) => Some(90.0),
+        (OcrWritingDirection::HorizontalLtr, Some(angle)) if angle != 0.0 => {
+            Some((360.0 - angle).rem_euclid(360.0))
+        }
+        _ => None,
+    }
+}
+
+pub(crate) fn to_hocr(
+    ocr_result: &OcrTraitResult,
+    image_name: &str,
+    width: u32,
+    height: u32,
+    ocr_system: &str,
+) -> String {
+    let language = ocr_result.language.clone().unwrap_or_default();
+    let mut hocr = String::new();
+    hocr.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    hocr.push_str("<!DOCTYPE html PUBLIC \"-//W3C//DTD XHTML 1.0 Transitional//EN\" \"http://www.w3.org/TR/xhtml1/DTD/xhtml1-transitional.dtd\">\n");
+    hocr.push_str(&format!(
+        "<html xmlns=\"http://www.w3.org/1999/xhtml\" xml:lang=\"{0}\" lang=\"{0}\">\n",
+        escape(language.as_str())
+    ));
+    hocr.push_str(" <head>\n  <title></title>\n");
+    hocr.push_str("  <meta http-equiv=\"Content-Type\" content=\"text/html;charset=utf-8\"/>\n");
+    hocr.push_str(&format!(
+        "  <meta name=\"ocr-system\" content=\"lenzu {}\"/>\n",
+        escape(ocr_system)
+    ));
+    hocr.push_str("  <meta name=\"ocr-capabilities\" content=\"ocr_page ocr_carea ocr_par ocr_line ocrx_word ocrp_lang ocrp_wconf\"/>\n");
+    hocr.push_str(" </head>\n <body>\n");
+    hocr.push_str(&format!(
+        "  <div class=\"ocr_page\" id=\"page_1\" title=\"image &quot;{}&quot;; bbox 0 0 {} {}; ppageno 0\">\n",
+        escape(image_name),
+        width,
+        height
+    ));
+    let mut paragraph_count = 0;
+    let mut line_count = 0;
+    let mut word_count = 0;
+    for (block_number, block) in ocr_result.blocks().iter().enumerate() {
+        let block_lines = block.iter().flatten().copied().collect::<Vec<&OcrLine>>();
+        hocr.push_str(&format!(
+            "   <div class=\"ocr_carea\" id=\"block_1_{}\" title=\"{}\">\n",
+            block_number + 1,
+            bbox(&union_rect(&block_lines))
+        ));
+        for paragraph in block.iter() {
+            paragraph_count += 1;
+            hocr.push_str(&format!(
+                "    <p class=\"ocr_par\" id=\"par_1_{}\" lang=\"{}\" title=\"{}\">\n",
+                paragraph_count,
+                escape(language.as_str()),
+                bbox(&union_rect(paragraph))
+            ));
+            for line in paragraph.iter() {
+                line_count += 1;
+                let angle = match text_angle(line) {
+                    Some(angle) => format!("; textangle {}", angle),
+                    None => "".to_string(),
+                };
+                hocr.push_str(&format!(
+                    "     <span class=\"ocr_line\" id=\"line_1_{}\" title=\"{}{}\">",
+                    line_count,
+                    bbox(&line.rect()),
+                    angle
+                ));
+                for word in line.words().iter() {
+                    word_count += 1;
+                    // x_wconf is an integer percentage
+                    let confidence = match word.confidence() {
+                        Some(confidence) => format!("; x_wconf {}", (confidence * 100.0).round()),
+                        None => "".to_string(),
+                    };
+                    hocr.push_str(&format!(
+                        "\n      <span class=\"ocrx_word\" id=\"word_1_{}\" title=\"{}{}\">{}</span>",
+                        word_count,
+                        bbox(&word.rect()),
+                        confidence,
+                        escape(word.word().as_str())
+                    ));
+                }
+                hocr.push_str("\n     </span>\n");
+            }
+            hocr.push_str("    </p>\n");
+        }
+        hocr.push_str("   </div>\n");
+    }
+    hocr.push_str("  </div>\n </body>\n</html>\n");
+    hocr
+}
+
+// values of a property in the title attribute, i.e. ("bbox 0 0 10 20; x_wconf 95", "bbox") -> ["0", "0", "10", "20"]
+fn title_property<'a>(title: &'a str, key: &str) -> Option<Vec<&'a str>> {
+    title.split(';').find_map(|property| {
+        let mut values = property.split_whitespace();
+        match values.next() {
+            Some(name) if name == key => Some(values.collect()),
+            _ => None,
+        }
+    })
+}
+
+fn title_bbox(title: &str) -> Result<OcrRect, Error> {
+    let values = title_property(title, "bbox")
+        .ok_or_else(|| anyhow::anyhow!("Missing bbox in '{}'", title))?
+        .iter()
+        .map(|value| value.parse::<i32>())
+        .collect::<Result<Vec<i32>, _>>()?;
+    match values[..] {
+        [x_min, y_min, x_max, y_max] => Ok(OcrRect::new(x_min, y_min, x_max, y_max)),
+        _ => Err(anyhow::anyhow!("Malformed bbox in '{}'", title)),
+    }
+}
+
+fn title_number(title: &str, key: &str) -> Option<f32> {
+    title_property(title, key)
+        .and_then(|values| values.first().and_then(|value| value.parse::<f32>().ok()))
+}
+
+// what the element (that is opened) is, so that we know what to close when the end tag comes
+#[derive(PartialEq)]
+enum HocrElement {
+    Page,
+    Block,
+    Paragraph,
+    Line,
+    Word,
+    Other,
+}
+
+// returns the result and the page (width, height); block/paragraph/line indices are renumbered page-global
+// in the order they appear
+pub(crate) fn from_hocr(hocr: &str) -> Result<(OcrTraitResult, u32, u32), Error> {
+    let mut reader = Reader::from_str(hocr);
+    reader.trim_text(false);
+    let mut ocr_result = OcrTraitResult::new();
+    let (mut width, mut height) = (0, 0);
+    let mut elements: Vec<HocrElement> = Vec::new();
+    let (mut block_index, mut paragraph_index) = (-1, -1);
+    let mut current_line: Option<OcrLine> = None;
+    let mut current_word: Option<(String, OcrRect, Option<f32>)> = None;
+    loop {
+        match reader.read_event()? {
+            Event::Start(element) => {
+                let mut class = String::new();
+                let mut title = String::new();
+                let mut lang = String::new();
+                for attribute in element.attributes() {
+                    let attribute = attribute?;
+                    let value = attribute.unescape_value()?.to_string();
+                    match attribute.key.as_ref() {
+                        b"class" => class = value,
+                        b"title" => title = value,
+                        b"lang" => lang = value,
+                        _ => (),
+                    }
+                }
+                let hocr_element = match class.as_str() {
+                    "ocr_page" => {
+                        let page_rect = title_bbox(&title)?;
+                        width = page_rect.width();
+                        height = page_rect.height();
+                        HocrElement::Page
+                    }
+                    "ocr_carea" => {
+                        block_index += 1;
+                        HocrElement::Block
+                    }
+                    "ocr_par" => {
+                        paragraph_index += 1;
+                        if !lang.is_empty() && ocr_result.language.is_none() {
+                            ocr_result.language = Some(lang);
+                        }
+                        HocrElement::Paragraph
+                    }
+                    // Tesseract also writes headers, captions, etc as (special) lines
+                    "ocr_line" | "ocr_header" | "ocr_caption" | "ocr_textfloat" => {
+                        let mut line = OcrLine::new(vec![]);
+                        match title_number(&title, "textangle") {
+                            Some(90.0) => {
+                                line.set_writing_direction(OcrWritingDirection::VerticalTtbRtl)
+                            }
+                            Some(angle) => {
+                                line.set_writing_direction(OcrWritingDirection::HorizontalLtr);
+                                line.set_angle((360.0 - angle).rem_euclid(360.0));
+                            }
+                            None => (),
+                        }
+                        current_line = Some(line);
+                        HocrElement::Line
+                    }
+                    "ocrx_word" => {
+                        let confidence =
+                            title_number(&title, "x_wconf").map(|confidence| confidence / 100.0);
+                        current_word = Some((String::new(), title_bbox(&title)?, confidence));
+                        HocrElement::Word
+                    }
+                    _ => HocrElement::Other,
+                };
+                elements.push(hocr_element);
+            }
+            Event::Text(text) => {
+                if let Some((word_text, _, _)) = current_word.as_mut() {
+                    word_text.push_str(text.unescape()?.trim());
+                }
+            }
+            Event::End(_) => match elements.pop() {
+                Some(HocrElement::Word) => {
+                    if let (Some((word_text, rect, confidence)), Some(line)) =
+                        (current_word.take(), current_line.as_mut())
+                    {
+                        line.add_word(OcrWord::new_with_layout(
+                            word_text,
+                            std::cmp::max(block_index, 0) as u16,
+                            std::cmp::max(paragraph_index, 0) as u16,
+                            ocr_result.rects.len() as u16,
+                            rect,
+                            confidence,
+                        ));
+                    }
+                }
+                Some(HocrElement::Line) => {
+                    if let Some(line) = current_line.take() {
+                        if !line.words().is_empty() {
+                            ocr_result.lines.push(line.text());
+                            ocr_result.rects.push(line);
+                        }
+                    }
+                }
+                _ => (),
+            },
+            Event::Eof => break,
+            _ => (),
+        }
+    }
+    ocr_result.text = ocr_result.lines.join("\n");
+    Ok((ocr_result, width, height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ocr_traits::OcrTrait;
+
+    #[test]
+    #[ignore = "needs Tesseract (with jpn and jpn_vert), run with `cargo test -- --ignored`"]
+    fn test_hocr_round_trip() {
+        let image_path = "../assets/ubunchu01_02_panel01_section_02.png";
+        let image = image::open(image_path).unwrap();
+        let mut ocr = crate::ocr_tesseract::OcrTesseract::new();
+        assert!(ocr.set_language("ja"));
+        let mut ocr_result = ocr.evaluate(&image).unwrap();
+        assert!(!ocr_result.rects.is_empty());
+        // and a rotated horizontal line (in a block of its own) with a word that needs escaping
+        let block_index = ocr_result
+            .rects
+            .iter()
+            .map(|line| line.block_index() + 1)
+            .max()
+            .unwrap();
+        let paragraph_index = ocr_result
+            .rects
+            .iter()
+            .map(|line| line.paragraph_index() + 1)
+            .max()
+            .unwrap();
+        let mut rotated_line = OcrLine::new(vec![
+            OcrWord::new_with_layout(
+                "R&D".to_string(),
+                block_index,
+                paragraph_index,
+                ocr_result.rects.len() as u16,
+                OcrRect::new(10, 10, 80, 30),
+                Some(0.5),
+            ),
+            OcrWord::new_with_layout(
+                "<ubuntu>".to_string(),
+                block_index,
+                paragraph_index,
+                ocr_result.rects.len() as u16,
+                OcrRect::new(85, 10, 150, 30),
+                None,
+            ),
+        ]);
+        rotated_line.set_angle(10.0);
+        ocr_result.lines.push("R&D<ubuntu>".to_string());
+        ocr_result.rects.push(rotated_line);
+
+        let hocr = to_hocr(
+            &ocr_result,
+            "ubunchu01_02_panel01_section_02.png",
+            image.width(),
+            image.height(),
+            "tesseract",
+        );
+        assert!(hocr.contains(&format!("bbox 0 0 {} {}", image.width(), image.height())));
+        assert!(hocr.contains("textangle 350"));
+        assert!(hocr.contains("R&amp;D"));
+        assert!(hocr.contains("&lt;ubuntu&gt;"));
+
+        let (loaded, loaded_width, loaded_height) = from_hocr(&hocr).unwrap();
+        assert_eq!(
+            (loaded_width, loaded_height),
+            (image.width(), image.height())
+        );
+        assert_eq!(loaded.lines, ocr_result.lines);
+        assert_eq!(loaded.language, ocr_result.language);
+        assert_eq!(loaded.blocks().len(), ocr_result.blocks().len());
+        assert_eq!(loaded.rects.len(), ocr_result.rects.len());
+        for (loaded_line, line) in loaded.rects.iter().zip(ocr_result.rects.iter()) {
+            assert_eq!(loaded_line.rect(), line.rect());
+            assert_eq!(loaded_line.writing_direction(), line.writing_direction());
+            assert_eq!(loaded_line.angle(), line.angle());
+            for (loaded_word, word) in loaded_line.words().iter().zip(line.words().iter()) {
+                assert_eq!(loaded_word.word(), word.word());
+                assert_eq!(loaded_word.rect(), word.rect());
+                match (loaded_word.confidence(), word.confidence()) {
+                    (Some(loaded_confidence), Some(confidence)) => {
+                        assert!((loaded_confidence - confidence).abs() <= 0.005)
+                    }
+                    (loaded_confidence, confidence) => assert_eq!(loaded_confidence, confidence),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,244 @@
+// Export to mokuro (https://github.com/kha-white/mokuro) layout, so that lenzu's (offline) OCR results can be
+// read with mokuro's HTML reader (and whatever Anki tooling that already understands it):
+//  - per-page JSON (what mokuro writes to _ocr/<volume>/<page>.json)
+//  - <volume>.mokuro which is all the pages of a volume (chapter) in one file, each with img_path
+// mokuro's blocks are (more or less) speech bubbles, which is closest to our (block, paragraph), and lines are
+// listed in reading order as the OCR backend gave them
+use crate::ocr_traits::OcrWritingDirection;
+use crate::result_schema::{OcrLineJson, ResultDocument};
+use serde::{Deserialize, Serialize};
+
+// the version of mokuro whose output we are mimicking (the reader checks for it)
+pub(crate) const MOKURO_VERSION: &str = "0.2.1";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct MokuroBlock {
+    #[serde(rename = "box")]
+    pub bounding_box: [i32; 4], // x_min, y_min, x_max, y_max
+    pub vertical: bool,
+    pub font_size: f32, // in pixels, the thickness of the lines (width if vertical, height if horizontal)
+    pub lines_coords: Vec<[[f32; 2]; 4]>, // corners of each line, clockwise from upper left
+    pub lines: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct MokuroPage {
+    pub version: String,
+    pub img_width: u32,
+    pub img_height: u32,
+    pub blocks: Vec<MokuroBlock>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub img_path: Option<String>, // only in .mokuro (relative to the volume directory), not in per-page JSON
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct MokuroVolume {
+    pub version: String,
+    pub title: String,
+    pub title_uuid: String,
+    pub volume: String,
+    pub volume_uuid: String,
+    pub pages: Vec<MokuroPage>,
+}
+
+impl MokuroBlock {
+    fn from_lines(lines: &[&OcrLineJson]) -> Self {
+        let vertical_count = lines
+            .iter()
+            .filter(|line| line.writing_direction == OcrWritingDirection::VerticalTtbRtl)
+            .count();
+        let vertical = vertical_count * 2 >= lines.len();
+        let bounding_box = lines.iter().skip(1).fold(
+            [
+                lines[0].rect.x_min,
+                lines[0].rect.y_min,
+                lines[0].rect.x_max,
+                lines[0].rect.y_max,
+            ],
+            |acc, line| {
+                [
+                    std::cmp::min(acc[0], line.rect.x_min),
+                    std::cmp::min(acc[1], line.rect.y_min),
+                    std::cmp::max(acc[2], line.rect.x_max),
+                    std::cmp::max(acc[3], line.rect.y_max),
+                ]
+            },
+        );
+        // median rather than average, so that a stray (i.e. furigana or a punctuation-only) line does not skew it
+        let mut thicknesses = lines
+            .iter()
+            .map(|line| {
+                if vertical {
+                    line.rect.width()
+                } else {
+                    line.rect.height()
+                }
+            })
+            .collect::<Vec<u32>>();
+        thicknesses.sort();
+        let font_size = thicknesses[thicknesses.len() / 2] as f32;
+        MokuroBlock {
+            bounding_box,
+            vertical,
+            font_size,
+            lines_coords: lines
+                .iter()
+                .map(|line| {
+                    let (x_min, y_min, x_max, y_max) = (
+                        line.rect.x_min as f32,
+                        line.rect.y_min as f32,
+                        line.rect.x_max as f32,
+                        line.rect.y_max as f32,
+                    );
+                    [
+                        [x_min, y_min],
+                        [x_max, y_min],
+                        [x_max, y_max],
+                        [x_min, y_max],
+                    ]
+                })
+                .collect(),
+            // mokuro lines have no spaces between (Japanese) words, but Tesseract separates words with a space
+            lines: lines
+                .iter()
+                .map(|line| line.text.split_whitespace().collect::<String>())
+                .collect(),
+        }
+    }
+}
+
+impl MokuroPage {
+    pub fn from_result_document(document: &ResultDocument) -> Self {
+        // group lines into blocks by (block, paragraph), keeping the order in which each block first appears
+        let mut grouped_lines: Vec<((u16, u16), Vec<&OcrLineJson>)> = Vec::new();
+        for line in document.ocr.lines.iter() {
+            if line.rect.x_max <= line.rect.x_min || line.rect.y_max <= line.rect.y_min {
+                continue; // nothing the reader can show a box for
+            }
+            let key = (line.block_index, line.paragraph_index);
+            match grouped_lines
+                .iter_mut()
+                .find(|(group_key, _)| *group_key == key)
+            {
+                Some((_, group)) => group.push(line),
+                None => grouped_lines.push((key, vec![line])),
+            }
+        }
+        MokuroPage {
+            version: MOKURO_VERSION.to_string(),
+            img_width: document.image.width,
+            img_height: document.image.height,
+            blocks: grouped_lines
+                .iter()
+                .map(|(_, lines)| MokuroBlock::from_lines(lines))
+                .collect(),
+            img_path: None,
+        }
+    }
+}
+
+impl MokuroVolume {
+    pub fn new(title: &str, volume: &str, pages: Vec<MokuroPage>) -> Self {
+        MokuroVolume {
+            version: MOKURO_VERSION.to_string(),
+            title: title.to_string(),
+            title_uuid: Self::uuid_from_name(title),
+            volume: volume.to_string(),
+            volume_uuid: Self::uuid_from_name(&format!("{}/{}", title, volume)),
+            pages,
+        }
+    }
+
+    // the reader keys the reading progress by the uuids, so rather than random (v4) uuids, they are derived from the
+    // names so that re-exporting the same volume keeps the progress; this is a version 8 (custom) uuid made up of
+    // two FNV-1a hashes, since we do not need (nor want to depend on) anything cryptographic here
+    fn uuid_from_name(name: &str) -> String {
+        let fnv1a = |seed: u64| {
+            name.bytes().fold(seed, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+            })
+        };
+        let high = fnv1a(0xcbf2_9ce4_8422_2325);
+        let low = fnv1a(0x8422_2325_cbf2_9ce4);
+        let high = (high & 0xffff_ffff_ffff_0fff) | 0x0000_0000_0000_8000; // version 8
+        let low = (low & 0x3fff_ffff_ffff_ffff) | 0x8000_0000_0000_0000; // RFC 4122 variant
+        format!(
+            "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+            high >> 32,
+            (high >> 16) & 0xffff,
+            high & 0xffff,
+            low >> 48,
+            low & 0xffff_ffff_ffff
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ocr_traits::{OcrLine, OcrRect, OcrTraitResult, OcrWord};
+    use crate::result_schema::OcrResultJson;
+
+    #[test]
+    fn test_mokuro_page_and_volume() {
+        let word = |text: &str, paragraph_index: u16, line_index: u16, rect: OcrRect| {
+            OcrWord::new_with_layout(text.to_string(), 0, paragraph_index, line_index, rect, None)
+        };
+        let mut ocr_result = OcrTraitResult::new();
+        ocr_result.rects = vec![
+            OcrLine::new(vec![word(
+                "最近人気の",
+                0,
+                0,
+                OcrRect::new(300, 20, 340, 230),
+            )]),
+            OcrLine::new(vec![word(
+                "デスクトップな",
+                0,
+                1,
+                OcrRect::new(255, 20, 295, 300),
+            )]),
+            OcrLine::new(vec![
+                word("ubuntu", 1, 2, OcrRect::new(10, 400, 100, 430)),
+                word("です", 1, 2, OcrRect::new(100, 400, 160, 430)),
+            ]),
+        ];
+        ocr_result.lines = vec![
+            "最近人気の".to_string(),
+            "デスクトップな".to_string(),
+            "ubuntu です".to_string(),
+        ];
+        let document = ResultDocument::new(
+            None,
+            360,
+            500,
+            OcrResultJson::from_ocr_result("tesseract", 0, &ocr_result),
+            None,
+        );
+
+        let page = MokuroPage::from_result_document(&document);
+        assert_eq!(page.blocks.len(), 2);
+        assert!(page.blocks[0].vertical);
+        assert_eq!(page.blocks[0].bounding_box, [255, 20, 340, 300]);
+        assert_eq!(page.blocks[0].font_size, 40.0);
+        assert_eq!(page.blocks[0].lines, ["最近人気の", "デスクトップな"]);
+        assert!(!page.blocks[1].vertical);
+        assert_eq!(page.blocks[1].lines, ["ubuntuです"]);
+        assert_eq!(
+            page.blocks[1].lines_coords[0],
+            [[10.0, 400.0], [160.0, 400.0], [160.0, 430.0], [10.0, 430.0]]
+        );
+        let page_json: serde_json::Value = serde_json::to_value(&page).unwrap();
+        assert_eq!(page_json["blocks"][0]["box"][0], 255);
+        assert!(page_json.get("img_path").is_none());
+
+        let volume = MokuroVolume::new("ubunchu", "01", vec![page]);
+        assert_eq!(
+            volume.volume_uuid,
+            MokuroVolume::new("ubunchu", "01", vec![]).volume_uuid
+        );
+        assert_ne!(volume.title_uuid, volume.volume_uuid);
+        assert_eq!(volume.volume_uuid.len(), 36);
+        assert_eq!(&volume.volume_uuid[14..15], "8");
+    }
+}
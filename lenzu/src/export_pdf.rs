@@ -0,0 +1,344 @@
+// Searchable PDF (one per chapter) of scanned pages: each page is the original image, with an invisible (text
+// rendering mode 3) text layer on top of it, where each OcrWord is placed and stretched over its rect, so that the
+// text can be searched, selected and copied in any PDF viewer
+// The text layer uses the (non-embedded) Adobe-Japan1 CID font with the predefined UniJIS-UTF16-H/V CMaps, which
+// means the strings are just UTF-16BE and viewers can map them back to Unicode without a ToUnicode CMap (nor do we
+// need to ship a font); vertical lines use the -V CMap, so that copying a column gives the characters top to bottom
+// NOTE: rotated (non-vertical) lines are placed on their axis-aligned word rects, since that is all we have
+use crate::image_handling::OCRImage;
+use crate::ocr_traits::{OcrRect, OcrTraitResult, OcrWritingDirection};
+use anyhow::Error;
+use image::{codecs::jpeg::JpegEncoder, ColorType, DynamicImage};
+use pdf_writer::{
+    types::{CidFontType, FontFlags, SystemInfo, TextRenderingMode},
+    Content, Filter, Finish, Name, Pdf, Rect, Ref, Str, TextStr,
+};
+use std::path::Path;
+
+// scans are usually 300dpi, which is what the page size (in points) is derived from
+pub(crate) const DEFAULT_SCAN_DPI: f32 = 300.0;
+const JPEG_QUALITY: u8 = 85;
+
+const CID_FONT_NAME: Name = Name(b"KozMinPr6N-Regular");
+const HORIZONTAL_FONT: Name = Name(b"F1");
+const VERTICAL_FONT: Name = Name(b"F2");
+const PAGE_IMAGE: Name = Name(b"Im1");
+// Kozuka Mincho's descent is 120/1000 em, so the baseline is that much above the bottom of a horizontal word
+const FONT_DESCENT: f32 = 0.12;
+
+pub(crate) struct PdfChapter {
+    pdf: Pdf,
+    title: String,
+    dpi: f32,
+    next_ref: i32,
+    pages_ref: Ref,
+    horizontal_font_ref: Ref,
+    vertical_font_ref: Ref,
+    page_refs: Vec<Ref>,
+}
+
+impl PdfChapter {
+    pub fn new(title: &str, dpi: f32) -> Self {
+        let mut chapter = PdfChapter {
+            pdf: Pdf::new(),
+            title: title.to_string(),
+            dpi,
+            next_ref: 1,
+            pages_ref: Ref::new(1),
+            horizontal_font_ref: Ref::new(1),
+            vertical_font_ref: Ref::new(1),
+            page_refs: Vec::new(),
+        };
+        chapter.pages_ref = chapter.new_ref();
+        chapter.horizontal_font_ref = chapter.new_ref();
+        chapter.vertical_font_ref = chapter.new_ref();
+        let cid_font_ref = chapter.new_ref();
+        let font_descriptor_ref = chapter.new_ref();
+
+        // both Type0 fonts share the same descendant font, and only differ by the CMap (writing mode)
+        chapter
+            .pdf
+            .type0_font(chapter.horizontal_font_ref)
+            .base_font(Name(b"KozMinPr6N-Regular-UniJIS-UTF16-H"))
+            .encoding_predefined(Name(b"UniJIS-UTF16-H"))
+            .descendant_font(cid_font_ref);
+        chapter
+            .pdf
+            .type0_font(chapter.vertical_font_ref)
+            .base_font(Name(b"KozMinPr6N-Regular-UniJIS-UTF16-V"))
+            .encoding_predefined(Name(b"UniJIS-UTF16-V"))
+            .descendant_font(cid_font_ref);
+        chapter
+            .pdf
+            .cid_font(cid_font_ref)
+            .subtype(CidFontType::Type0)
+            .base_font(CID_FONT_NAME)
+            .system_info(SystemInfo {
+                registry: Str(b"Adobe"),
+                ordering: Str(b"Japan1"),
+                supplement: 6,
+            })
+            .font_descriptor(font_descriptor_ref)
+            .default_width(1000.0);
+        chapter
+            .pdf
+            .font_descriptor(font_descriptor_ref)
+            .name(CID_FONT_NAME)
+            .flags(FontFlags::SERIF | FontFlags::SYMBOLIC)
+            .bbox(Rect::new(-437.0, -340.0, 1147.0, 1317.0))
+            .italic_angle(0.0)
+            .ascent(880.0)
+            .descent(-120.0)
+            .cap_height(742.0)
+            .stem_v(80.0);
+        chapter
+    }
+
+    fn new_ref(&mut self) -> Ref {
+        let new_ref = Ref::new(self.next_ref);
+        self.next_ref += 1;
+        new_ref
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.page_refs.len()
+    }
+
+    // ocr_result is expected to be in the pixel coordinates of the image (which is the case for all OCR backends
+    // when the image is evaluated as-is)
+    pub fn add_page(
+        &mut self,
+        ocr_image: &OCRImage,
+        ocr_result: &OcrTraitResult,
+    ) -> Result<(), Error> {
+        let image = ocr_image.get_possible_image().ok_or_else(|| {
+            anyhow::anyhow!("No image loaded for '{}'", ocr_image.get_image_path())
+        })?;
+        let (image_width, image_height) = (image.width(), image.height());
+        let (jpeg_data, is_gray) = Self::encode_jpeg(image)?;
+
+        let page_ref = self.new_ref();
+        let image_ref = self.new_ref();
+        let content_ref = self.new_ref();
+        let mut image_xobject = self.pdf.image_xobject(image_ref, &jpeg_data);
+        image_xobject.filter(Filter::DctDecode);
+        image_xobject
+            .width(image_width as i32)
+            .height(image_height as i32)
+            .bits_per_component(8);
+        if is_gray {
+            image_xobject.color_space().device_gray();
+        } else {
+            image_xobject.color_space().device_rgb();
+        }
+        image_xobject.finish();
+
+        let scale = 72.0 / self.dpi; // pixels to points
+        let (page_width, page_height) = (image_width as f32 * scale, image_height as f32 * scale);
+        let mut content = Content::new();
+        content
+            .save_state()
+            .transform([page_width, 0.0, 0.0, page_height, 0.0, 0.0])
+            .x_object(PAGE_IMAGE)
+            .restore_state();
+        content
+            .begin_text()
+            .set_text_rendering_mode(TextRenderingMode::Invisible);
+        for line in ocr_result.rects.iter() {
+            let is_vertical = line.writing_direction() == OcrWritingDirection::VerticalTtbRtl;
+            for word in line.words().iter() {
+                let text = word.word();
+                let rect = word.rect();
+                if text.trim().is_empty() || rect.width() == 0 || rect.height() == 0 {
+                    continue;
+                }
+                let text_matrix = Self::text_matrix(&text, &rect, is_vertical, scale, page_height);
+                let utf16_be = text
+                    .encode_utf16()
+                    .flat_map(|code_unit| code_unit.to_be_bytes())
+                    .collect::<Vec<u8>>();
+                content
+                    .set_font(
+                        if is_vertical {
+                            VERTICAL_FONT
+                        } else {
+                            HORIZONTAL_FONT
+                        },
+                        1.0,
+                    )
+                    .set_text_matrix(text_matrix)
+                    .show(Str(&utf16_be));
+            }
+        }
+        content.end_text();
+        let content_data = content.finish();
+        self.pdf.stream(content_ref, &content_data);
+
+        let mut page = self.pdf.page(page_ref);
+        page.media_box(Rect::new(0.0, 0.0, page_width, page_height))
+            .parent(self.pages_ref)
+            .contents(content_ref);
+        let mut resources = page.resources();
+        resources.x_objects().pair(PAGE_IMAGE, image_ref);
+        resources
+            .fonts()
+            .pair(HORIZONTAL_FONT, self.horizontal_font_ref)
+            .pair(VERTICAL_FONT, self.vertical_font_ref);
+        resources.finish();
+        page.finish();
+        self.page_refs.push(page_ref);
+        Ok(())
+    }
+
+    // returns (JPEG bytes, whether it is grayscale), since most scans are grayscale and a third of the size that way
+    fn encode_jpeg(image: &DynamicImage) -> Result<(Vec<u8>, bool), Error> {
+        let mut jpeg_data = Vec::new();
+        let mut encoder = JpegEncoder::new_with_quality(&mut jpeg_data, JPEG_QUALITY);
+        let is_gray = matches!(
+            image.color(),
+            ColorType::L8 | ColorType::La8 | ColorType::L16 | ColorType::La16
+        );
+        if is_gray {
+            let gray_image = image.to_luma8();
+            encoder.encode(
+                gray_image.as_raw(),
+                gray_image.width(),
+                gray_image.height(),
+                image::ExtendedColorType::L8,
+            )?;
+        } else {
+            let rgb_image = image.to_rgb8();
+            encoder.encode(
+                rgb_image.as_raw(),
+                rgb_image.width(),
+                rgb_image.height(),
+                image::ExtendedColorType::Rgb8,
+            )?;
+        }
+        Ok((jpeg_data, is_gray))
+    }
+
+    // text space is 1 unit = 1 em (the font size is 1), so the text matrix both scales the glyphs to the word rect
+    // and places the origin: bottom-left (on the baseline) for horizontal, and top-center for vertical writing
+    // (which is where the glyph origin is in vertical writing mode); the advance is estimated as 1em for full-width
+    // and 0.5em for ASCII characters, which is close enough for selection highlighting
+    fn text_matrix(
+        text: &str,
+        rect: &OcrRect,
+        is_vertical: bool,
+        scale: f32,
+        page_height: f32,
+    ) -> [f32; 6] {
+        let advance = text
+            .chars()
+            .map(|c| if c.is_ascii() { 0.5 } else { 1.0 })
+            .sum::<f32>();
+        let (width, height) = (rect.width() as f32 * scale, rect.height() as f32 * scale);
+        let (x_min, y_max) = (rect.x_min as f32 * scale, rect.y_max as f32 * scale);
+        if is_vertical {
+            let y_top = page_height - rect.y_min as f32 * scale;
+            [
+                width,
+                0.0,
+                0.0,
+                height / advance,
+                x_min + width / 2.0,
+                y_top,
+            ]
+        } else {
+            let y_bottom = page_height - y_max;
+            [
+                width / advance,
+                0.0,
+                0.0,
+                height,
+                x_min,
+                y_bottom + height * FONT_DESCENT,
+            ]
+        }
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        let catalog_ref = self.new_ref();
+        let info_ref = self.new_ref();
+        self.pdf
+            .pages(self.pages_ref)
+            .kids(self.page_refs.iter().copied())
+            .count(self.page_refs.len() as i32);
+        self.pdf.catalog(catalog_ref).pages(self.pages_ref);
+        self.pdf
+            .document_info(info_ref)
+            .title(TextStr(&self.title))
+            .producer(TextStr("lenzu"));
+        self.pdf.finish()
+    }
+
+    pub fn save(self, pdf_path: &Path) -> Result<(), Error> {
+        std::fs::write(pdf_path, self.finish())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ocr_gcloud::OcrGcloud;
+
+    #[test]
+    fn test_pdf_chapter_with_vertical_text_layer() {
+        let image_path = "../assets/ubunchu01_02.png";
+        let ocr_image = OCRImage::from(image::open(image_path).unwrap());
+        let response_body = std::fs::read_to_string(
+            "../assets/fixtures/gcloud_synthetic_ubunchu01_02_panel01_section_02.json",
+        )
+        .unwrap();
+        let ocr_result = OcrGcloud::to_ocr_trait_result(&response_body).unwrap();
+
+        let mut chapter = PdfChapter::new("ubunchu01", DEFAULT_SCAN_DPI);
+        chapter.add_page(&ocr_image, &ocr_result).unwrap();
+        chapter
+            .add_page(&ocr_image, &OcrTraitResult::new())
+            .unwrap();
+        assert_eq!(chapter.page_count(), 2);
+        let pdf_data = chapter.finish();
+
+        let document = lopdf::Document::load_mem(&pdf_data).unwrap();
+        let pages = document.get_pages();
+        assert_eq!(pages.len(), 2);
+        let first_page_id = pages[&1];
+        let operations =
+            lopdf::content::Content::decode(&document.get_page_content(first_page_id).unwrap())
+                .unwrap()
+                .operations;
+        assert!(operations.iter().any(|operation| operation.operator == "Tr"
+            && operation.operands[0].as_i64().ok() == Some(3)));
+        let (fonts, shown_words): (Vec<_>, Vec<_>) = operations
+            .iter()
+            .filter(|operation| operation.operator == "Tf" || operation.operator == "Tj")
+            .partition(|operation| operation.operator == "Tf");
+        let shown_words = shown_words
+            .iter()
+            .map(|operation| {
+                let utf16_be = operation.operands[0].as_str().unwrap();
+                String::from_utf16(
+                    &utf16_be
+                        .chunks(2)
+                        .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                        .collect::<Vec<u16>>(),
+                )
+                .unwrap()
+            })
+            .collect::<String>();
+        assert_eq!(
+            shown_words,
+            ocr_result.text.split_whitespace().collect::<String>()
+        );
+        // the fixture is all vertical, so all the words are in the -V font
+        assert!(fonts
+            .iter()
+            .all(|operation| operation.operands[0].as_name_str().ok() == Some("F2")));
+        let pdf_text = String::from_utf8_lossy(&pdf_data);
+        assert!(pdf_text.contains("/UniJIS-UTF16-V"));
+        assert!(pdf_text.contains("/DCTDecode"));
+    }
+}
@@ -0,0 +1,249 @@
+use crate::interpreter_traits::{InterpreterToken, InterpreterTraitResult};
+use crate::kana::{align_reading, is_kanji, OutputScript};
+use crate::ocr_traits::{OcrLine, OcrRect, OcrTraitResult, OcrWritingDirection};
+
+// Furigana (ruby): rather than rendering the whole interpreted text as a block, we put the reading of each kanji
+// run next to the kanji on the image.  The tokens of the (whole) interpreted text are split by line, so that their
+// char offsets map to the characters of the line, and the rect of a character is taken from the word rects,
+// splitting a word of several characters evenly along the writing direction.  A token such as "読む" gets its reading on
+// the kanji only (see kana::align_reading())
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Ruby {
+    pub base: String,                   // kanji run (i.e. "人気")
+    pub reading: String,                // its reading in the output script (i.e. "にんき", "ninki")
+    pub rect: OcrRect,                  // where the kanji run is on the image
+    pub direction: OcrWritingDirection, // vertical: ruby goes to the right of the column, horizontal: above the line
+}
+
+// rect of each character of the line, in the order of the line text (without spaces)
+fn char_rects(line: &OcrLine) -> Vec<(char, OcrRect)> {
+    let is_vertical = line.writing_direction() == OcrWritingDirection::VerticalTtbRtl;
+    let mut char_rects = vec![];
+    for word in line.words() {
+        let chars: Vec<char> = word.word().chars().filter(|c| !c.is_whitespace()).collect();
+        let rect = word.rect();
+        let count = chars.len().max(1) as i32;
+        for (index, c) in chars.into_iter().enumerate() {
+            let index = index as i32;
+            let char_rect = if is_vertical {
+                let height = rect.y_max - rect.y_min;
+                OcrRect::new(
+                    rect.x_min,
+                    rect.y_min + height * index / count,
+                    rect.x_max,
+                    rect.y_min + height * (index + 1) / count,
+                )
+            } else {
+                let width = rect.x_max - rect.x_min;
+                OcrRect::new(
+                    rect.x_min + width * index / count,
+                    rect.y_min,
+                    rect.x_min + width * (index + 1) / count,
+                    rect.y_max,
+                )
+            };
+            char_rects.push((c, char_rect));
+        }
+    }
+    char_rects
+}
+
+// rubies of a single line given its tokens (offsets into the characters of the line, without whitespace)
+pub(crate) fn ruby_for_line(line: &OcrLine, tokens: &[InterpreterToken]) -> Vec<Ruby> {
+    let char_rects = char_rects(line);
+    let mut rubies = vec![];
+    for token in tokens.iter() {
+        if token.end > char_rects.len() || !token.surface.chars().any(is_kanji) {
+            continue;
+        }
+        let kanji_runs = align_reading(&token.surface, &token.reading).unwrap_or_else(|| {
+            // the reading does not fit the surface, so the best we can do is the whole token
+            vec![(0..token.end - token.start, token.reading.clone())]
+        });
+        for (range, reading) in kanji_runs {
+            let range = token.start + range.start..token.start + range.end;
+            let rect = char_rects[range.clone()]
+                .iter()
+                .skip(1)
+                .fold(char_rects[range.start].1, |acc, (_, rect)| acc.union(rect));
+            rubies.push(Ruby {
+                base: char_rects[range].iter().map(|(c, _)| *c).collect(),
+                reading,
+                rect,
+                direction: line.writing_direction(),
+            });
+        }
+    }
+    rubies
+}
+
+// rubies of all the lines of the OCR result from the interpretation of its text (the lines joined with "\n", so
+// that the tokens are char offsets into it), rather than interpreting each line once more; the readings are in
+// output_script (the one of the interpreter)
+pub(crate) fn ruby_for_result(
+    ocr_result: &OcrTraitResult,
+    interpreter_result: &InterpreterTraitResult,
+    output_script: OutputScript,
+) -> Vec<Ruby> {
+    if ocr_result.lines.len() != ocr_result.rects.len() {
+        return vec![]; // cannot tell which line of the text is which line on the image
+    }
+    let mut rubies = vec![];
+    let mut line_start = 0; // char offset of the line into the text
+    for (line_index, (line_text, line)) in ocr_result
+        .lines
+        .iter()
+        .zip(ocr_result.rects.iter())
+        .enumerate()
+    {
+        let line_chars: Vec<char> = line_text.chars().collect();
+        let line_end = line_start + line_chars.len();
+        // offsets into the line without the whitespace (which is what char_rects() has)
+        let mut offsets = vec![0];
+        for c in line_chars.iter() {
+            offsets.push(offsets.last().unwrap() + usize::from(!c.is_whitespace()));
+        }
+        let surface: String = line_chars.iter().filter(|c| !c.is_whitespace()).collect();
+        let tokens = if interpreter_result.tokens.is_empty() {
+            // an interpreter that does not tokenize, so the whole line (if it has one per line) is a single token
+            match interpreter_result.lines.get(line_index) {
+                Some(reading) if interpreter_result.lines.len() == ocr_result.lines.len() => {
+                    vec![InterpreterToken {
+                        surface: surface.clone(),
+                        reading: reading.clone(),
+                        base_form: surface.clone(),
+                        part_of_speech: None,
+                        start: 0,
+                        end: surface.chars().count(),
+                    }]
+                }
+                _ => vec![],
+            }
+        } else {
+            interpreter_result
+                .tokens
+                .iter()
+                .filter(|token| token.start >= line_start && token.end <= line_end)
+                .map(|token| InterpreterToken {
+                    start: offsets[token.start - line_start],
+                    end: offsets[token.end - line_start],
+                    ..token.clone()
+                })
+                .filter(|token| token.start < token.end)
+                .collect()
+        };
+        if surface.chars().any(is_kanji) && surface.chars().count() == char_rects(line).len() {
+            rubies.extend(ruby_for_line(line, &tokens));
+        }
+        line_start = line_end + 1; // and the "\n"
+    }
+    rubies
+        .into_iter()
+        .map(|ruby| Ruby {
+            reading: output_script.convert_kana(&ruby.reading),
+            ..ruby
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_handling::OCRImage;
+    use crate::ocr_traits::OcrWord;
+    use image::{DynamicImage, GenericImageView};
+    use std::ops::Range;
+
+    #[test]
+    fn test_ruby_for_line() {
+        // a vertical column of 6 characters, 40x40 each, split into two words
+        let mut line = OcrLine::new(vec![
+            OcrWord::new("日本語を".to_string(), 0, OcrRect::new(100, 20, 140, 180)),
+            OcrWord::new("読む".to_string(), 0, OcrRect::new(100, 180, 140, 260)),
+        ]);
+        line.set_writing_direction(OcrWritingDirection::VerticalTtbRtl);
+        let token = |surface: &str, reading: &str, start: usize| InterpreterToken {
+            surface: surface.to_string(),
+            reading: reading.to_string(),
+            base_form: surface.to_string(),
+            part_of_speech: None,
+            start,
+            end: start + surface.chars().count(),
+        };
+        let tokens = [
+            token("日本語", "にほんご", 0),
+            token("を", "を", 3),
+            token("読む", "よむ", 4),
+        ];
+        let rubies = ruby_for_line(&line, &tokens);
+        assert_eq!(rubies.len(), 2);
+        assert_eq!(rubies[0].base, "日本語");
+        assert_eq!(rubies[0].rect, OcrRect::new(100, 20, 140, 140));
+        assert_eq!(rubies[1].base, "読");
+        assert_eq!(rubies[1].reading, "よ");
+        assert_eq!(rubies[1].rect, OcrRect::new(100, 180, 140, 220));
+
+        // the ruby is drawn to the right of the column, leaving the column itself as-is
+        let mut ocr_image = OCRImage::new(None);
+        ocr_image.set_image(DynamicImage::new_rgba8(200, 300));
+        let rendered = ocr_image.overlay_ruby(&rubies);
+        let changed = |x_range: Range<u32>| {
+            x_range
+                .flat_map(|x| (0..300).map(move |y| (x, y)))
+                .any(|(x, y)| rendered.get_pixel(x, y) != ocr_image.get_image().get_pixel(x, y))
+        };
+        assert!(changed(140..200));
+        assert!(!changed(0..140));
+    }
+
+    #[test]
+    fn test_ruby_for_result() {
+        // the tokens of the whole text, the second line of which has a space between its words
+        let mut ocr_result = OcrTraitResult::new();
+        ocr_result.rects = vec![
+            OcrLine::new(vec![OcrWord::new(
+                "日本語を".to_string(),
+                0,
+                OcrRect::new(0, 0, 160, 40),
+            )]),
+            OcrLine::new(vec![
+                OcrWord::new("本を".to_string(), 1, OcrRect::new(0, 50, 80, 90)),
+                OcrWord::new("読む".to_string(), 1, OcrRect::new(100, 50, 180, 90)),
+            ]),
+        ];
+        ocr_result.lines = vec!["日本語を".to_string(), "本を 読む".to_string()];
+        ocr_result.text = ocr_result.lines.join("\n");
+        let token = |surface: &str, reading: &str, start: usize| InterpreterToken {
+            surface: surface.to_string(),
+            reading: reading.to_string(),
+            base_form: surface.to_string(),
+            part_of_speech: None,
+            start,
+            end: start + surface.chars().count(),
+        };
+        let interpreter_result = InterpreterTraitResult {
+            text: "にほんごを\nほんを よむ".to_string(),
+            lines: vec!["にほんごを".to_string(), "ほんを よむ".to_string()],
+            tokens: vec![
+                token("日本語", "にほんご", 0),
+                token("を", "を", 3),
+                token("本", "ほん", 5),
+                token("を", "を", 6),
+                token("読む", "よむ", 8),
+            ],
+            translations: vec![],
+        };
+        let rubies = ruby_for_result(&ocr_result, &interpreter_result, OutputScript::Katakana);
+        assert_eq!(
+            rubies
+                .iter()
+                .map(|ruby| (ruby.base.as_str(), ruby.reading.as_str(), ruby.rect))
+                .collect::<Vec<_>>(),
+            [
+                ("日本語", "ニホンゴ", OcrRect::new(0, 0, 120, 40)),
+                ("本", "ホン", OcrRect::new(0, 50, 40, 90)),
+                ("読", "ヨ", OcrRect::new(100, 50, 140, 90)),
+            ]
+        );
+    }
+}
@@ -1,8 +1,11 @@
+use crate::furigana::Ruby;
+use crate::ocr_traits::{OcrRect, OcrTrait, OcrTraitResult, OcrWritingDirection};
+use crate::reading_order::order_blocks;
+use ab_glyph::{Font as _, PxScale, ScaleFont as _};
 use anyhow::Error; // the most easiest way to handle errors
 use core::result::Result;
 use image::{imageops::overlay, DynamicImage, GrayImage, ImageBuffer, RgbImage, Rgba, *};
-use imageproc::drawing::{draw_text_mut, text_size};
-use rusttype::{Font, Scale};
+use imageproc::drawing::draw_text_mut;
 use rusty_tesseract::image::{GenericImage as _, GenericImageView as _};
 use std::{
     collections::HashMap,
@@ -12,19 +15,24 @@ use std::{
 
 // The BOLD font is about 32x32 pixels
 const DEFAULT_FONT_SIZE: f32 = 32.0;
+const RUBY_SIZE_RATIO: f32 = 0.5; // furigana is about half the size of the characters it annotates
 
 // fonts as constant (data pool)
-const FONT_DATA: &[u8] = if cfg!(target_os = "windows") {
-    include_bytes!("..\\..\\assets\\fonts\\Noto_Sans_JP\\static\\NotoSansJP-Regular.ttf")
-} else {
-    include_bytes!("../../assets/fonts/Noto_Sans_JP/static/NotoSansJP-Regular.ttf")
-};
+// NOTE: has to be #[cfg] rather than if cfg!() because include_bytes!() on both branches are evaluated at compile time
+// (and a path with backslashes does not exist on Linux)
+#[cfg(target_os = "windows")]
+const FONT_DATA: &[u8] =
+    include_bytes!("..\\..\\assets\\fonts\\Noto_Sans_JP\\static\\NotoSansJP-Regular.ttf");
+#[cfg(not(target_os = "windows"))]
+const FONT_DATA: &[u8] =
+    include_bytes!("../../assets/fonts/Noto_Sans_JP/static/NotoSansJP-Regular.ttf");
 
-const FONT_DATA_BOLD: &[u8] = if cfg!(target_os = "windows") {
-    include_bytes!("..\\..\\assets\\fonts\\Noto_Sans_JP\\static\\NotoSansJP-Bold.ttf")
-} else {
-    include_bytes!("../../assets/fonts/Noto_Sans_JP/static/NotoSansJP-Bold.ttf")
-};
+#[cfg(target_os = "windows")]
+const FONT_DATA_BOLD: &[u8] =
+    include_bytes!("..\\..\\assets\\fonts\\Noto_Sans_JP\\static\\NotoSansJP-Bold.ttf");
+#[cfg(not(target_os = "windows"))]
+const FONT_DATA_BOLD: &[u8] =
+    include_bytes!("../../assets/fonts/Noto_Sans_JP/static/NotoSansJP-Bold.ttf");
 
 #[derive(Debug, Clone)]
 pub struct OCRImage {
@@ -73,7 +81,7 @@ impl From<RawBitmapImage> for OCRImage {
         let img = match load_result {
             Ok(loaded_image) => loaded_image,
             Err(e) => {
-                println!("Error loading image from memory: {}, attempting to dynamically construct rgba8...", e);
+                eprintln!("Error loading image from memory: {}, attempting to dynamically construct rgba8...", e);
                 let mut rebuilt_image =
                     image::DynamicImage::new_rgba8(from_image.width, from_image.height);
                 // now update image with buffer data as if the data is a PNG (order by height (row-ordered))
@@ -91,7 +99,7 @@ impl From<RawBitmapImage> for OCRImage {
                 rebuilt_image
             }
         };
-        println!(
+        eprintln!(
             "from_raw_bytes() - Image is {} bytes, ColorType: {:?},  Dimensions: {:?}",
             from_image.image.len(),
             img.color(),
@@ -117,7 +125,7 @@ impl From<RawPngImage> for OCRImage {
         let img = match load_result {
             Ok(loaded_image) => loaded_image,
             Err(e) => {
-                println!("Error loading image from memory: {}, attempting to dynamically construct rgba8...", e);
+                eprintln!("Error loading image from memory: {}, attempting to dynamically construct rgba8...", e);
                 let mut rebuilt_image = image::DynamicImage::new_rgba8(value.width, value.height);
                 // now update image with buffer data as if the data is a PNG (order by height (row-ordered))
                 for y in 0..value.height {
@@ -147,7 +155,7 @@ impl From<RawPngImage> for OCRImage {
                 ret
             }
         };
-        println!(
+        eprintln!(
             "from_png_bytes() - Image is {} bytes, ColorType: {:?},  Dimensions: {:?}",
             value.image.len(),
             img.color(),
@@ -197,21 +205,9 @@ impl From<image::DynamicImage> for OCRImage {
 //    }
 //}
 
-impl From<rusty_tesseract::image::DynamicImage> for OCRImage {
-    fn from(img: rusty_tesseract::image::DynamicImage) -> Self {
-        let mut buffer = Vec::with_capacity(img.as_bytes().len());
-        img.as_bytes()
-            .read_to_end(&mut buffer)
-            .expect("Error reading image bytes");
-        let img =
-            image::load_from_memory(buffer.as_slice()).expect("Error loading image from memory");
-        OCRImage {
-            dynamic_image: Some(img),
-            // initialize other fields as needed
-            ..OCRImage::new(None)
-        }
-    }
-}
+// NOTE: rusty-tesseract (since 1.1.10) also depends on image 0.25, so rusty_tesseract::image::DynamicImage is the
+// same type as image::DynamicImage and the From<image::DynamicImage> above covers it (a separate impl for it
+// would be a conflicting implementation)
 
 impl OCRImage {
     pub fn get_image_path(&self) -> &str {
@@ -302,7 +298,7 @@ impl OCRImage {
                 )
                 .as_str(),
             );
-            println!(
+            eprintln!(
             "to_imageproc_dynamic_image() - Image is {} bytes, ColorType: {:?},  Dimensions: {:?}",
             image.len(),
             transformed_image.color(),
@@ -340,7 +336,7 @@ impl OCRImage {
                 )
                 .as_str(),
             );
-            println!(
+            eprintln!(
         "to_rusty_tesseract_dynamic_image() - Image is {} bytes, ColorType: {:?},  Dimensions: {:?}",
         image.len(),
         transformed_image.color(),
@@ -424,181 +420,1355 @@ impl OCRImage {
         texty: i32,
         //c_background_image: &DynamicImage,
     ) -> DynamicImage {
-        let mut background_image = self.get_image().clone();
+        let background_image = self.get_image().clone();
         if text.is_empty() {
-            println!("Warning: No text to overlay onto image");
+            eprintln!("Warning: No text to overlay onto image");
             return background_image; // return back the original cloned (for optimization, make sure to pretest text length before calling here, so we won't even need to clone here)
         }
 
-        let scale = Scale::uniform(DEFAULT_FONT_SIZE);
-        println!("overlay_text() - Scale: {:?}", scale);
-
-        // first, we need to determine how wide the text is, and if it is wider than the image,
-        // we need to break the text down to multiple lines
-        let image_char_width =
-            std::cmp::max((background_image.width() / scale.x as u32) - 10u32, 1);
-        let mut text_width: u32 = text.len() as u32; // number of characters
-        let mut text_height: u32 = 1; // number of lines
-        let mut text_width_pixels: u32 = text_width * scale.x as u32;
-        let mut text_height_pixels: u32 = text_height * scale.y as u32;
-        if text_width > image_char_width {
-            // if the text is wider than the image, then we need to break it down to multiple lines
-            text_width = image_char_width;
-            text_height = (text.len() as u32 / text_width) + 1;
-            text_width_pixels = text_width * scale.x as u32;
-            text_height_pixels = text_height * scale.y as u32;
-        }
-        // iterate each char and add newline when we reach text_width
-        let binding = text
-            .chars()
-            .enumerate()
-            .map(|(i, c)| {
-                if (i as u32 + 1) % text_width == 0 {
-                    format!("{}\n", c)
-                } else {
-                    format!("{}", c)
+        // the text is offset by a character from (textx, texty), and wrapped so that it leaves a margin of a
+        // character on the right as well (or at least one character per line if the image is that narrow)
+        let margin = DEFAULT_FONT_SIZE as i32;
+        let max_width = std::cmp::max(background_image.width() as i32 - textx - margin * 2, 1);
+        let layout = layout_text(
+            &self.ttf_font_bold,
+            text,
+            DEFAULT_FONT_SIZE,
+            OcrWritingDirection::HorizontalLtr,
+            max_width as u32,
+        );
+        eprintln!(
+            "overlay_text() - {} chars laid out in {}x{} pixels (image: {}x{})",
+            text.chars().count(),
+            layout.width,
+            layout.height,
+            background_image.width(),
+            background_image.height()
+        );
+
+        // Note that imageproc::overlay() only works on same byte depth, so we always draw on Rgba<u8>, which
+        // is also so that I can have the text in RED...
+        let mut overlayed_image = background_image.to_rgba8();
+        draw_text_layout(
+            &mut overlayed_image,
+            &self.ttf_font_bold,
+            &layout,
+            textx + margin,
+            texty + margin,
+            image::Rgba([0xff, 0x40, 0x40, 0xff]),
+        );
+        DynamicImage::ImageRgba8(overlayed_image)
+    }
+
+    // fits the text into the rect (i.e. a speech bubble) by shrinking the font from DEFAULT_FONT_SIZE until it
+    // does, and centers it there; vertical text is laid out in columns right-to-left as in manga
+    pub fn overlay_text_in_rect(
+        &self,
+        text: &str,
+        rect: &OcrRect,
+        direction: OcrWritingDirection,
+    ) -> DynamicImage {
+        let mut overlayed_image = self.get_image().to_rgba8();
+        if text.is_empty() {
+            return DynamicImage::ImageRgba8(overlayed_image);
+        }
+        let layout = fit_text(
+            &self.ttf_font_bold,
+            text,
+            direction,
+            rect.width(),
+            rect.height(),
+            DEFAULT_FONT_SIZE,
+        );
+        draw_text_layout(
+            &mut overlayed_image,
+            &self.ttf_font_bold,
+            &layout,
+            rect.x_min + (rect.width() as i32 - layout.width as i32) / 2,
+            rect.y_min + (rect.height() as i32 - layout.height as i32) / 2,
+            image::Rgba([0xff, 0x40, 0x40, 0xff]),
+        );
+        DynamicImage::ImageRgba8(overlayed_image)
+    }
+
+    // draws the furigana (see furigana.rs) next to each kanji run: to the right of vertical columns, stacked
+    // top-to-bottom and centered along the run, and above horizontal lines; the reading is put on a white
+    // background so that it stays readable over screentone and artwork
+    pub fn overlay_ruby(&self, rubies: &[Ruby]) -> DynamicImage {
+        let mut canvas = self.get_image().to_rgba8();
+        let background = image::Rgba([0xff, 0xff, 0xff, 0xff]);
+        let color = image::Rgba([0xff, 0x40, 0x40, 0xff]);
+        for ruby in rubies.iter() {
+            if ruby.reading.is_empty() {
+                continue;
+            }
+            let (x, y, layout) = match ruby.direction {
+                OcrWritingDirection::VerticalTtbRtl => {
+                    let size = (ruby.rect.width() as f32 * RUBY_SIZE_RATIO).max(MIN_FONT_SIZE);
+                    let layout = layout_text(
+                        &self.ttf_font_bold,
+                        &ruby.reading,
+                        size,
+                        OcrWritingDirection::VerticalTtbRtl,
+                        u32::MAX,
+                    );
+                    let y =
+                        ruby.rect.y_min + (ruby.rect.height() as i32 - layout.height as i32) / 2;
+                    (ruby.rect.x_max + 1, std::cmp::max(y, 0), layout)
+                }
+                OcrWritingDirection::HorizontalLtr => {
+                    let size = (ruby.rect.height() as f32 * RUBY_SIZE_RATIO).max(MIN_FONT_SIZE);
+                    let layout = layout_text(
+                        &self.ttf_font_bold,
+                        &ruby.reading,
+                        size,
+                        OcrWritingDirection::HorizontalLtr,
+                        u32::MAX,
+                    );
+                    let x = ruby.rect.x_min + (ruby.rect.width() as i32 - layout.width as i32) / 2;
+                    let y = ruby.rect.y_min - layout.height as i32 - 1;
+                    (std::cmp::max(x, 0), std::cmp::max(y, 0), layout)
                 }
-            })
-            .collect::<String>();
-        let multi_lined_text: Vec<&str> = binding.split("\n").collect();
-
-        // Note that imageproc::overlay() only works on same byte depth (i.e. 8-bit, 16-bit, 24-bit, 32-bit, etc.),
-        let mut color_type: image::ColorType = background_image.color();
-
-        // see if we can transform the background_image to  Rgba<u8> IF it is just plain gray scale or other format other than 32-bits
-        // if this is not possible, we'll always just convert to Luma<u8>  (lowest comman denominator) but I'd like to have it as 32-bits
-        // so that I can have the text in RED...
-        if color_type != image::ColorType::Rgba8 {
-            // convert to Rgba<u8>
-            let (img_width, img_height) = background_image.dimensions().clone();
-            let binding = background_image.into_rgba8().clone();
-            let raw_png_image = RawPngImage {
-                image: binding.as_raw().to_vec(),
-                width: img_width,
-                height: img_height,
             };
-            if Self::is_valid_png(&raw_png_image.image) {
-                println!("overlay_text() - Image is PNG");
-            } else {
-                println!("overlay_text() - Image is NOT PNG");
+            imageproc::drawing::draw_filled_rect_mut(
+                &mut canvas,
+                imageproc::rect::Rect::at(x, y).of_size(layout.width.max(1), layout.height.max(1)),
+                background,
+            );
+            draw_text_layout(&mut canvas, &self.ttf_font_bold, &layout, x, y, color);
+        }
+        DynamicImage::ImageRgba8(canvas)
+    }
+}
+
+// Text layout: glyphs are measured with ab_glyph (rather than counting chars, let alone bytes) so that
+// full-width (Japanese) and proportional (ASCII) text wrap where they actually reach the edge.  Horizontal text
+// flows left-to-right in lines top-to-bottom; vertical (tategaki) text flows top-to-bottom in columns
+// right-to-left, one em per character, where the characters that are horizontal by nature (long-vowel mark,
+// dashes, brackets) are rotated 90 degrees clockwise, and the small punctuation (、。) moves to the upper right
+// of its cell
+const MIN_FONT_SIZE: f32 = 8.0; // fit_text() will not shrink the font any further, even if the text overflows
+const FIT_FONT_SIZE_STEP: f32 = 0.9; // shrink by 10% at a time until the text fits
+const VERTICAL_ROTATED_CHARS: &str =
+    "ー－—―‐-~～〜…‥=＝()（）[]［］{}｛｝<>＜＞「」『』【】〈〉《》〔〕";
+const VERTICAL_SHIFTED_CHARS: &str = "、。，．,.";
+const VERTICAL_SHIFT_RATIO: f32 = 0.6; // of the font size, up and to the right
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LaidOutChar {
+    pub c: char,
+    pub x: i32, // where to draw the glyph (for draw_text_mut()), relative to the upper left of the layout
+    pub y: i32,
+    pub rotated: bool, // rotated 90 degrees clockwise, x/y is then the upper left of the rotated glyph
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextLayout {
+    pub chars: Vec<LaidOutChar>,
+    pub font_size: f32,
+    pub width: u32, // in pixels
+    pub height: u32,
+}
+
+// lays out the text, wrapping it at max_extent (the width of a line, or the height of a column); newlines
+// always break, and each line (or column) has at least one character even if it does not fit
+pub fn layout_text(
+    font: &ab_glyph::FontArc,
+    text: &str,
+    font_size: f32,
+    direction: OcrWritingDirection,
+    max_extent: u32,
+) -> TextLayout {
+    let scaled = font.as_scaled(PxScale::from(font_size));
+    let line_height = (scaled.height() + scaled.line_gap()).ceil() as i32;
+    let max_extent = max_extent.min(i32::MAX as u32) as f32;
+
+    // first, break the text into lines (columns) of (char, offset along the line, advance)
+    let mut lines: Vec<Vec<(char, f32, f32)>> = vec![vec![]];
+    let mut position = 0.0f32;
+    for c in text.chars() {
+        if c == '\n' {
+            lines.push(vec![]);
+            position = 0.0;
+            continue;
+        }
+        if c.is_control() {
+            continue;
+        }
+        let advance = match direction {
+            OcrWritingDirection::HorizontalLtr => scaled.h_advance(font.glyph_id(c)),
+            OcrWritingDirection::VerticalTtbRtl => font_size,
+        };
+        if position > 0.0 && position + advance > max_extent {
+            lines.push(vec![]);
+            position = 0.0;
+        }
+        if let Some(line) = lines.last_mut() {
+            line.push((c, position, advance));
+        }
+        position += advance;
+    }
+    if lines.last().map(|line| line.is_empty()).unwrap_or(false) && lines.len() > 1 {
+        lines.pop();
+    }
+
+    let extent = lines
+        .iter()
+        .filter_map(|line| line.last().map(|(_, offset, advance)| offset + advance))
+        .fold(0.0f32, f32::max)
+        .ceil() as u32;
+    let mut chars = vec![];
+    match direction {
+        OcrWritingDirection::HorizontalLtr => {
+            for (index, line) in lines.iter().enumerate() {
+                for (c, offset, _) in line.iter() {
+                    chars.push(LaidOutChar {
+                        c: *c,
+                        x: offset.round() as i32,
+                        y: index as i32 * line_height,
+                        rotated: false,
+                    });
+                }
             }
-            let rgba_image = OCRImage::from(raw_png_image);
-            let rgba_image_buffer =  // : ImageBuffer<Rgba<u8>, Vec<u8>> =
-            rgba_image.get_image().as_rgba8().unwrap();
-            // now convert it back to DynamicImge
-            let ret = DynamicImage::ImageRgba8(rgba_image_buffer.clone());
-            background_image = ret;
-            color_type = background_image.color();
-        }
-        println!("overlay_text() - ColorType: {:?}", color_type);
-
-        // instantiate ImageBuffer matching same type as background_image via creating a sample pixel matching color_type
-        let p = image::Rgba([0u8, 0, 0, 0]);
-        let mut text_image_canvas =
-            image::ImageBuffer::from_pixel(text_width_pixels, text_height_pixels, p);
-
-        // Load a font (you can replace this with your own font)
-        // Create a font (Meiryo or any other suitable Japanese font)
-        //let font_data: &[u8] = if cfg!(target_os = "windows") {
-        //    include_bytes!("..\\..\\assets\\fonts\\kochi-mincho-subst.ttf")
-        //} else {
-        //    include_bytes!("../../assets/fonts/kochi-mincho-subst.ttf")
-        //};
-        ////let font: Font<'_> = rusttype::Font::try_from_bytes(font_data).expect("Failed to load font");
-        //let font: ab_glyph::FontArc = ab_glyph::FontArc::try_from_slice(font_data)
-        //    .expect("Failed to load font 'kochi-mincho-subst.ttf'");
-        //let scale = Scale {
-        //    x: image.width() as f32 * 0.2,
-        //    y: font.scale_for_pixel_height(DEFAULT_FONT_SIZE),
-        //};
-        //let fscale = font.scale_for_pixel_height(DEFAULT_FONT_SIZE);
-
-        // render text on the text_image_canvas
-        println!(
-            "Width: {} (WinX:{}, pixels:{})\nHeight: {} (WinY:{}, pixels:{})\n{:?}\n\n",
-            text_width, 
-            background_image.width(),
-            text_height_pixels,
-            text_height, 
-            background_image.height(),
-            text_width_pixels,
-            multi_lined_text,
-        );
+            TextLayout {
+                chars,
+                font_size,
+                width: extent,
+                height: (lines.len() as i32 * line_height) as u32,
+            }
+        }
+        OcrWritingDirection::VerticalTtbRtl => {
+            // the font's line box is taller than an em, so it is centered on the cell
+            let cell = font_size.ceil() as i32;
+            let glyph_dy = ((font_size - scaled.height()) / 2.0).round() as i32;
+            let shift = (font_size * VERTICAL_SHIFT_RATIO).round() as i32;
+            for (index, column) in lines.iter().enumerate() {
+                let column_x =
+                    (lines.len() - 1 - index) as i32 * line_height + (line_height - cell) / 2;
+                for (c, offset, _) in column.iter() {
+                    let cell_y = offset.round() as i32;
+                    let advance = scaled.h_advance(font.glyph_id(*c)).round() as i32;
+                    let laid_out = if VERTICAL_ROTATED_CHARS.contains(*c) {
+                        LaidOutChar {
+                            c: *c,
+                            x: column_x + (cell - line_height) / 2,
+                            y: cell_y + (cell - advance) / 2,
+                            rotated: true,
+                        }
+                    } else if VERTICAL_SHIFTED_CHARS.contains(*c) {
+                        LaidOutChar {
+                            c: *c,
+                            x: column_x + (cell - advance) / 2 + shift,
+                            y: cell_y + glyph_dy - shift,
+                            rotated: false,
+                        }
+                    } else {
+                        LaidOutChar {
+                            c: *c,
+                            x: column_x + (cell - advance) / 2,
+                            y: cell_y + glyph_dy,
+                            rotated: false,
+                        }
+                    };
+                    chars.push(laid_out);
+                }
+            }
+            TextLayout {
+                chars,
+                font_size,
+                width: (lines.len() as i32 * line_height) as u32,
+                height: extent,
+            }
+        }
+    }
+}
 
-        // Draw each line
-        let mut line_y: u32 = 0;
-        for (index, line) in multi_lined_text.iter().enumerate() {
-            println!(
-                "Line {}: Y={} - '{}' ({} chars)",
-                index,
-                line_y,
-                line,
-                line.len()
+// the largest layout (up to max_font_size) that fits in width x height, or the one at MIN_FONT_SIZE if none does
+pub fn fit_text(
+    font: &ab_glyph::FontArc,
+    text: &str,
+    direction: OcrWritingDirection,
+    width: u32,
+    height: u32,
+    max_font_size: f32,
+) -> TextLayout {
+    let max_extent = match direction {
+        OcrWritingDirection::HorizontalLtr => width,
+        OcrWritingDirection::VerticalTtbRtl => height,
+    };
+    let mut font_size = max_font_size.max(MIN_FONT_SIZE);
+    loop {
+        let layout = layout_text(font, text, font_size, direction, max_extent);
+        if (layout.width <= width && layout.height <= height) || font_size <= MIN_FONT_SIZE {
+            return layout;
+        }
+        font_size = (font_size * FIT_FONT_SIZE_STEP).max(MIN_FONT_SIZE);
+    }
+}
+
+// draws the layout with its upper left at (x, y)
+pub fn draw_text_layout(
+    canvas: &mut RgbaImage,
+    font: &ab_glyph::FontArc,
+    layout: &TextLayout,
+    x: i32,
+    y: i32,
+    color: Rgba<u8>,
+) {
+    let scaled = font.as_scaled(PxScale::from(layout.font_size));
+    for laid_out in layout.chars.iter() {
+        let glyph = laid_out.c.to_string();
+        if laid_out.rotated {
+            // draw it upright on its own, and then overlay it rotated
+            let advance = scaled.h_advance(font.glyph_id(laid_out.c)).ceil() as u32;
+            let line_height = (scaled.height() + scaled.line_gap()).ceil() as u32;
+            let mut upright = RgbaImage::new(advance.max(1), line_height.max(1));
+            draw_text_mut(&mut upright, color, 0, 0, layout.font_size, font, &glyph);
+            let rotated = image::imageops::rotate90(&upright);
+            overlay(
+                canvas,
+                &rotated,
+                (x + laid_out.x) as i64,
+                (y + laid_out.y) as i64,
             );
+        } else {
             draw_text_mut(
-                &mut text_image_canvas,                // canvas surface
-                image::Rgba([0xff, 0x40, 0x40, 0xff]), // font color
-                0,
-                line_y as i32, // Q: Do we need to shift pixels down?
-                scale.y,
-                &self.ttf_font_bold,
-                &line, // text to render (will come out as blank if the UTF8 is not supported by ttf)
+                canvas,
+                color,
+                x + laid_out.x,
+                y + laid_out.y,
+                layout.font_size,
+                font,
+                &glyph,
             );
-            let (ts_width, ts_height) = text_size(scale.y, &self.ttf_font_bold, line); // Adjust y position for the next line
-            line_y += ts_height;
-        }
-
-        // overlay the two images.  Bottom (background) image is the original image, and the top (foreground) image is the text
-        let mut overlayed_image: image::ImageBuffer<image::Rgba<u8>, Vec<u8>> = background_image
-            .as_rgba8()
-            .expect("Unable to convert bg-image as rgba8")
-            .clone();
-        let overlay_x: i64 = textx as i64 + scale.x as i64;
-        let overlay_y: i64 = texty as i64 + scale.y as i64;
-        overlay(
-            &mut overlayed_image,
-            &mut text_image_canvas,
-            overlay_x,
-            overlay_y,
+        }
+    }
+}
+
+// Text-region detection: OCR engines (Tesseract in particular) do well on cropped text blocks such as a single
+// speech bubble, but fail on whole panels/pages, so we first look for the regions that are likely text and OCR
+// each of them on its own:
+//  1. binarize (Otsu) so that the ink is the foreground
+//  2. connected components of the ink, keeping the ones that are (more or less) character sized, which drops
+//     screentone dots and specks on one end, and panel borders and large artwork on the other
+//  3. dilate the character boxes by about half a character, so that the characters of a column (or a line) and
+//     neighboring columns of the same bubble merge into one region
+//  4. connected components of that is the text regions, ordered right-to-left and then top-to-bottom as in manga;
+//     a region that covers much of the image is either the image already being a text block (i.e. a cropped
+//     speech bubble) or handwritten text merged into the artwork, and either way is better off OCR'ed as a whole
+// NOTE: this is deliberately heuristic (no training data needed), so false positives (i.e. sound effects, or
+// artwork that happens to be character sized) are expected, and are left for the OCR confidence to filter out
+const MIN_CHARACTER_SIZE: u32 = 4; // in pixels, anything smaller is screentone or dust
+const MAX_CHARACTER_ASPECT_RATIO: u32 = 12; // longer than this is a (border) line, not a character stroke
+const MIN_CHARACTERS_PER_REGION: usize = 2; // a lone character-sized component is more likely artwork
+const MAX_REGION_AREA_RATIO: f32 = 0.2; // of the image area
+
+// returns the candidate text regions in reading order, with a margin (half a character) around the text
+pub fn find_text_regions(image: &DynamicImage) -> Vec<OcrRect> {
+    let gray_image = image.to_luma8();
+    let (image_width, image_height) = gray_image.dimensions();
+    let max_character_size = std::cmp::max(
+        std::cmp::min(image_width, image_height) / 4,
+        MIN_CHARACTER_SIZE * 4,
+    );
+
+    // 1. ink (darker than the Otsu level) as foreground; both imageproc 0.24 and 0.25 have otsu_level(), but
+    // threshold() differs between them, so it is done here
+    let level = imageproc::contrast::otsu_level(&gray_image);
+    let ink_image = GrayImage::from_fn(image_width, image_height, |x, y| {
+        if gray_image.get_pixel(x, y)[0] <= level {
+            Luma([255u8])
+        } else {
+            Luma([0u8])
+        }
+    });
+
+    // 2. character-sized components
+    let character_rects = component_rects(&ink_image)
+        .into_iter()
+        .filter(|rect| {
+            let (width, height) = (rect.width(), rect.height());
+            let (shorter, longer) = (std::cmp::min(width, height), std::cmp::max(width, height));
+            longer >= MIN_CHARACTER_SIZE
+                && longer <= max_character_size
+                && longer <= std::cmp::max(shorter, 1) * MAX_CHARACTER_ASPECT_RATIO
+        })
+        .collect::<Vec<OcrRect>>();
+    if character_rects.is_empty() {
+        return vec![];
+    }
+
+    // 3. dilate by about half of the (median) character size; strokes of a kanji are separate components, so
+    // the median is of a stroke rather than of a character, which is why it is not halved any further
+    let mut character_sizes = character_rects
+        .iter()
+        .map(|rect| std::cmp::max(rect.width(), rect.height()))
+        .collect::<Vec<u32>>();
+    character_sizes.sort();
+    let radius = character_sizes[character_sizes.len() / 2].clamp(2, 64) as u8;
+    let mut character_mask = GrayImage::new(image_width, image_height);
+    for rect in character_rects.iter() {
+        for y in rect.y_min..rect.y_max {
+            for x in rect.x_min..rect.x_max {
+                character_mask.put_pixel(x as u32, y as u32, Luma([255u8]));
+            }
+        }
+    }
+    let region_mask = imageproc::morphology::dilate(
+        &character_mask,
+        imageproc::distance_transform::Norm::LInf,
+        radius,
+    );
+
+    // 4. regions, with at least a few characters in them
+    let max_region_area = (image_width * image_height) as f32 * MAX_REGION_AREA_RATIO;
+    let mut regions = component_rects(&region_mask)
+        .into_iter()
+        .filter(|region| (region.width() * region.height()) as f32 <= max_region_area)
+        .filter(|region| {
+            let character_count = character_rects
+                .iter()
+                .filter(|rect| {
+                    rect.x_min >= region.x_min
+                        && rect.x_max <= region.x_max
+                        && rect.y_min >= region.y_min
+                        && rect.y_max <= region.y_max
+                })
+                .count();
+            character_count >= MIN_CHARACTERS_PER_REGION
+        })
+        .collect::<Vec<OcrRect>>();
+    regions.sort_by(|a, b| b.x_max.cmp(&a.x_max).then(a.y_min.cmp(&b.y_min)));
+    regions
+}
+
+// bounding rects (x_max/y_max exclusive) of the 8-connected components of the non-zero pixels
+fn component_rects(mask: &GrayImage) -> Vec<OcrRect> {
+    let labels = imageproc::region_labelling::connected_components(
+        mask,
+        imageproc::region_labelling::Connectivity::Eight,
+        Luma([0u8]),
+    );
+    label_rects(&labels).into_iter().flatten().collect()
+}
+
+// bounding rect of each label, indexed by label - 1 (label 0 is the background)
+fn label_rects(labels: &ImageBuffer<Luma<u32>, Vec<u32>>) -> Vec<Option<OcrRect>> {
+    let mut rects: Vec<Option<OcrRect>> = Vec::new();
+    for (x, y, label) in labels.enumerate_pixels() {
+        let label = label[0] as usize;
+        if label == 0 {
+            continue; // background
+        }
+        if rects.len() < label {
+            rects.resize(label, None);
+        }
+        let pixel_rect = OcrRect::new(x as i32, y as i32, x as i32 + 1, y as i32 + 1);
+        rects[label - 1] = Some(match rects[label - 1] {
+            Some(rect) => rect.union(&pixel_rect),
+            None => pixel_rect,
+        });
+    }
+    rects
+}
+
+// OCR each of the text regions (see find_text_regions()) on its own, and merge the results back into the
+// coordinates of the whole image; if no regions are found, the whole image is evaluated as-is instead
+pub fn evaluate_by_text_regions(
+    ocr: &dyn OcrTrait,
+    image: &DynamicImage,
+) -> Result<OcrTraitResult, Error> {
+    let regions = find_text_regions(image);
+    if regions.is_empty() {
+        return ocr.evaluate(image);
+    }
+    let mut ocr_result = OcrTraitResult::new();
+    for region in regions.iter() {
+        let region_image = image.crop_imm(
+            region.x_min as u32,
+            region.y_min as u32,
+            region.width(),
+            region.height(),
+        );
+        match ocr.evaluate(&region_image) {
+            Ok(region_result) => ocr_result.append_at(region_result, region.x_min, region.y_min),
+            // one region failing (i.e. too small for the OCR engine) should not fail the whole image
+            Err(e) => eprintln!("Warning: OCR failed on text region {:?} - {:?}", region, e),
+        }
+    }
+    Ok(ocr_result)
+}
+
+// Panel segmentation: manga panels are (more or less) boxes of ink framed by a border and separated by white
+// gutters, so rather than looking for the borders themselves (which may be straight, slanted, or not drawn at all
+// when the art bleeds to the edge), we look for the gutters:
+//  1. binarize (Otsu), and everything that is not ink and is reachable from the edge of the page (the margins and
+//     the gutters between the panels) is the gutter
+//  2. connected components of whatever is left are the panels (the frame and everything inside it), and their
+//     bounding rects are the panels, dropping the ones that are too small (i.e. captions and page numbers in the
+//     gutters) and the ones inside another panel
+//  3. ordered as panels are read, rows top-to-bottom and right-to-left within a row (see reading_order.rs)
+// NOTE: art that crosses the gutter (i.e. a character breaking out of the frame into the next panel) joins the two
+// panels into one, and a page without gutters (i.e. a single panel, or a crop of one) is a single panel
+const MIN_PANEL_AREA_RATIO: f32 = 0.02; // of the page area
+const MIN_PANEL_COVERAGE_RATIO: f32 = 0.5; // of the page area, less than that is art rather than panels (i.e. a crop)
+
+// returns the panels in reading order; the whole image if it has no gutters to speak of
+pub fn find_panels(image: &DynamicImage) -> Vec<OcrRect> {
+    let gray_image = image.to_luma8();
+    let (image_width, image_height) = gray_image.dimensions();
+    let whole_image = vec![OcrRect::from(0, 0, image_width, image_height)];
+    if image_width == 0 || image_height == 0 {
+        return whole_image; // nothing to look for gutters in (and no edge to start from)
+    }
+
+    // 1. the paper (not ink) and which of it is connected to the edge of the page
+    let level = imageproc::contrast::otsu_level(&gray_image);
+    let paper_image = GrayImage::from_fn(image_width, image_height, |x, y| {
+        if gray_image.get_pixel(x, y)[0] > level {
+            Luma([255u8])
+        } else {
+            Luma([0u8])
+        }
+    });
+    let paper_labels = imageproc::region_labelling::connected_components(
+        &paper_image,
+        imageproc::region_labelling::Connectivity::Four,
+        Luma([0u8]),
+    );
+    let label_count = paper_labels
+        .pixels()
+        .map(|label| label[0])
+        .max()
+        .unwrap_or(0);
+    let mut is_gutter_label = vec![false; label_count as usize + 1];
+    let edge_pixels = (0..image_width)
+        .flat_map(|x| [(x, 0), (x, image_height - 1)])
+        .chain((0..image_height).flat_map(|y| [(0, y), (image_width - 1, y)]));
+    for (x, y) in edge_pixels {
+        is_gutter_label[paper_labels.get_pixel(x, y)[0] as usize] = true;
+    }
+    is_gutter_label[0] = false; // ink on the edge is not gutter (label 0 is the ink)
+    let panel_mask = GrayImage::from_fn(image_width, image_height, |x, y| {
+        if is_gutter_label[paper_labels.get_pixel(x, y)[0] as usize] {
+            Luma([0u8])
+        } else {
+            Luma([255u8])
+        }
+    });
+
+    // 2. panels large enough, and not inside another one
+    let min_panel_area = (image_width * image_height) as f32 * MIN_PANEL_AREA_RATIO;
+    let candidates = component_rects(&panel_mask)
+        .into_iter()
+        .filter(|rect| (rect.width() * rect.height()) as f32 >= min_panel_area)
+        .collect::<Vec<OcrRect>>();
+    let panels = candidates
+        .iter()
+        .enumerate()
+        .filter(|(index, rect)| {
+            !candidates.iter().enumerate().any(|(other_index, other)| {
+                other_index != *index
+                    && other.x_min <= rect.x_min
+                    && other.y_min <= rect.y_min
+                    && other.x_max >= rect.x_max
+                    && other.y_max >= rect.y_max
+                    && (other != *rect || other_index < *index)
+            })
+        })
+        .map(|(_, rect)| *rect)
+        .collect::<Vec<OcrRect>>();
+    let coverage = panels
+        .iter()
+        .map(|panel| (panel.width() * panel.height()) as f32)
+        .sum::<f32>();
+    if coverage < (image_width * image_height) as f32 * MIN_PANEL_COVERAGE_RATIO {
+        return whole_image;
+    }
+
+    // 3. reading order
+    order_blocks(&panels, OcrWritingDirection::VerticalTtbRtl)
+        .into_iter()
+        .map(|index| panels[index])
+        .collect()
+}
+
+// OCR each panel (see find_panels()) on its own, either as a whole or by its text regions, and merge the results
+// back into the coordinates of the whole image with the panels recorded in OcrTraitResult::panels, so that the
+// lines can be grouped (and read) panel by panel
+pub fn evaluate_by_panels(
+    ocr: &dyn OcrTrait,
+    image: &DynamicImage,
+    detect_text_regions: bool,
+) -> Result<OcrTraitResult, Error> {
+    let panels = find_panels(image);
+    let mut ocr_result = OcrTraitResult::new();
+    for panel in panels.iter() {
+        let panel_image = image.crop_imm(
+            panel.x_min as u32,
+            panel.y_min as u32,
+            panel.width(),
+            panel.height(),
+        );
+        let panel_result = if detect_text_regions {
+            evaluate_by_text_regions(ocr, &panel_image)
+        } else {
+            ocr.evaluate(&panel_image)
+        };
+        match panel_result {
+            Ok(panel_result) => ocr_result.append_at(panel_result, panel.x_min, panel.y_min),
+            Err(e) => eprintln!("Warning: OCR failed on panel {:?} - {:?}", panel, e),
+        }
+    }
+    ocr_result.panels = panels;
+    Ok(ocr_result)
+}
+
+// Preprocessing pipeline ahead of OcrTrait::evaluate(): a list of steps (each one image in, one image out) which is
+// written as a comma separated spec, i.e. "upscale:2,contrast,sauvola:15,denoise:1", so that it can be passed in
+// from the command line or environment per OCR backend (see main.rs), and filters can be experimented with without
+// rebuilding; when dump_dir is set, each step's output is saved as <dump_dir>/<name>_<index>_<step>.png
+// Steps that move pixels around (upscale, deskew) are recorded, so that the rects the OCR engine returns can be
+// mapped back to the coordinates of the original image (see PreprocessedImage::to_original_result())
+const SAUVOLA_K: f32 = 0.34; // sensitivity of Sauvola's threshold, 0.2..0.5 are the commonly used values
+const SAUVOLA_R: f32 = 128.0; // dynamic range of the standard deviation (of 8-bit grayscale)
+const MAX_DESKEW_DEGREES: f32 = 5.0; // scans are a few degrees off at most, more than that is intentional (art)
+const DESKEW_STEP_DEGREES: f32 = 0.25;
+const DESKEW_MAX_SIZE: u32 = 800; // skew is estimated on a downscaled image, since it does not need the detail
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PreprocessStep {
+    Grayscale,
+    Upscale(f32), // factor, i.e. 2.0 for small (screen) text that the OCR engine cannot make out
+    ContrastStretch, // stretch the 1st..99th percentile to 0..255 (washed out scans)
+    OtsuBinarize, // global threshold, fine for clean black text on white
+    AdaptiveBinarize(u32), // local mean threshold of (2r+1)x(2r+1) blocks, for uneven lighting
+    SauvolaBinarize(u32), // local mean and deviation threshold, which keeps text on gray (tone) backgrounds
+    Denoise(u32),         // median filter radius
+    Deskew,               // rotate back scans that are slightly tilted
+    SuppressScreentone(u32), // erase screentone dots of up to (2r+1)x(2r+1), leaving the text as-is
+    Invert,               // invert if the image is mostly dark, for white-on-black text
+}
+
+impl PreprocessStep {
+    pub fn parse(step_spec: &str) -> Result<Self, Error> {
+        let mut parts = step_spec.trim().splitn(2, ':');
+        let name = parts.next().unwrap_or_default().trim().to_lowercase();
+        let value = parts.next().map(|value| value.trim());
+        let radius = |default: u32| -> Result<u32, Error> {
+            match value {
+                Some(value) => value.parse::<u32>().map_err(|e| {
+                    anyhow::anyhow!("Invalid radius '{}' for '{}': {}", value, name, e)
+                }),
+                None => Ok(default),
+            }
+        };
+        Ok(match name.as_str() {
+            "grayscale" | "gray" => PreprocessStep::Grayscale,
+            "upscale" => {
+                let factor = match value {
+                    Some(value) => value.parse::<f32>().map_err(|e| {
+                        anyhow::anyhow!("Invalid factor '{}' for 'upscale': {}", value, e)
+                    })?,
+                    None => 2.0,
+                };
+                if !(1.0..=8.0).contains(&factor) {
+                    return Err(anyhow::anyhow!("'upscale' factor must be 1.0..=8.0"));
+                }
+                PreprocessStep::Upscale(factor)
+            }
+            "contrast" => PreprocessStep::ContrastStretch,
+            "otsu" => PreprocessStep::OtsuBinarize,
+            "adaptive" => PreprocessStep::AdaptiveBinarize(radius(15)?),
+            "sauvola" => PreprocessStep::SauvolaBinarize(radius(15)?),
+            "denoise" | "median" => PreprocessStep::Denoise(radius(1)?),
+            "deskew" => PreprocessStep::Deskew,
+            "screentone" => PreprocessStep::SuppressScreentone(radius(2)?),
+            "invert" => PreprocessStep::Invert,
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "Unknown preprocessing step '{}'",
+                    step_spec
+                ))
+            }
+        })
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            PreprocessStep::Grayscale => "grayscale".to_string(),
+            PreprocessStep::Upscale(factor) => format!("upscale:{}", factor),
+            PreprocessStep::ContrastStretch => "contrast".to_string(),
+            PreprocessStep::OtsuBinarize => "otsu".to_string(),
+            PreprocessStep::AdaptiveBinarize(radius) => format!("adaptive:{}", radius),
+            PreprocessStep::SauvolaBinarize(radius) => format!("sauvola:{}", radius),
+            PreprocessStep::Denoise(radius) => format!("denoise:{}", radius),
+            PreprocessStep::Deskew => "deskew".to_string(),
+            PreprocessStep::SuppressScreentone(radius) => format!("screentone:{}", radius),
+            PreprocessStep::Invert => "invert".to_string(),
+        }
+    }
+}
+
+// geometric changes made by the steps, in the order they were applied
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PreprocessTransform {
+    Scale(f32),
+    Rotate { radians: f32, center: (f32, f32) }, // clockwise, as imageproc's rotate_about_center()
+}
+
+#[derive(Debug, Clone)]
+pub struct PreprocessedImage {
+    pub image: DynamicImage,
+    transforms: Vec<PreprocessTransform>,
+}
+
+impl PreprocessedImage {
+    // maps a rect on the preprocessed image back onto the original image; for rotations, this is the bounding
+    // rect of the rotated corners, which for the few degrees of deskew is only slightly larger
+    pub fn to_original(&self, rect: &OcrRect) -> OcrRect {
+        let mut corners = [
+            (rect.x_min as f32, rect.y_min as f32),
+            (rect.x_max as f32, rect.y_min as f32),
+            (rect.x_max as f32, rect.y_max as f32),
+            (rect.x_min as f32, rect.y_max as f32),
+        ];
+        for transform in self.transforms.iter().rev() {
+            for corner in corners.iter_mut() {
+                *corner = match *transform {
+                    PreprocessTransform::Scale(factor) => (corner.0 / factor, corner.1 / factor),
+                    PreprocessTransform::Rotate { radians, center } => {
+                        let (dx, dy) = (corner.0 - center.0, corner.1 - center.1);
+                        let (sin, cos) = radians.sin_cos();
+                        (
+                            center.0 + dx * cos + dy * sin,
+                            center.1 - dx * sin + dy * cos,
+                        )
+                    }
+                };
+            }
+        }
+        let (x_min, x_max) = corners.iter().fold((f32::MAX, f32::MIN), |acc, corner| {
+            (acc.0.min(corner.0), acc.1.max(corner.0))
+        });
+        let (y_min, y_max) = corners.iter().fold((f32::MAX, f32::MIN), |acc, corner| {
+            (acc.0.min(corner.1), acc.1.max(corner.1))
+        });
+        OcrRect::new(
+            x_min.floor() as i32,
+            y_min.floor() as i32,
+            x_max.ceil() as i32,
+            y_max.ceil() as i32,
+        )
+    }
+
+    pub fn to_original_result(&self, ocr_result: OcrTraitResult) -> OcrTraitResult {
+        if self.transforms.is_empty() {
+            return ocr_result;
+        }
+        ocr_result.map_rects(|rect| self.to_original(rect))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreprocessPipeline {
+    pub steps: Vec<PreprocessStep>,
+    pub dump_dir: Option<std::path::PathBuf>,
+}
+
+impl PreprocessPipeline {
+    // an empty spec (or "none") is no preprocessing at all
+    pub fn parse(spec: &str) -> Result<Self, Error> {
+        let steps = spec
+            .split(',')
+            .map(|step_spec| step_spec.trim())
+            .filter(|step_spec| !step_spec.is_empty() && *step_spec != "none")
+            .map(PreprocessStep::parse)
+            .collect::<Result<Vec<PreprocessStep>, Error>>()?;
+        Ok(PreprocessPipeline {
+            steps,
+            dump_dir: None,
+        })
+    }
+
+    // default per OCR backend (OcrTrait::name()): Tesseract is trained on clean black-on-white text, so the contrast
+    // is stretched for it, while Windows.Media.Ocr and Google Cloud Vision do their own preprocessing, and are given
+    // the grayscale image as-is (which is what the lens always did)
+    pub fn default_for_backend(backend_name: &str) -> Self {
+        let spec = match backend_name {
+            "tesseract" => "grayscale,contrast",
+            _ => "grayscale",
+        };
+        Self::parse(spec).unwrap()
+    }
+
+    pub fn spec(&self) -> String {
+        self.steps
+            .iter()
+            .map(|step| step.name())
+            .collect::<Vec<String>>()
+            .join(",")
+    }
+
+    // name is used for the dumped images (i.e. the image file stem), so that a batch does not overwrite them
+    pub fn apply(&self, image: &DynamicImage, name: &str) -> Result<PreprocessedImage, Error> {
+        let mut preprocessed = PreprocessedImage {
+            image: image.clone(),
+            transforms: vec![],
+        };
+        if let Some(dump_dir) = &self.dump_dir {
+            std::fs::create_dir_all(dump_dir)?;
+        }
+        for (step_index, step) in self.steps.iter().enumerate() {
+            preprocessed.image =
+                Self::apply_step(step, &preprocessed.image, &mut preprocessed.transforms);
+            if let Some(dump_dir) = &self.dump_dir {
+                let dump_path = dump_dir.join(format!(
+                    "{}_{:02}_{}.png",
+                    name,
+                    step_index,
+                    step.name().replace(':', "-")
+                ));
+                preprocessed.image.save(&dump_path)?;
+                eprintln!("Preprocess: {} -> {}", step.name(), dump_path.display());
+            }
+        }
+        Ok(preprocessed)
+    }
+
+    fn apply_step(
+        step: &PreprocessStep,
+        image: &DynamicImage,
+        transforms: &mut Vec<PreprocessTransform>,
+    ) -> DynamicImage {
+        // everything but grayscale works on (and returns) 8-bit grayscale
+        let gray_image = image.to_luma8();
+        let processed = match *step {
+            PreprocessStep::Grayscale => gray_image,
+            PreprocessStep::Upscale(factor) => {
+                transforms.push(PreprocessTransform::Scale(factor));
+                imageops::resize(
+                    &gray_image,
+                    (gray_image.width() as f32 * factor).round() as u32,
+                    (gray_image.height() as f32 * factor).round() as u32,
+                    imageops::FilterType::CatmullRom,
+                )
+            }
+            PreprocessStep::ContrastStretch => stretch_contrast(&gray_image),
+            PreprocessStep::OtsuBinarize => {
+                let level = imageproc::contrast::otsu_level(&gray_image);
+                binarize(&gray_image, |_, _, value| value > level)
+            }
+            PreprocessStep::AdaptiveBinarize(radius) => {
+                imageproc::contrast::adaptive_threshold(&gray_image, radius)
+            }
+            PreprocessStep::SauvolaBinarize(radius) => sauvola_binarize(&gray_image, radius),
+            PreprocessStep::Denoise(radius) => {
+                imageproc::filter::median_filter(&gray_image, radius, radius)
+            }
+            PreprocessStep::Deskew => {
+                let degrees = estimate_skew_degrees(&gray_image);
+                if degrees.abs() < DESKEW_STEP_DEGREES {
+                    gray_image
+                } else {
+                    let radians = -degrees.to_radians();
+                    transforms.push(PreprocessTransform::Rotate {
+                        radians,
+                        center: (
+                            gray_image.width() as f32 / 2.0,
+                            gray_image.height() as f32 / 2.0,
+                        ),
+                    });
+                    imageproc::geometric_transformations::rotate_about_center(
+                        &gray_image,
+                        radians,
+                        imageproc::geometric_transformations::Interpolation::Bilinear,
+                        Luma([255u8]),
+                    )
+                }
+            }
+            PreprocessStep::SuppressScreentone(radius) => suppress_screentone(&gray_image, radius),
+            PreprocessStep::Invert => {
+                let total = gray_image
+                    .pixels()
+                    .map(|pixel| pixel[0] as u64)
+                    .sum::<u64>();
+                let pixel_count = std::cmp::max(gray_image.len() as u64, 1);
+                let mut inverted = gray_image;
+                if total / pixel_count < 128 {
+                    imageops::invert(&mut inverted);
+                }
+                inverted
+            }
+        };
+        DynamicImage::ImageLuma8(processed)
+    }
+}
+
+// black (0) where is_background is false, white (255) otherwise
+fn binarize(gray_image: &GrayImage, is_background: impl Fn(u32, u32, u8) -> bool) -> GrayImage {
+    GrayImage::from_fn(gray_image.width(), gray_image.height(), |x, y| {
+        let value = gray_image.get_pixel(x, y)[0];
+        if is_background(x, y, value) {
+            Luma([255u8])
+        } else {
+            Luma([0u8])
+        }
+    })
+}
+
+// stretches the 1st..99th percentile to the full range; percentiles rather than min/max, so that a few black
+// (i.e. borders) or white pixels do not prevent the stretch
+fn stretch_contrast(gray_image: &GrayImage) -> GrayImage {
+    let mut histogram = [0u64; 256];
+    for pixel in gray_image.pixels() {
+        histogram[pixel[0] as usize] += 1;
+    }
+    let pixel_count = gray_image.len() as u64;
+    let percentile = |fraction: f64| {
+        let target = (pixel_count as f64 * fraction) as u64;
+        let mut count = 0;
+        for (value, value_count) in histogram.iter().enumerate() {
+            count += value_count;
+            if count > target {
+                return value as f32;
+            }
+        }
+        255.0
+    };
+    let (low, high) = (percentile(0.01), percentile(0.99));
+    if high <= low {
+        return gray_image.clone();
+    }
+    let mut stretched = gray_image.clone();
+    for pixel in stretched.pixels_mut() {
+        let value = (pixel[0] as f32 - low) * 255.0 / (high - low);
+        pixel[0] = value.clamp(0.0, 255.0) as u8;
+    }
+    stretched
+}
+
+// Sauvola: threshold = mean * (1 + k * (deviation / R - 1)) over a (2r+1)x(2r+1) window, computed from integral
+// images (of the values and of their squares) so that it is linear regardless of the radius
+fn sauvola_binarize(gray_image: &GrayImage, radius: u32) -> GrayImage {
+    let (width, height) = gray_image.dimensions();
+    let stride = width as usize + 1;
+    let mut sums = vec![0u64; stride * (height as usize + 1)];
+    let mut squared_sums = vec![0u64; stride * (height as usize + 1)];
+    for y in 0..height as usize {
+        let (mut row_sum, mut row_squared_sum) = (0u64, 0u64);
+        for x in 0..width as usize {
+            let value = gray_image.get_pixel(x as u32, y as u32)[0] as u64;
+            row_sum += value;
+            row_squared_sum += value * value;
+            sums[(y + 1) * stride + x + 1] = sums[y * stride + x + 1] + row_sum;
+            squared_sums[(y + 1) * stride + x + 1] =
+                squared_sums[y * stride + x + 1] + row_squared_sum;
+        }
+    }
+    let window_sum = |integral: &[u64], x_min: usize, y_min: usize, x_max: usize, y_max: usize| {
+        integral[y_max * stride + x_max] + integral[y_min * stride + x_min]
+            - integral[y_min * stride + x_max]
+            - integral[y_max * stride + x_min]
+    };
+    binarize(gray_image, |x, y, value| {
+        let x_min = x.saturating_sub(radius) as usize;
+        let y_min = y.saturating_sub(radius) as usize;
+        let x_max = std::cmp::min(x + radius + 1, width) as usize;
+        let y_max = std::cmp::min(y + radius + 1, height) as usize;
+        let count = ((x_max - x_min) * (y_max - y_min)) as f32;
+        let mean = window_sum(&sums, x_min, y_min, x_max, y_max) as f32 / count;
+        let variance =
+            window_sum(&squared_sums, x_min, y_min, x_max, y_max) as f32 / count - mean * mean;
+        let threshold = mean * (1.0 + SAUVOLA_K * (variance.max(0.0).sqrt() / SAUVOLA_R - 1.0));
+        value as f32 > threshold
+    })
+}
+
+// Screentone suppression: halftone dots are small ink blobs that come in dense, (more or less) regular crowds,
+// while glyph strokes are either larger or, when small (dakuten, punctuation, the dot of a "!"), few and far
+// between; so rather than blurring (which thins out strokes as much as it does the dots), each ink component is
+// classified on its own, and only the ones that are dot sized AND have many dot sized neighbors are erased:
+//  1. binarize (Otsu) so that the ink is the foreground
+//  2. connected components of the ink, where those within (2r+1)x(2r+1) are dot candidates
+//  3. count the candidates on a grid of cells (a few dots wide), and a candidate is a screentone dot if the 3x3
+//     cells around it have at least SCREENTONE_MIN_DOTS candidates
+//  4. paint the screentone dots (and their anti-aliased fringe) white, leaving every other pixel as-is
+// NOTE: dark tones where the dots run into each other (a lattice rather than dots) are one large component, and
+// are left alone, as is gradation (which is not dots at all)
+const SCREENTONE_CELL_DOTS: u32 = 4; // cell size, in dot sizes
+const SCREENTONE_MIN_DOTS: u32 = 8; // in the 3x3 cells, so that even the sparsest tones count
+
+pub fn suppress_screentone(gray_image: &GrayImage, radius: u32) -> GrayImage {
+    // 1. ink (darker than the Otsu level) as foreground
+    let (width, height) = gray_image.dimensions();
+    let level = imageproc::contrast::otsu_level(gray_image);
+    let ink_image = GrayImage::from_fn(width, height, |x, y| {
+        if gray_image.get_pixel(x, y)[0] <= level {
+            Luma([255u8])
+        } else {
+            Luma([0u8])
+        }
+    });
+    let labels = imageproc::region_labelling::connected_components(
+        &ink_image,
+        imageproc::region_labelling::Connectivity::Eight,
+        Luma([0u8]),
+    );
+
+    // 2. dot candidates
+    let rects = label_rects(&labels);
+    let dot_size = radius * 2 + 1;
+    let is_dot_sized = |rect: &OcrRect| std::cmp::max(rect.width(), rect.height()) <= dot_size;
+
+    // 3. density of the dot sized components
+    let cell_size = dot_size * SCREENTONE_CELL_DOTS;
+    let (columns, rows) = (width.div_ceil(cell_size), height.div_ceil(cell_size));
+    let cell_of = |rect: &OcrRect| {
+        (
+            ((rect.x_min + rect.x_max) as u32 / 2) / cell_size,
+            ((rect.y_min + rect.y_max) as u32 / 2) / cell_size,
+        )
+    };
+    let mut cell_counts = vec![0u32; (columns * rows) as usize];
+    for rect in rects.iter().flatten().filter(|rect| is_dot_sized(rect)) {
+        let (column, row) = cell_of(rect);
+        cell_counts[(row * columns + column) as usize] += 1;
+    }
+    let is_screentone = rects
+        .iter()
+        .map(|rect| match rect {
+            Some(rect) if is_dot_sized(rect) => {
+                let (column, row) = cell_of(rect);
+                let mut count = 0;
+                for neighbor_row in row.saturating_sub(1)..std::cmp::min(row + 2, rows) {
+                    for neighbor_column in
+                        column.saturating_sub(1)..std::cmp::min(column + 2, columns)
+                    {
+                        count += cell_counts[(neighbor_row * columns + neighbor_column) as usize];
+                    }
+                }
+                count >= SCREENTONE_MIN_DOTS
+            }
+            _ => false,
+        })
+        .collect::<Vec<bool>>();
+
+    // 4. erase the dots, including the pixel around them (lighter than the Otsu level, so not part of the
+    // component) that anti-aliasing leaves behind, unless it belongs to other ink
+    let mut suppressed_image = gray_image.clone();
+    for (index, rect) in rects.iter().enumerate() {
+        let rect = match rect {
+            Some(rect) if is_screentone[index] => rect,
+            _ => continue,
+        };
+        let x_max = std::cmp::min(rect.x_max as u32 + 1, width);
+        let y_max = std::cmp::min(rect.y_max as u32 + 1, height);
+        for y in (rect.y_min as u32).saturating_sub(1)..y_max {
+            for x in (rect.x_min as u32).saturating_sub(1)..x_max {
+                let label = labels.get_pixel(x, y)[0] as usize;
+                if label == 0 || is_screentone[label - 1] {
+                    suppressed_image.put_pixel(x, y, Luma([255u8]));
+                }
+            }
+        }
+    }
+    suppressed_image
+}
+
+// skew (in degrees, clockwise) that makes the ink line up the best in rows or columns (whichever is the stronger,
+// since text can be either horizontal or vertical), found by maximizing the sum of squares of the projection
+// profile over the candidate angles
+pub fn estimate_skew_degrees(gray_image: &GrayImage) -> f32 {
+    let (width, height) = gray_image.dimensions();
+    let downscale = std::cmp::max(width, height) as f32 / DESKEW_MAX_SIZE as f32;
+    let small_image = if downscale > 1.0 {
+        imageops::resize(
+            gray_image,
+            (width as f32 / downscale) as u32,
+            (height as f32 / downscale) as u32,
+            imageops::FilterType::Triangle,
+        )
+    } else {
+        gray_image.clone()
+    };
+    let level = imageproc::contrast::otsu_level(&small_image);
+    let ink = small_image
+        .enumerate_pixels()
+        .filter(|(_, _, pixel)| pixel[0] <= level)
+        .map(|(x, y, _)| (x as f32, y as f32))
+        .collect::<Vec<(f32, f32)>>();
+    if ink.is_empty() {
+        return 0.0;
+    }
+    let profile_size = (small_image.width() + small_image.height()) as usize * 2;
+    let offset = profile_size as f32 / 2.0;
+    let score = |degrees: f32| {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let mut rows = vec![0u32; profile_size];
+        let mut columns = vec![0u32; profile_size];
+        for (x, y) in ink.iter() {
+            // coordinates after rotating the ink counter-clockwise by the angle (i.e. undoing a clockwise skew)
+            let row = (-x * sin + y * cos + offset) as usize;
+            let column = (x * cos + y * sin + offset) as usize;
+            rows[row.min(profile_size - 1)] += 1;
+            columns[column.min(profile_size - 1)] += 1;
+        }
+        let sum_of_squares = |profile: &[u32]| {
+            profile
+                .iter()
+                .map(|count| (*count as f64).powi(2))
+                .sum::<f64>()
+        };
+        sum_of_squares(&rows).max(sum_of_squares(&columns))
+    };
+    let step_count = (MAX_DESKEW_DEGREES / DESKEW_STEP_DEGREES) as i32;
+    let mut best = (0.0, score(0.0));
+    for step in -step_count..=step_count {
+        let degrees = step as f32 * DESKEW_STEP_DEGREES;
+        let degrees_score = score(degrees);
+        if degrees_score > best.1 {
+            best = (degrees, degrees_score);
+        }
+    }
+    best.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::FakeOcr;
+
+    #[test]
+    fn test_find_text_regions_and_evaluate() {
+        let page_image = image::open("../assets/ubunchu01_02.png").unwrap();
+        let regions = find_text_regions(&page_image);
+        // the "最近人気の デスクトップな リナックスです!" speech bubble on the first panel
+        assert!(regions.iter().any(|region| {
+            region.x_min <= 170 && region.y_min <= 240 && region.x_max >= 320 && region.y_max >= 490
+        }));
+        // right-to-left
+        assert!(regions
+            .windows(2)
+            .all(|pair| pair[0].x_max >= pair[1].x_max));
+
+        let ocr_result = evaluate_by_text_regions(&FakeOcr::new(), &page_image).unwrap();
+        assert_eq!(ocr_result.rects.len(), regions.len());
+        for (index, (line, region)) in ocr_result.rects.iter().zip(regions.iter()).enumerate() {
+            assert_eq!(line.rect(), *region); // back in page coordinates
+            assert_eq!(line.block_index() as usize, index);
+            assert_eq!(line.line_index() as usize, index);
+        }
+
+        // an already cropped speech bubble is the text region itself, so it is evaluated as a whole
+        let bubble_image = image::open("../assets/ubunchu01_02_panel01_section_02.png").unwrap();
+        assert!(find_text_regions(&bubble_image).is_empty());
+        let ocr_result = evaluate_by_text_regions(&FakeOcr::new(), &bubble_image).unwrap();
+        assert_eq!(ocr_result.rects[0].rect(), OcrRect::new(0, 0, 187, 286));
+    }
+
+    #[test]
+    fn test_preprocess_pipeline() {
+        let pipeline =
+            PreprocessPipeline::parse("upscale:2, contrast,deskew,sauvola:7,denoise").unwrap();
+        assert_eq!(
+            pipeline.spec(),
+            "upscale:2,contrast,deskew,sauvola:7,denoise:1"
+        );
+        assert!(PreprocessPipeline::parse("grayscale,sharpen").is_err());
+        assert!(PreprocessPipeline::parse("none").unwrap().steps.is_empty());
+
+        // a bubble tilted by 3 degrees is binarized, deskewed back, and the rects map back onto the original
+        let bubble_image = image::open("../assets/ubunchu01_02_panel01_section_02.png").unwrap();
+        let tilted_image =
+            DynamicImage::ImageLuma8(imageproc::geometric_transformations::rotate_about_center(
+                &bubble_image.to_luma8(),
+                3f32.to_radians(),
+                imageproc::geometric_transformations::Interpolation::Bilinear,
+                Luma([255u8]),
+            ));
+        assert!((estimate_skew_degrees(&tilted_image.to_luma8()) - 3.0).abs() <= 0.5);
+        let preprocessed = pipeline.apply(&tilted_image, "tilted").unwrap();
+        assert_eq!(
+            preprocessed.image.dimensions(),
+            (bubble_image.width() * 2, bubble_image.height() * 2)
+        );
+        assert!(preprocessed
+            .image
+            .as_luma8()
+            .unwrap()
+            .pixels()
+            .all(|pixel| pixel[0] == 0 || pixel[0] == 255));
+        assert!(estimate_skew_degrees(preprocessed.image.as_luma8().unwrap()).abs() <= 0.5);
+        // the center does not move on rotation (the rotated corners are floored/ceiled, hence a pixel larger), and
+        // the upper left moves with the tilt (clockwise, so to the right and up)
+        let center = OcrRect::new(186, 284, 188, 288);
+        assert_eq!(
+            preprocessed.to_original(&center),
+            OcrRect::new(92, 141, 95, 145)
+        );
+        let upper_left = preprocessed.to_original(&OcrRect::new(40, 40, 42, 42));
+        assert_eq!((upper_left.x_min, upper_left.y_min), (25, 16));
+    }
+
+    // ink components (darker than the Otsu level of the original) that are within the dot size
+    fn count_dots(gray_image: &GrayImage, level: u8, radius: u32) -> usize {
+        let ink_image = GrayImage::from_fn(gray_image.width(), gray_image.height(), |x, y| {
+            Luma([if gray_image.get_pixel(x, y)[0] <= level {
+                255
+            } else {
+                0
+            }])
+        });
+        component_rects(&ink_image)
+            .iter()
+            .filter(|rect| std::cmp::max(rect.width(), rect.height()) <= radius * 2 + 1)
+            .count()
+    }
+
+    #[test]
+    fn test_suppress_screentone() {
+        let gray_image = image::open("../assets/ubunchu01_02.png")
+            .unwrap()
+            .to_luma8();
+        let level = imageproc::contrast::otsu_level(&gray_image);
+        let suppressed_image = suppress_screentone(&gray_image, 2);
+        assert_eq!(suppressed_image.dimensions(), gray_image.dimensions());
+        // most of the dots are gone (the rest are the sparse ones, or next to the dark tone lattice)
+        assert!(count_dots(&suppressed_image, level, 2) * 2 <= count_dots(&gray_image, level, 2));
+        // while the text (including the dakuten and the small kana) is untouched: the "最近人気の デスクトップな
+        // リナックスです!" bubble, and the "※ うぶんちゅではなくウブントゥです" caption between the panels
+        for (x_min, y_min, x_max, y_max) in [(150, 138, 360, 517), (400, 635, 770, 665)] {
+            for y in y_min..y_max {
+                for x in x_min..x_max {
+                    assert_eq!(suppressed_image.get_pixel(x, y), gray_image.get_pixel(x, y));
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[ignore = "needs Tesseract (with jpn and jpn_vert), run with `cargo test -- --ignored`"]
+    fn test_suppress_screentone_ocr() {
+        // Tesseract reads screentones as runs of latin letters (i.e. "NN", "SS"), which is what should go away
+        let noise_word_count = |ocr_result: &OcrTraitResult| {
+            ocr_result
+                .rects
+                .iter()
+                .flat_map(|line| line.words())
+                .filter(|word| {
+                    !word.word().is_empty() && word.word().chars().all(|c| c.is_ascii_alphabetic())
+                })
+                .count()
+        };
+        let ocr = crate::ocr_tesseract::OcrTesseract::new();
+        let gray_image = image::open("../assets/ubunchu01_02.png")
+            .unwrap()
+            .to_luma8();
+        let before = ocr
+            .evaluate(&DynamicImage::ImageLuma8(gray_image.clone()))
+            .unwrap();
+        let after = ocr
+            .evaluate(&DynamicImage::ImageLuma8(suppress_screentone(
+                &gray_image,
+                2,
+            )))
+            .unwrap();
+        eprintln!("before:\n{}\nafter:\n{}", before.text, after.text);
+        assert!(noise_word_count(&after) < noise_word_count(&before));
+    }
+
+    #[test]
+    fn test_find_panels_and_evaluate() {
+        assert_eq!(
+            find_panels(&DynamicImage::new_luma8(0, 0)),
+            vec![OcrRect::new(0, 0, 0, 0)]
+        );
+        assert_eq!(
+            find_panels(&DynamicImage::new_luma8(5, 0)),
+            vec![OcrRect::new(0, 0, 5, 0)]
+        );
+        let page_image = image::open("../assets/ubunchu01_02.png").unwrap();
+        let panels = find_panels(&page_image);
+        // three tiers of a panel each, top-to-bottom; each panel covers (at least) the inside of its frame
+        assert_eq!(panels.len(), 3);
+        let insides = [
+            OcrRect::new(70, 110, 1140, 625),
+            OcrRect::new(70, 670, 1140, 990),
+            OcrRect::new(70, 1030, 1140, 1540),
+        ];
+        for (panel, inside) in panels.iter().zip(insides.iter()) {
+            assert_eq!(panel.union(inside), *panel);
+        }
+        assert!(panels.windows(2).all(|pair| pair[0].y_max <= pair[1].y_min));
+
+        let ocr_result = evaluate_by_panels(&FakeOcr::new(), &page_image, false).unwrap();
+        assert_eq!(ocr_result.panels, panels);
+        let ordered = crate::reading_order::to_reading_order(ocr_result);
+        for (index, (line, panel)) in ordered.rects.iter().zip(panels.iter()).enumerate() {
+            assert_eq!(line.rect(), *panel);
+            assert_eq!(line.block_index() as usize, index);
+        }
+
+        // a speech bubble has no gutters, so it is a single panel
+        let bubble_image = image::open("../assets/ubunchu01_02_panel01_section_02.png").unwrap();
+        assert_eq!(find_panels(&bubble_image), [OcrRect::new(0, 0, 187, 286)]);
+    }
+
+    #[test]
+    fn test_layout_text() {
+        let ocr_image = OCRImage::new(None);
+        let font = ocr_image.get_font_bold();
+
+        // horizontal: wraps where the glyphs reach the edge, not by chars (or bytes)
+        let text = "最近人気のLinux";
+        let unwrapped = layout_text(
+            font,
+            text,
+            32.0,
+            OcrWritingDirection::HorizontalLtr,
+            u32::MAX,
+        );
+        assert_eq!(unwrapped.chars.len(), text.chars().count());
+        assert!(unwrapped.chars.iter().all(|laid_out| laid_out.y == 0));
+        assert!(unwrapped.chars.windows(2).all(|pair| pair[0].x < pair[1].x));
+        let wrapped = layout_text(
+            font,
+            text,
+            32.0,
+            OcrWritingDirection::HorizontalLtr,
+            unwrapped.chars[4].x as u32,
+        );
+        assert!(wrapped.width <= unwrapped.chars[4].x as u32);
+        assert!(wrapped.height > unwrapped.height);
+
+        // vertical: 3 characters per column, columns right-to-left, and the long-vowel mark is rotated
+        let vertical = layout_text(
+            font,
+            "ラーメン、\nです",
+            32.0,
+            OcrWritingDirection::VerticalTtbRtl,
+            96,
+        );
+        assert_eq!(vertical.height, 96);
+        let column_of = |index: usize| vertical.chars[index].x / (vertical.width as i32 / 3);
+        assert_eq!(
+            (0..vertical.chars.len()).map(column_of).collect::<Vec<_>>(),
+            [2, 2, 2, 1, 1, 0, 0]
         );
+        assert!(vertical.chars[1].rotated);
+        assert!(!vertical.chars[0].rotated);
 
-        // and finally, convert it back to DynamicImage
-        let ret = DynamicImage::ImageRgba8(overlayed_image);
-        println!(
-            "Overlayed text onto image ({}x{}) - {} chars",
-            ret.width(),
-            ret.height(),
-            text.len()
+        // fit: shrinks the font until the text fits the rect
+        let fitted = fit_text(
+            font,
+            "最近人気のデスクトップなリナックスです!",
+            OcrWritingDirection::VerticalTtbRtl,
+            100,
+            200,
+            DEFAULT_FONT_SIZE,
         );
-        //if cfg!(debug_assertions) {
-        //    // append first 10 characters of the text to the filename ( replace space with no-space)
-        //    let first_10 = &text[0..10]
-        //        .replace(" ", "")
-        //        .replace(":", "_")
-        //        .replace("?", "_")
-        //        .replace("\\", "_")
-        //        .replace("/", "_");
-        //    let filename = format!("overlay_text_{}.png", first_10);
-        //    println!("Saving: {}", filename.clone());
-        //    match text_image_canvas.save(filename.clone()) {
-        //        Ok(_) => println!(
-        //            "Saved: {} - {} bytes",
-        //            filename.clone(),
-        //            text_image_canvas.len()
-        //        ),
-        //        Err(e) => println!("Error: Could not save {} - {}", filename.clone(), e),
-        //    }
-        //    let filename = format!("overlayed_text_{}.png", first_10);
-        //    match ret.clone().save(filename.clone()) {
-        //        Ok(_) => println!(
-        //            "Saved: {} - {} bytes",
-        //            filename.clone(),
-        //            ret.clone().as_bytes().len()
-        //        ),
-        //        Err(e) => println!("Error: Could not save {} - {}", filename.clone(), e),
-        //    }
-        //}
-
-        ret
+        assert!(fitted.font_size < DEFAULT_FONT_SIZE);
+        assert!(fitted.width <= 100 && fitted.height <= 200);
     }
 }
@@ -0,0 +1,227 @@
+// Chain of interpreters (i.e. --interpreter kakasi,libretranslate for the reading and the translation): each of
+// them interprets the same text, and their lines are put side by side, i.e. "にんきのほん / Popular book"; the
+// tokens (furigana) are the ones of the first interpreter that has any, or else a token per line of the first
+// interpreter that does not translate, so that the furigana are the readings rather than the lines side by side
+use crate::interpreter_passthrough::InterpreterPassthrough;
+use crate::interpreter_traits::{InterpreterToken, InterpreterTrait, InterpreterTraitResult};
+use crate::kana::OutputScript;
+use anyhow::Error;
+
+const LINE_SEPARATOR: &str = " / ";
+
+pub(crate) struct InterpreterChain {
+    interpreters: Vec<Box<dyn InterpreterTrait>>,
+}
+
+impl InterpreterChain {
+    pub fn new_with_interpreters(interpreters: Vec<Box<dyn InterpreterTrait>>) -> Self {
+        InterpreterChain { interpreters }
+    }
+
+    // the results of the interpreters combined: line by line if each has a line per line of the text, otherwise
+    // one after the other
+    fn combine(
+        text: &str,
+        results: Vec<InterpreterTraitResult>,
+        translates: &[bool],
+    ) -> InterpreterTraitResult {
+        let line_count = text.split('\n').count();
+        let tokens = match results.iter().find(|result| !result.tokens.is_empty()) {
+            Some(result) => result.tokens.clone(),
+            None => results
+                .iter()
+                .zip(translates.iter())
+                .find(|(result, translates)| !**translates && result.lines.len() == line_count)
+                .map(|(result, _)| Self::line_tokens(text, &result.lines))
+                .unwrap_or_default(),
+        };
+        // the translations of the translators on their own as well, so that the lens can draw them over the OCR
+        // lines along with the furigana
+        let translation_results: Vec<&InterpreterTraitResult> = results
+            .iter()
+            .filter(|result| result.translations.len() == line_count)
+            .collect();
+        let translations: Vec<String> = if translation_results.is_empty() {
+            vec![]
+        } else {
+            (0..line_count)
+                .map(|index| {
+                    translation_results
+                        .iter()
+                        .map(|result| result.translations[index].as_str())
+                        .filter(|line| !line.is_empty())
+                        .collect::<Vec<&str>>()
+                        .join(LINE_SEPARATOR)
+                })
+                .collect()
+        };
+        let lines: Vec<String> = if results
+            .iter()
+            .all(|result| result.lines.len() == line_count)
+        {
+            (0..line_count)
+                .map(|index| {
+                    results
+                        .iter()
+                        .map(|result| result.lines[index].as_str())
+                        .filter(|line| !line.is_empty())
+                        .collect::<Vec<&str>>()
+                        .join(LINE_SEPARATOR)
+                })
+                .collect()
+        } else {
+            results
+                .into_iter()
+                .flat_map(|result| result.lines)
+                .collect()
+        };
+        InterpreterTraitResult {
+            text: lines.join("\n"),
+            lines,
+            tokens,
+            translations,
+        }
+    }
+}
+
+impl InterpreterChain {
+    // a token per (non-empty) line of the text, whose reading is the line of the result
+    fn line_tokens(text: &str, lines: &[String]) -> Vec<InterpreterToken> {
+        let mut tokens = vec![];
+        let mut start = 0;
+        for (line, reading) in text.split('\n').zip(lines.iter()) {
+            let end = start + line.chars().count();
+            if start < end {
+                tokens.push(InterpreterToken {
+                    surface: line.to_string(),
+                    reading: reading.clone(),
+                    base_form: line.to_string(),
+                    part_of_speech: None,
+                    start,
+                    end,
+                });
+            }
+            start = end + 1;
+        }
+        tokens
+    }
+}
+
+impl InterpreterTrait for InterpreterChain {
+    fn new() -> Self
+    where
+        Self: Sized,
+    {
+        InterpreterChain::new_with_interpreters(vec![Box::new(InterpreterPassthrough::new())])
+    }
+
+    // the first interpreter is the one that the chain is about (the one that gets recorded in the results)
+    fn name(&self) -> &str {
+        self.interpreters
+            .first()
+            .map(|interpreter| interpreter.name())
+            .unwrap_or("chain")
+    }
+
+    fn init(&self) -> Vec<String> {
+        let mut languages: Vec<String> = vec![];
+        for language in self
+            .interpreters
+            .iter()
+            .flat_map(|interpreter| interpreter.init())
+        {
+            if !languages.contains(&language) {
+                languages.push(language);
+            }
+        }
+        languages
+    }
+
+    fn convert(&self, text: &str) -> Result<InterpreterTraitResult, Error> {
+        let results = self
+            .interpreters
+            .iter()
+            .map(|interpreter| interpreter.convert(text))
+            .collect::<Result<Vec<InterpreterTraitResult>, Error>>()?;
+        let translates: Vec<bool> = self
+            .interpreters
+            .iter()
+            .map(|interpreter| interpreter.translates())
+            .collect();
+        Ok(Self::combine(text, results, &translates))
+    }
+
+    fn output_script(&self) -> OutputScript {
+        self.interpreters
+            .iter()
+            .find(|interpreter| !interpreter.translates())
+            .map(|interpreter| interpreter.output_script())
+            .unwrap_or(OutputScript::Hiragana)
+    }
+
+    // all the interpreters of the readings (the translators have none) switch the script
+    fn set_output_script(&mut self, output_script: OutputScript) -> bool {
+        let mut supported = false;
+        for interpreter in self.interpreters.iter_mut() {
+            if !interpreter.translates() {
+                supported |= interpreter.set_output_script(output_script);
+            }
+        }
+        supported
+    }
+
+    // only a chain of translators is drawn over the OCR lines, otherwise the furigana of the readings are
+    fn translates(&self) -> bool {
+        self.interpreters
+            .iter()
+            .all(|interpreter| interpreter.translates())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dictionary::Dictionary;
+    use crate::interpreter_translate::{InterpreterTranslate, TranslateBackend};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_convert_with_chain() {
+        let dictionary =
+            Dictionary::import(&[PathBuf::from("../assets/fixtures/jmdict_mini.xml")]).unwrap();
+        let chain = InterpreterChain::new_with_interpreters(vec![
+            Box::new(InterpreterPassthrough::new()),
+            Box::new(InterpreterTranslate::new_with_endpoint(
+                TranslateBackend::Gloss,
+                "",
+                None,
+                Some(std::sync::Arc::new(dictionary)),
+            )),
+        ]);
+        assert_eq!(chain.name(), "passthrough");
+        assert!(!chain.translates());
+
+        let result = chain.convert("人気の本\n\n読んだ").unwrap();
+        assert_eq!(
+            result.lines,
+            [
+                "人気の本 / popularity, public favor / book",
+                "",
+                "読んだ / to read"
+            ]
+        );
+        assert_eq!(
+            result.translations,
+            ["popularity, public favor / book", "", "to read"]
+        );
+        // the furigana are the passthrough's lines, not the lines side by side
+        assert_eq!(
+            result
+                .tokens
+                .iter()
+                .map(|token| (token.reading.as_str(), token.start, token.end))
+                .collect::<Vec<_>>(),
+            [("人気の本", 0, 4), ("読んだ", 6, 9)]
+        );
+    }
+}
@@ -0,0 +1,416 @@
+// External command interpreter: any CLI tool that reads the text on stdin and writes its interpretation to stdout
+// (kakasi, mecab, a local translator, ...) plugged in via --interpreter command, configured by --interpreter-command
+// <kakasi|mecab|config.json> or environment variable LENZU_INTERPRETER_COMMAND, where the config is:
+//      {
+//          "name": "kakasi",                       // recorded in the results (see result_schema.rs)
+//          "executable": "C:\\kakasi\\bin\\kakasi.exe",
+//          "args": ["-JH", "-i", "utf8", "-o", "utf8"],
+//          "env": {"KANWADICTPATH": "C:\\kakasi\\share\\kakasi\\kanwadict"},
+//          "timeout_ms": 5000,
+//          "output": "text",                       // "text" (as-is), or "mecab" (words, see below)
+//          "languages": ["ja"]
+//      }
+// stdin is written while stdout/stderr are read (each on its own thread, so that a tool which writes before it
+// has read all of its input does not deadlock on a full pipe), and the tool is killed if it does not exit in time;
+// stderr is only logged, it is the exit status that tells whether the tool failed
+// with "output": "mecab", stdout is MeCab's default format ("<surface>\t<features>" per word, "EOS" per line of
+// input), so that the tokens have their readings, base forms and part of speech (see interpreter_mecab.rs)
+use crate::interpreter_ja::render_tokens;
+use crate::interpreter_mecab::MecabDictionary;
+use crate::interpreter_traits::{InterpreterToken, InterpreterTrait, InterpreterTraitResult};
+use crate::kana::OutputScript;
+use anyhow::Error;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+const ENV_INTERPRETER_COMMAND: &str = "LENZU_INTERPRETER_COMMAND";
+const ENV_KAKASI: &str = "LENZU_KAKASI"; // executable of the kakasi preset, "kakasi" (on the PATH) by default
+const ENV_MECAB: &str = "LENZU_MECAB"; // executable of the mecab preset, "mecab" (on the PATH) by default
+const DEFAULT_TIMEOUT_MS: u64 = 10_000;
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum CommandOutput {
+    #[default]
+    Text,
+    Mecab,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct CommandConfig {
+    pub name: String,
+    pub executable: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>, // in addition to (or overriding) the environment of lenzu
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+    #[serde(default)]
+    pub output: CommandOutput,
+    #[serde(default = "default_languages")]
+    pub languages: Vec<String>,
+}
+
+fn default_timeout_ms() -> u64 {
+    DEFAULT_TIMEOUT_MS
+}
+
+fn default_languages() -> Vec<String> {
+    vec!["ja".to_string()]
+}
+
+// the ways a command can fail, so that callers can tell them apart (via anyhow's downcast_ref())
+#[derive(Debug)]
+pub(crate) enum CommandError {
+    Spawn {
+        executable: String,
+        error: std::io::Error,
+    },
+    Io(std::io::Error),
+    Timeout {
+        executable: String,
+        timeout_ms: u64,
+    },
+    Failed {
+        executable: String,
+        code: Option<i32>,
+        stderr: String,
+    },
+    InvalidOutput(String),
+}
+
+impl Display for CommandError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandError::Spawn { executable, error } => {
+                write!(f, "unable to start '{}' - {}", executable, error)
+            }
+            CommandError::Io(error) => write!(f, "I/O error - {}", error),
+            CommandError::Timeout {
+                executable,
+                timeout_ms,
+            } => write!(f, "'{}' did not finish in {} ms", executable, timeout_ms),
+            CommandError::Failed {
+                executable,
+                code,
+                stderr,
+            } => write!(
+                f,
+                "'{}' failed ({}) - {}",
+                executable,
+                code.map(|code| format!("exit code {}", code))
+                    .unwrap_or("killed".to_string()),
+                stderr.trim()
+            ),
+            CommandError::InvalidOutput(message) => write!(f, "invalid output - {}", message),
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+impl CommandConfig {
+    // kakasi (as a command rather than the kakasi crate), i.e. for its newer dictionaries
+    pub fn kakasi() -> Self {
+        CommandConfig {
+            name: "kakasi".to_string(),
+            executable: std::env::var(ENV_KAKASI).unwrap_or("kakasi".to_string()),
+            args: ["-JH", "-i", "utf8", "-o", "utf8"]
+                .iter()
+                .map(|arg| arg.to_string())
+                .collect(),
+            env: HashMap::new(),
+            timeout_ms: DEFAULT_TIMEOUT_MS,
+            output: CommandOutput::Text,
+            languages: default_languages(),
+        }
+    }
+
+    // mecab with its installed (system) dictionary
+    pub fn mecab() -> Self {
+        CommandConfig {
+            name: "mecab".to_string(),
+            executable: std::env::var(ENV_MECAB).unwrap_or("mecab".to_string()),
+            args: vec![],
+            env: HashMap::new(),
+            timeout_ms: DEFAULT_TIMEOUT_MS,
+            output: CommandOutput::Mecab,
+            languages: default_languages(),
+        }
+    }
+
+    // a preset name ("kakasi" or "mecab"), or else the path of a JSON config
+    pub fn from_arg(arg: &str) -> Result<Self, Error> {
+        match arg {
+            "kakasi" => Ok(Self::kakasi()),
+            "mecab" => Ok(Self::mecab()),
+            path => Ok(serde_json::from_str(&std::fs::read_to_string(Path::new(
+                path,
+            ))?)?),
+        }
+    }
+
+    // the one of LENZU_INTERPRETER_COMMAND, or else the kakasi preset
+    pub fn from_env() -> Self {
+        std::env::var(ENV_INTERPRETER_COMMAND)
+            .ok()
+            .and_then(|arg| {
+                CommandConfig::from_arg(&arg)
+                    .map_err(|e| eprintln!("Error: {} - {:?}", arg, e))
+                    .ok()
+            })
+            .unwrap_or(CommandConfig::kakasi())
+    }
+
+    // path of the executable, looked up on the PATH unless it is a path already; None if it is not there
+    pub fn find_executable(&self) -> Option<PathBuf> {
+        let executable = Path::new(&self.executable);
+        if executable.components().count() > 1 {
+            return Some(executable.to_path_buf()).filter(|path| path.is_file());
+        }
+        let extensions: &[&str] = if cfg!(target_os = "windows") {
+            &["", "exe", "cmd", "bat"]
+        } else {
+            &[""]
+        };
+        std::env::split_paths(&std::env::var_os("PATH")?)
+            .flat_map(|dir| {
+                extensions.iter().map(move |extension| match *extension {
+                    "" => dir.join(executable),
+                    extension => dir.join(format!("{}.{}", self.executable, extension)),
+                })
+            })
+            .find(|path| path.is_file())
+    }
+
+    // runs the command with the input on stdin, returns its stdout
+    pub fn run(&self, input: &str) -> Result<String, CommandError> {
+        let mut child = Command::new(&self.executable)
+            .args(&self.args)
+            .envs(&self.env)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|error| CommandError::Spawn {
+                executable: self.executable.clone(),
+                error,
+            })?;
+
+        // stdin is closed (dropped) once written, which is how the tool knows that the input is complete
+        let mut stdin = child.stdin.take().ok_or(CommandError::InvalidOutput(
+            "stdin is not piped".to_string(),
+        ))?;
+        let input = input.to_string();
+        let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+        let read_all = |mut pipe: Box<dyn Read + Send>| {
+            std::thread::spawn(move || {
+                let mut output = Vec::new();
+                pipe.read_to_end(&mut output).map(|_| output)
+            })
+        };
+        let stdout_reader = read_all(Box::new(child.stdout.take().ok_or(
+            CommandError::InvalidOutput("stdout is not piped".to_string()),
+        )?));
+        let stderr_reader = read_all(Box::new(child.stderr.take().ok_or(
+            CommandError::InvalidOutput("stderr is not piped".to_string()),
+        )?));
+
+        let deadline = Instant::now() + Duration::from_millis(self.timeout_ms);
+        let status = loop {
+            match child.try_wait().map_err(CommandError::Io)? {
+                Some(status) => break status,
+                None if Instant::now() >= deadline => {
+                    // the pipes are closed once it is killed, so the threads end as well
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(CommandError::Timeout {
+                        executable: self.executable.clone(),
+                        timeout_ms: self.timeout_ms,
+                    });
+                }
+                None => std::thread::sleep(WAIT_POLL_INTERVAL),
+            }
+        };
+
+        // a tool that exits without reading all of its input is only a failure if its exit status says so
+        let _ = writer.join();
+        let join = |reader: std::thread::JoinHandle<std::io::Result<Vec<u8>>>| {
+            reader
+                .join()
+                .map_err(|_| CommandError::InvalidOutput("reader thread panicked".to_string()))?
+                .map_err(CommandError::Io)
+        };
+        let stdout = join(stdout_reader)?;
+        let stderr = String::from_utf8_lossy(&join(stderr_reader)?).to_string();
+        if !status.success() {
+            return Err(CommandError::Failed {
+                executable: self.executable.clone(),
+                code: status.code(),
+                stderr,
+            });
+        }
+        if !stderr.trim().is_empty() {
+            eprintln!("{}: {}", self.name, stderr.trim());
+        }
+        String::from_utf8(stdout).map_err(|e| {
+            CommandError::InvalidOutput(format!("'{}' did not output UTF-8 - {}", self.name, e))
+        })
+    }
+}
+
+// tokens of MeCab's default output, located in the text (in order) for their char offsets
+fn parse_mecab_output(text: &str, output: &str) -> Result<Vec<InterpreterToken>, CommandError> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = vec![];
+    let mut position = 0;
+    for line in output.lines() {
+        let Some((surface, features)) = line.split_once('\t') else {
+            continue; // EOS, or an empty line
+        };
+        let surface_chars: Vec<char> = surface.chars().collect();
+        let start = (position..chars.len())
+            .find(|start| chars[*start..].starts_with(&surface_chars))
+            .ok_or(CommandError::InvalidOutput(format!(
+                "'{}' is not in the text",
+                surface
+            )))?;
+        let end = start + surface_chars.len();
+        tokens.push(MecabDictionary::to_token(
+            surface.to_string(),
+            features,
+            start,
+            end,
+        ));
+        position = end;
+    }
+    Ok(tokens)
+}
+
+pub(crate) struct InterpreterCommand {
+    config: CommandConfig,
+}
+
+impl InterpreterCommand {
+    pub fn from_config(config: CommandConfig) -> Self {
+        InterpreterCommand { config }
+    }
+}
+
+impl InterpreterTrait for InterpreterCommand {
+    fn new() -> Self
+    where
+        Self: Sized,
+    {
+        Self::from_config(CommandConfig::from_env())
+    }
+
+    fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    fn init(&self) -> Vec<String> {
+        self.config.languages.clone()
+    }
+
+    fn convert(&self, text: &str) -> Result<InterpreterTraitResult, Error> {
+        let output = self.config.run(text)?;
+        let (text, tokens) = match self.config.output {
+            CommandOutput::Text => (output.trim_end_matches('\n').to_string(), vec![]),
+            CommandOutput::Mecab => {
+                let tokens = parse_mecab_output(text, &output)?;
+                (render_tokens(text, &tokens, OutputScript::Hiragana), tokens)
+            }
+        };
+        Ok(InterpreterTraitResult {
+            lines: text.split('\n').map(|line| line.to_string()).collect(),
+            text,
+            tokens,
+            translations: vec![],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // fake tools are shell scripts, hence unix only
+    #[cfg(unix)]
+    #[test]
+    fn test_convert_with_fake_commands() {
+        let shell = |name: &str, script: &str, output: CommandOutput| {
+            InterpreterCommand::from_config(CommandConfig {
+                name: name.to_string(),
+                executable: "/bin/sh".to_string(),
+                args: vec!["-c".to_string(), script.to_string()],
+                env: HashMap::from([("GREETING".to_string(), "やあ".to_string())]),
+                timeout_ms: 2000,
+                output,
+                languages: default_languages(),
+            })
+        };
+
+        // stdin to stdout, with the configured environment and a warning on stderr that is not a failure
+        let echo = shell(
+            "echo",
+            "echo warning >&2; printf \"$GREETING \"; cat",
+            CommandOutput::Text,
+        );
+        assert_eq!(echo.name(), "echo");
+        let result = echo.convert("最近人気の\nデスクトップ\n").unwrap();
+        assert_eq!(result.lines, ["やあ 最近人気の", "デスクトップ"]);
+
+        // large input and output (more than a pipe buffer) does not deadlock
+        let large = "あ".repeat(200_000);
+        assert_eq!(
+            shell("cat", "cat", CommandOutput::Text)
+                .convert(&large)
+                .unwrap()
+                .text,
+            large
+        );
+
+        let mecab = shell(
+            "mecab",
+            "cat > /dev/null; printf '人気\\t名詞,一般,*,*,*,*,人気,ニンキ,ニンキ\\nの\\t助詞,連体化,*,*,*,*,の,ノ,ノ\\nEOS\\n'",
+            CommandOutput::Mecab,
+        );
+        let result = mecab.convert("人気の").unwrap();
+        assert_eq!(result.text, "にんきの");
+        assert_eq!(result.tokens[1].start, 2);
+        assert_eq!(
+            result.tokens[1].part_of_speech.as_deref(),
+            Some("助詞-連体化")
+        );
+
+        let error = |interpreter: InterpreterCommand| {
+            interpreter
+                .convert("人気の")
+                .unwrap_err()
+                .downcast::<CommandError>()
+                .unwrap()
+        };
+        assert!(matches!(
+            error(shell("sleep", "sleep 10", CommandOutput::Text)),
+            CommandError::Timeout { .. }
+        ));
+        assert!(matches!(
+            error(shell("fail", "echo broken >&2; exit 3", CommandOutput::Text)),
+            CommandError::Failed { code: Some(3), ref stderr, .. } if stderr.trim() == "broken"
+        ));
+        let mut missing = CommandConfig::kakasi();
+        missing.executable = "/nonexistent/kakasi".to_string();
+        assert!(matches!(
+            error(InterpreterCommand::from_config(missing)),
+            CommandError::Spawn { .. }
+        ));
+    }
+}
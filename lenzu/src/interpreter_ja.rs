@@ -1,17 +1,24 @@
-use crate::interpreter_traits::{InterpreterTrait, InterpreterTraitResult}; // so odd that unless I'd  import it in main.rs, this will not be recognized, but once it is recognized, you can comment it in main.rs
+use crate::interpreter_traits::{InterpreterToken, InterpreterTrait, InterpreterTraitResult}; // so odd that unless I'd  import it in main.rs, this will not be recognized, but once it is recognized, you can comment it in main.rs
+use crate::kana::{align_reading, is_kanji, script_of, to_hiragana, OutputScript, Script};
 use anyhow::{Error, Ok};
 use kakasi;
-use std::io::{BufRead, BufReader, Write};
-use std::process::{Command, Stdio};
 
-pub(crate) struct InterpreterJa {}
+pub(crate) struct InterpreterJa {
+    output_script: OutputScript,
+}
 
 impl InterpreterTrait for InterpreterJa {
     fn new() -> Self
     where
         Self: Sized,
     {
-        InterpreterJa {}
+        InterpreterJa {
+            output_script: OutputScript::Hiragana,
+        }
+    }
+
+    fn name(&self) -> &str {
+        "kakasi"
     }
 
     fn init(&self) -> Vec<String> {
@@ -20,92 +27,193 @@ impl InterpreterTrait for InterpreterJa {
 
     fn convert(&self, text: &str) -> Result<InterpreterTraitResult, Error> {
         let result = kakasi::convert(text);
-        let lines = vec![result.hiragana.split('\n').map(|s| s.to_string()).collect()];
-        let text = result.hiragana;
-        Ok(InterpreterTraitResult { text, lines })
-        //self.call_shell_kakasi(text)
+        let tokens = Self::tokenize(text, &result.hiragana);
+        let text = match self.output_script {
+            OutputScript::Hiragana => result.hiragana,
+            OutputScript::Katakana => self.output_script.convert_kana(&result.hiragana),
+            OutputScript::Romaji(_) | OutputScript::KanjiReading => {
+                render_tokens(text, &tokens, self.output_script)
+            }
+        };
+        let lines = text.split('\n').map(|s| s.to_string()).collect();
+        Ok(InterpreterTraitResult {
+            text,
+            lines,
+            tokens,
+            translations: vec![],
+        })
+    }
+
+    fn output_script(&self) -> OutputScript {
+        self.output_script
+    }
+
+    fn set_output_script(&mut self, output_script: OutputScript) -> bool {
+        self.output_script = output_script;
+        true
     }
 }
 
+// the text with each token replaced by its reading in the output script, keeping what is in between the tokens
+// (whitespace, newlines) as-is; romaji words are separated by a space where a kanji or katakana run starts (the
+// tokens of kakasi are runs of the same script, so that okurigana and particles stay with the word before them)
+pub(crate) fn render_tokens(
+    text: &str,
+    tokens: &[InterpreterToken],
+    output_script: OutputScript,
+) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut rendered = String::new();
+    let mut position = 0;
+    let mut after_n = false;
+    for token in tokens.iter() {
+        rendered.extend(chars[position..token.start].iter());
+        match output_script {
+            OutputScript::KanjiReading if token.surface.chars().any(is_kanji) => {
+                let surface: Vec<char> = token.surface.chars().collect();
+                let kanji_runs = align_reading(&token.surface, &token.reading)
+                    .unwrap_or_else(|| vec![(0..surface.len(), token.reading.clone())]);
+                let mut surface_position = 0;
+                for (range, reading) in kanji_runs {
+                    rendered.extend(surface[surface_position..range.end].iter());
+                    rendered.push_str(&format!("[{}]", reading));
+                    surface_position = range.end;
+                }
+                rendered.extend(surface[surface_position..].iter());
+            }
+            OutputScript::KanjiReading => rendered.push_str(&token.surface),
+            _ => {
+                let starts_word = token
+                    .surface
+                    .chars()
+                    .next()
+                    .map(|c| matches!(script_of(c), Script::Kanji | Script::Katakana))
+                    .unwrap_or(false);
+                let reading = output_script.convert_kana(&token.reading);
+                if matches!(output_script, OutputScript::Romaji(_)) && token.start == position {
+                    if starts_word && rendered.ends_with(|c: char| c.is_ascii_alphanumeric()) {
+                        rendered.push(' ');
+                    } else if after_n && reading.starts_with(['a', 'i', 'u', 'e', 'o', 'y']) {
+                        rendered.push('\''); // same as within a token, see kana::to_romaji()
+                    }
+                }
+                rendered.push_str(&reading);
+            }
+        }
+        after_n = token.reading.ends_with('ん');
+        position = token.end;
+    }
+    rendered.extend(chars[position..].iter());
+    rendered
+}
+
 impl InterpreterJa {
     pub fn new() -> Self {
-        InterpreterJa {}
+        InterpreterJa {
+            output_script: OutputScript::Hiragana,
+        }
     }
 
-    pub fn call_shell_kakasi(&self, text: &str) -> Result<InterpreterTraitResult, Error> {
-        // Create a Command for the 'kakasi' shell command
-        //      SET KANWADICTPATH=C:\kakasi\share\kakasi\kanwadict
-        //      SET ITAIJIDICTPATH=C:\kakasi\share\kakasi\itaijidict
-        //      $ kakasi -JH  -i utf8 -o utf8 <<< "最近人気の\nデスクトップな\nリナックスです!"
-        //      さいきんにんきの\nデスクトップな\nリナックスです!
-        //      $ kakasi -JH  -i utf8 -o utf8 -f <<< "最近人気の\nデスクトップな\nリナックスです!"
-        //      最近[さいきん]人気[にんき]の\nデスクトップな\nリナックスです!
-        let mut kakasi_cmd = if cfg!(target_os = "windows") {
-            Command::new("..\\..\\kakasi\\bin\\kakasi.exe")
-                .arg("-JH")
-                .arg("-i")
-                .arg("utf8")
-                .arg("-o")
-                .arg("utf8")
-                .arg("-f")
-                .stdin(Stdio::piped()) // Set up stdin for input
-                .stdout(Stdio::piped()) // Set up stdout for capturing
-                .stderr(Stdio::piped()) // Set up stderr for capturing
-                .spawn()
-                .expect("Failed to start kakasi process")
-        } else {
-            Command::new("kakasi")
-                .arg("-JH")
-                .arg("-i")
-                .arg("utf8")
-                .arg("-o")
-                .arg("utf8")
-                .arg("-f")
-                .stdin(Stdio::piped()) // Set up stdin for input
-                .stdout(Stdio::piped()) // Set up stdout for capturing
-                .stderr(Stdio::piped()) // Set up stderr for capturing
-                .spawn()
-                .expect("Failed to start kakasi process")
-        };
-
-        // Write your input data to the stdin stream
-        if let Some(stdin) = kakasi_cmd.stdin.as_mut() {
-            //let text = "最近人気の\nデスクトップな\nリナックスです!";
-            stdin
-                .write_all(text.as_bytes())
-                .expect("Failed to write to stdin");
+    // kakasi does not tell us the words, so the tokens are the runs of the same script (kanji, hiragana,
+    // katakana, or other) and the reading of each kanji run is its part of the whole reading (see
+    // kana::align_reading()), or if that does not fit, the kanji run converted on its own (which loses the
+    // context, i.e. okurigana, that kakasi uses to pick the reading)
+    fn tokenize(text: &str, reading: &str) -> Vec<InterpreterToken> {
+        let aligned = align_reading(text, reading).unwrap_or_default();
+        let chars: Vec<char> = text.chars().collect();
+        let mut tokens = vec![];
+        let mut start = 0;
+        while start < chars.len() {
+            let script = script_of(chars[start]);
+            let end = chars[start..]
+                .iter()
+                .position(|c| script_of(*c) != script || c.is_whitespace())
+                .map(|length| start + length.max(1))
+                .unwrap_or(chars.len());
+            if chars[start].is_whitespace() {
+                start = end;
+                continue;
+            }
+            let surface: String = chars[start..end].iter().collect();
+            let reading = match script {
+                Script::Kanji => aligned
+                    .iter()
+                    .find(|(range, _)| range.start == start)
+                    .map(|(_, reading)| reading.clone())
+                    .unwrap_or_else(|| kakasi::convert(&surface).hiragana),
+                Script::Katakana => surface.chars().map(to_hiragana).collect(),
+                _ => surface.clone(),
+            };
+            tokens.push(InterpreterToken {
+                base_form: surface.clone(),
+                surface,
+                reading,
+                part_of_speech: None,
+                start,
+                end,
+            });
+            start = end;
         }
+        tokens
+    }
+}
 
-        // Wait for the process to complete
-        let exit_status: std::process::ExitStatus = kakasi_cmd
-            .wait()
-            .expect("Failed to wait for kakasi process");
-
-        // Read stdout and stderr
-        let mut stdout_reader =
-            BufReader::new(kakasi_cmd.stdout.expect("Failed to capture stdout"));
-        let mut stderr_reader =
-            BufReader::new(kakasi_cmd.stderr.expect("Failed to capture stderr"));
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        let stdout_lines = stdout_reader
-            .lines()
-            .map(|line| line.unwrap())
-            .collect::<Vec<String>>();
-        let stderr_lines = stderr_reader
-            .lines()
-            .map(|line| line.unwrap())
-            .collect::<Vec<String>>();
+    #[test]
+    fn test_tokenize() {
+        let text = "最近人気の\nデスクトップ!";
+        let tokens = InterpreterJa::tokenize(text, "さいきんにんきの\nですくとっぷ!");
+        let summary: Vec<(&str, &str, usize, usize)> = tokens
+            .iter()
+            .map(|token| {
+                (
+                    token.surface.as_str(),
+                    token.reading.as_str(),
+                    token.start,
+                    token.end,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("最近人気", "さいきんにんき", 0, 4),
+                ("の", "の", 4, 5),
+                ("デスクトップ", "ですくとっぷ", 6, 12),
+                ("!", "!", 12, 13),
+            ]
+        );
+        // offsets are chars, not bytes
+        let surfaces: Vec<String> = tokens
+            .iter()
+            .map(|token| {
+                text.chars()
+                    .skip(token.start)
+                    .take(token.end - token.start)
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            surfaces,
+            tokens
+                .iter()
+                .map(|token| token.surface.clone())
+                .collect::<Vec<_>>()
+        );
 
-        match stderr_lines.len() {
-            0 => {
-                // no error
-                let text = stdout_lines.join("\n");
-                Ok(InterpreterTraitResult {
-                    text,
-                    lines: stdout_lines,
-                })
-            }
-            _ => Err(anyhow::anyhow!(stderr_lines.join("\n"))),
-        }
+        // output scripts other than hiragana are rendered from the tokens
+        let tokens = InterpreterJa::tokenize("人気の本を読む", "にんきのほんをよむ");
+        let render = |output_script| render_tokens("人気の本を読む", &tokens, output_script);
+        assert_eq!(
+            render(OutputScript::KanjiReading),
+            "人気[にんき]の本[ほん]を読[よ]む"
+        );
+        assert_eq!(
+            render(OutputScript::Romaji(crate::kana::RomajiSystem::Hepburn)),
+            "ninkino hon'o yomu"
+        );
     }
 }
@@ -0,0 +1,243 @@
+// Dictionary interpreter: the words of the text are looked up in the local dictionary store (JMdict, JMnedict
+// and/or Yomitan dictionaries, see dictionary.rs), so that the result has a line per word with its dictionary
+// form, reading and glosses, and the tokens (with the readings of the inflected surface) are used for furigana
+// the store is --dictionary <store.bin>, or environment variable LENZU_DICTIONARY (or dictionary.bin in the
+// current directory), and the glosses are in the first of the languages of LENZU_DICTIONARY_LANGUAGES that the
+// word has (comma separated JMdict language codes, i.e. "ger,eng", "eng" by default)
+use crate::dictionary::{Dictionary, DictionaryMatch, DEFAULT_DICTIONARY_STORE, ENV_DICTIONARY};
+use crate::interpreter_traits::{InterpreterToken, InterpreterTrait, InterpreterTraitResult};
+use crate::kana::to_hiragana;
+use anyhow::Error;
+use std::path::Path;
+use std::sync::Arc;
+
+const ENV_DICTIONARY_LANGUAGES: &str = "LENZU_DICTIONARY_LANGUAGES";
+const DEFAULT_DICTIONARY_LANGUAGE: &str = "eng";
+const MAX_GLOSSES: usize = 3; // per word, the overlay has little room
+
+pub(crate) struct InterpreterJmdict {
+    dictionary: Option<Arc<Dictionary>>,
+    languages: Vec<String>,
+}
+
+fn languages() -> Vec<String> {
+    std::env::var(ENV_DICTIONARY_LANGUAGES)
+        .unwrap_or(DEFAULT_DICTIONARY_LANGUAGE.to_string())
+        .split(',')
+        .map(|language| language.trim().to_string())
+        .filter(|language| !language.is_empty())
+        .collect()
+}
+
+// the reading of the (inflected) surface from the reading of its dictionary form: the dictionary form and the
+// surface share the stem (i.e. "読" of 読む and 読んだ), so the reading of the stem is the reading of the
+// dictionary form minus its inflected ending (i.e. よむ -> よ), plus the ending of the surface (i.e. よんだ)
+fn surface_reading(surface: &str, base_form: &str, base_reading: &str) -> String {
+    let surface: Vec<char> = surface.chars().collect();
+    let base_form: Vec<char> = base_form.chars().collect();
+    let stem_length = surface
+        .iter()
+        .zip(base_form.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let base_ending: String = base_form[stem_length..]
+        .iter()
+        .map(|c| to_hiragana(*c))
+        .collect();
+    let base_reading: String = base_reading.chars().map(to_hiragana).collect();
+    match base_reading.strip_suffix(base_ending.as_str()) {
+        Some(stem_reading) if stem_length > 0 => {
+            let surface_ending: String = surface[stem_length..]
+                .iter()
+                .map(|c| to_hiragana(*c))
+                .collect();
+            format!("{}{}", stem_reading, surface_ending)
+        }
+        _ => base_reading,
+    }
+}
+
+impl InterpreterJmdict {
+    pub fn from_store(path: &Path) -> Result<Self, Error> {
+        Ok(InterpreterJmdict {
+            dictionary: Some(Dictionary::load_shared(path)?),
+            languages: languages(),
+        })
+    }
+
+    // the line of a word, i.e. "読んだ (読む, past) 【よむ】 to read; ..."
+    fn describe(&self, dictionary_match: &DictionaryMatch) -> String {
+        let best = &dictionary_match.entries[0];
+        let mut line = dictionary_match.surface.clone();
+        if !best.reasons.is_empty() {
+            line.push_str(&format!(
+                " ({}, {})",
+                best.base_form,
+                best.reasons.join(", ")
+            ));
+        }
+        if let Some(reading) = best
+            .entry
+            .readings
+            .first()
+            .filter(|reading| **reading != best.base_form)
+        {
+            line.push_str(&format!(" 【{}】", reading));
+        }
+        let glosses = best.entry.glosses(&self.languages);
+        if !glosses.is_empty() {
+            line.push_str(&format!(
+                " {}",
+                glosses
+                    .iter()
+                    .take(MAX_GLOSSES)
+                    .cloned()
+                    .collect::<Vec<&str>>()
+                    .join("; ")
+            ));
+        }
+        if !best.entry.tags.is_empty() {
+            line.push_str(&format!(" [{}]", best.entry.tags.join(", ")));
+        }
+        line
+    }
+}
+
+impl InterpreterTrait for InterpreterJmdict {
+    fn new() -> Self
+    where
+        Self: Sized,
+    {
+        let path = std::env::var(ENV_DICTIONARY).unwrap_or(DEFAULT_DICTIONARY_STORE.to_string());
+        let dictionary = if Path::new(&path).exists() {
+            Dictionary::load_shared(Path::new(&path))
+                .map_err(|e| eprintln!("Error: {:?}", e))
+                .ok()
+        } else {
+            None
+        };
+        InterpreterJmdict {
+            dictionary,
+            languages: languages(),
+        }
+    }
+
+    fn name(&self) -> &str {
+        "jmdict"
+    }
+
+    fn init(&self) -> Vec<String> {
+        if self.dictionary.is_none() {
+            eprintln!(
+                "JMdict - Warning: no dictionary, import one with `lenzu dictionary import` and pass --dictionary <store.bin> or set {}",
+                ENV_DICTIONARY
+            );
+        }
+        vec!["ja".to_string()]
+    }
+
+    fn convert(&self, text: &str) -> Result<InterpreterTraitResult, Error> {
+        let dictionary = self.dictionary.as_ref().ok_or(anyhow::anyhow!(
+            "Dictionary is not loaded (see --dictionary or {})",
+            ENV_DICTIONARY
+        ))?;
+        let matches = dictionary.lookup_text(text);
+        let tokens = matches
+            .iter()
+            .map(|dictionary_match| {
+                let best = &dictionary_match.entries[0];
+                // the reading of the entry that is spelled as the text (kana words are their own reading)
+                let base_reading = best
+                    .entry
+                    .readings
+                    .iter()
+                    .find(|reading| {
+                        reading
+                            .chars()
+                            .map(to_hiragana)
+                            .eq(best.base_form.chars().map(to_hiragana))
+                    })
+                    .or(best.entry.readings.first())
+                    .cloned()
+                    .unwrap_or(best.base_form.clone());
+                InterpreterToken {
+                    surface: dictionary_match.surface.clone(),
+                    reading: surface_reading(
+                        &dictionary_match.surface,
+                        &best.base_form,
+                        &base_reading,
+                    ),
+                    base_form: best.base_form.clone(),
+                    part_of_speech: best
+                        .entry
+                        .senses
+                        .first()
+                        .filter(|sense| !sense.part_of_speech.is_empty())
+                        .map(|sense| sense.part_of_speech.join(",")),
+                    start: dictionary_match.start,
+                    end: dictionary_match.end,
+                }
+            })
+            .collect();
+        let lines: Vec<String> = matches
+            .iter()
+            .map(|dictionary_match| self.describe(dictionary_match))
+            .collect();
+        Ok(InterpreterTraitResult {
+            text: lines.join("\n"),
+            lines,
+            tokens,
+            translations: vec![],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_convert_with_jmdict() {
+        assert_eq!(surface_reading("読んだ", "読む", "よむ"), "よんだ");
+        assert_eq!(surface_reading("人気", "人気", "にんき"), "にんき");
+
+        let interpreter = InterpreterJmdict {
+            dictionary: Some(Arc::new(
+                Dictionary::import(&[PathBuf::from("../assets/fixtures/jmdict_mini.xml")]).unwrap(),
+            )),
+            languages: vec!["ger".to_string(), "eng".to_string()],
+        };
+        let result = interpreter.convert("人気の本を読んだ").unwrap();
+        let words: Vec<(&str, &str, &str, usize, usize)> = result
+            .tokens
+            .iter()
+            .map(|token| {
+                (
+                    token.surface.as_str(),
+                    token.reading.as_str(),
+                    token.base_form.as_str(),
+                    token.start,
+                    token.end,
+                )
+            })
+            .collect();
+        assert_eq!(
+            words,
+            [
+                ("人気", "にんき", "人気", 0, 2),
+                ("本", "ほん", "本", 3, 4),
+                ("読んだ", "よんだ", "読む", 5, 8),
+            ]
+        );
+        assert_eq!(result.tokens[2].part_of_speech.as_deref(), Some("v5m,vt"));
+        assert_eq!(
+            result.lines,
+            [
+                "人気 【にんき】 popularity; public favor; popular feeling [news1, nf01]",
+                "本 【ほん】 Buch",
+                "読んだ (読む, past) 【よむ】 lesen",
+            ]
+        );
+    }
+}
@@ -0,0 +1,409 @@
+// Morphological analyzer (offline) with MeCab's dictionaries, via lindera, so that we get words with their
+// readings, base forms and part of speech rather than kakasi's flat hiragana (and without having to get mecab
+// itself to build, see README)
+// The dictionary is either a lindera dictionary (as built by `lindera build`, with its metadata.json), or the
+// source (not the compiled sys.dic) of a MeCab dictionary such as mecab-ipadic or UniDic, in UTF-8 or EUC-JP:
+//      <dir>/*.csv     lexicon: surface,left-id,right-id,cost,features... (one or more files, i.e. Noun.csv, Verb.csv)
+//      <dir>/matrix.def    connection costs: "<right-id of the previous word> <left-id of the next word> <cost>"
+//      <dir>/char.def      character categories, and how to group unknown words of each category
+//      <dir>/unk.def       unknown words per character category, same as the lexicon with the category as surface
+// which is passed via --mecab-dictionary <dir>, or environment variable LENZU_MECAB_DICTIONARY
+// The source is built into a lindera dictionary on first use, in the user's cache directory (keyed by the files of
+// the source, so that editing them builds it again), and later starts only load the built dictionary
+use crate::interpreter_traits::{InterpreterToken, InterpreterTrait, InterpreterTraitResult};
+use crate::kana::to_hiragana;
+use anyhow::Error;
+use lindera::dictionary::{load_fs_dictionary, DictionaryBuilder, Metadata};
+use lindera::mode::Mode;
+use lindera::segmenter::Segmenter;
+use std::borrow::Cow;
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+pub(crate) const ENV_MECAB_DICTIONARY: &str = "LENZU_MECAB_DICTIONARY";
+const LINDERA_METADATA: &str = "metadata.json"; // of a built lindera dictionary
+
+// feature columns (after surface,left-id,right-id,cost) of IPADIC and UniDic: both start with 4 part of speech
+// columns, then IPADIC has conjugation type/form, base form and reading (katakana), whereas UniDic (which has
+// 17 or more columns) has lemma reading, lemma, orth and pronunciation, and from v3 on, the reading (kana)
+const POS_COLUMNS: usize = 4;
+const IPADIC_BASE_FORM_COLUMN: usize = 6;
+const IPADIC_READING_COLUMN: usize = 7;
+const UNIDIC_MIN_COLUMNS: usize = 17;
+const UNIDIC_LEMMA_COLUMN: usize = 7;
+const UNIDIC_PRONUNCIATION_COLUMN: usize = 9;
+const UNIDIC_KANA_COLUMN: usize = 20;
+
+pub(crate) struct MecabDictionary {
+    segmenter: Segmenter,
+}
+
+// CSV of the dictionaries are mostly plain, but UniDic quotes the surfaces that have a comma (i.e. `","`)
+fn split_csv(line: &str) -> Vec<String> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+// where the source of a MeCab dictionary is built to, which changes along with the files of the source
+fn built_dir(source: &Path) -> Result<PathBuf, Error> {
+    let mut hasher = DefaultHasher::new();
+    fs::canonicalize(source)?.hash(&mut hasher);
+    let mut entries: Vec<fs::DirEntry> = fs::read_dir(source)?
+        .filter_map(|entry| entry.ok())
+        .collect();
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let metadata = entry.metadata()?;
+        entry.file_name().hash(&mut hasher);
+        metadata.len().hash(&mut hasher);
+        metadata.modified().ok().hash(&mut hasher);
+    }
+    Ok(cache_dir()?.join(format!("mecab_{:016x}", hasher.finish())))
+}
+
+// $XDG_CACHE_HOME/lenzu, ~/.cache/lenzu or %LOCALAPPDATA%\lenzu, rather than the temporary directory that is shared
+// with the other users (any of whom could put a dictionary there to be loaded)
+fn cache_dir() -> Result<PathBuf, Error> {
+    let base = match std::env::var_os("XDG_CACHE_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => match std::env::var_os("HOME").filter(|dir| !dir.is_empty()) {
+            Some(home) => PathBuf::from(home).join(".cache"),
+            None => std::env::var_os("LOCALAPPDATA")
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from)
+                .ok_or_else(|| anyhow::anyhow!("no cache directory, set XDG_CACHE_HOME"))?,
+        },
+    };
+    let dir = base.join("lenzu");
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+impl MecabDictionary {
+    pub fn load(dir: &Path) -> Result<Self, Error> {
+        let start_load = std::time::Instant::now();
+        let dictionary = if dir.join(LINDERA_METADATA).is_file() {
+            load_fs_dictionary(dir)
+                .map_err(|e| anyhow::anyhow!("Unable to load '{}' - {}", dir.display(), e))?
+        } else {
+            // built on first use, or again if it cannot be loaded (i.e. built by another version of lindera)
+            let built = built_dir(dir)
+                .map_err(|e| anyhow::anyhow!("Unable to read '{}' - {}", dir.display(), e))?;
+            match load_fs_dictionary(&built) {
+                Ok(dictionary) => dictionary,
+                Err(_) => {
+                    Self::build(dir, &built)?;
+                    load_fs_dictionary(&built).map_err(|e| {
+                        anyhow::anyhow!("Unable to load '{}' - {}", built.display(), e)
+                    })?
+                }
+            }
+        };
+        eprintln!(
+            "MeCab dictionary '{}' ({} mSec)",
+            dir.display(),
+            start_load.elapsed().as_millis()
+        );
+        Ok(MecabDictionary {
+            segmenter: Segmenter::new(Mode::Normal, dictionary, None),
+        })
+    }
+
+    // builds the lindera dictionary of the source of a MeCab dictionary (UTF-8, or else EUC-JP as mecab-ipadic
+    // is distributed in)
+    pub fn build(source: &Path, output: &Path) -> Result<(), Error> {
+        let mut csv_paths: Vec<PathBuf> = fs::read_dir(source)
+            .map_err(|e| anyhow::anyhow!("Unable to read '{}' - {}", source.display(), e))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.extension()
+                    .map(|extension| extension.eq_ignore_ascii_case("csv"))
+                    .unwrap_or(false)
+            })
+            .collect();
+        csv_paths.sort();
+        let Some(csv_path) = csv_paths.first() else {
+            return Err(anyhow::anyhow!(
+                "No lexicon (*.csv) in '{}', expected the source of a MeCab dictionary",
+                source.display()
+            ));
+        };
+        let metadata = Metadata {
+            name: source.display().to_string(),
+            encoding: match String::from_utf8(fs::read(csv_path)?) {
+                Ok(_) => "UTF-8".to_string(),
+                Err(_) => "EUC-JP".to_string(),
+            },
+            flexible_csv: true, // UniDic has more feature columns than IPADIC
+            ..Metadata::default()
+        };
+
+        // built aside and then moved, so that an interrupted build is not loaded later on
+        let building = output.with_extension(format!("building{}", std::process::id()));
+        DictionaryBuilder::new(metadata)
+            .build_dictionary(source, &building)
+            .map_err(|e| anyhow::anyhow!("Unable to build '{}' - {}", source.display(), e))?;
+        if output.exists() {
+            fs::remove_dir_all(output)?;
+        }
+        fs::rename(&building, output)?;
+        Ok(())
+    }
+
+    // tokens of the text; whitespace (including newlines) separates the words, as in MeCab
+    pub fn analyze(&self, text: &str) -> Result<Vec<InterpreterToken>, Error> {
+        let chars: Vec<char> = text.chars().collect();
+        let mut tokens = vec![];
+        let mut start = 0;
+        while start < chars.len() {
+            if chars[start].is_whitespace() {
+                start += 1;
+                continue;
+            }
+            let end = chars[start..]
+                .iter()
+                .position(|c| c.is_whitespace())
+                .map(|length| start + length)
+                .unwrap_or(chars.len());
+            let run: String = chars[start..end].iter().collect();
+            for mut word in self
+                .segmenter
+                .segment(Cow::Borrowed(&run))
+                .map_err(|e| anyhow::anyhow!("MeCab - {}", e))?
+            {
+                let surface = word.surface.to_string();
+                let word_start = start + run[..word.byte_start].chars().count();
+                let word_end = word_start + surface.chars().count();
+                let features: Vec<String> = word.details().iter().map(|f| f.to_string()).collect();
+                tokens.push(Self::token(surface, &features, word_start, word_end));
+            }
+            start = end;
+        }
+        Ok(tokens)
+    }
+
+    // from the features of MeCab's output, i.e. "名詞,一般,*,*,*,*,人気,ニンキ,ニンキ"
+    pub(crate) fn to_token(
+        surface: String,
+        features: &str,
+        start: usize,
+        end: usize,
+    ) -> InterpreterToken {
+        Self::token(surface, &split_csv(features), start, end)
+    }
+
+    fn token(surface: String, features: &[String], start: usize, end: usize) -> InterpreterToken {
+        let feature = |index: usize| {
+            features
+                .get(index)
+                .filter(|feature| !feature.is_empty() && feature.as_str() != "*")
+                .cloned()
+        };
+        let (base_form, reading) = if features.len() >= UNIDIC_MIN_COLUMNS {
+            (
+                feature(UNIDIC_LEMMA_COLUMN),
+                feature(UNIDIC_KANA_COLUMN).or(feature(UNIDIC_PRONUNCIATION_COLUMN)),
+            )
+        } else {
+            (
+                feature(IPADIC_BASE_FORM_COLUMN),
+                feature(IPADIC_READING_COLUMN),
+            )
+        };
+        // i.e. "名詞-固有名詞-組織" (as ChaSen does)
+        let part_of_speech: Vec<String> = (0..POS_COLUMNS).filter_map(feature).collect();
+        InterpreterToken {
+            reading: reading
+                .unwrap_or(surface.clone())
+                .chars()
+                .map(to_hiragana)
+                .collect(),
+            base_form: base_form.unwrap_or(surface.clone()),
+            part_of_speech: if part_of_speech.is_empty() {
+                None
+            } else {
+                Some(part_of_speech.join("-"))
+            },
+            surface,
+            start,
+            end,
+        }
+    }
+}
+
+pub(crate) struct InterpreterMecab {
+    dictionary: Option<MecabDictionary>,
+}
+
+impl InterpreterTrait for InterpreterMecab {
+    fn new() -> Self
+    where
+        Self: Sized,
+    {
+        let dictionary = std::env::var(ENV_MECAB_DICTIONARY).ok().and_then(|dir| {
+            MecabDictionary::load(Path::new(&dir))
+                .map_err(|e| eprintln!("Error: {:?}", e))
+                .ok()
+        });
+        InterpreterMecab { dictionary }
+    }
+
+    fn name(&self) -> &str {
+        "mecab"
+    }
+
+    fn init(&self) -> Vec<String> {
+        if self.dictionary.is_none() {
+            eprintln!(
+                "MeCab - Warning: no dictionary, pass --mecab-dictionary <dir> or set {}",
+                ENV_MECAB_DICTIONARY
+            );
+        }
+        vec!["ja".to_string()]
+    }
+
+    fn convert(&self, text: &str) -> Result<InterpreterTraitResult, Error> {
+        let dictionary = self.dictionary.as_ref().ok_or(anyhow::anyhow!(
+            "MeCab dictionary is not loaded (see --mecab-dictionary or {})",
+            ENV_MECAB_DICTIONARY
+        ))?;
+        let tokens = dictionary.analyze(text)?;
+
+        // the readings in place of the words, keeping the whitespace (and newlines) as-is
+        let chars: Vec<char> = text.chars().collect();
+        let mut reading = String::new();
+        let mut position = 0;
+        for token in tokens.iter() {
+            reading.extend(chars[position..token.start].iter());
+            reading.push_str(&token.reading);
+            position = token.end;
+        }
+        reading.extend(chars[position..].iter());
+        Ok(InterpreterTraitResult {
+            lines: reading.split('\n').map(|line| line.to_string()).collect(),
+            text: reading,
+            tokens,
+            translations: vec![],
+        })
+    }
+}
+
+impl InterpreterMecab {
+    pub fn from_dir(dir: &Path) -> Result<Self, Error> {
+        Ok(InterpreterMecab {
+            dictionary: Some(MecabDictionary::load(dir)?),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_convert_with_ipadic() {
+        // a handful of IPADIC words, the rest (katakana) are unknown words grouped by char.def
+        let interpreter =
+            InterpreterMecab::from_dir(Path::new("../assets/fixtures/mecab_ipadic")).unwrap();
+        let result = interpreter
+            .convert("最近人気の\nデスクトップなリナックスを読んだ")
+            .unwrap();
+        let words: Vec<(&str, &str, &str, usize, usize)> = result
+            .tokens
+            .iter()
+            .map(|token| {
+                (
+                    token.surface.as_str(),
+                    token.reading.as_str(),
+                    token.base_form.as_str(),
+                    token.start,
+                    token.end,
+                )
+            })
+            .collect();
+        assert_eq!(
+            words,
+            [
+                ("最近", "さいきん", "最近", 0, 2),
+                ("人気", "にんき", "人気", 2, 4),
+                ("の", "の", "の", 4, 5),
+                ("デスクトップ", "ですくとっぷ", "デスクトップ", 6, 12),
+                ("な", "な", "だ", 12, 13),
+                ("リナックス", "りなっくす", "リナックス", 13, 18),
+                ("を", "を", "を", 18, 19),
+                ("読ん", "よん", "読む", 19, 21),
+                ("だ", "だ", "だ", 21, 22),
+            ]
+        );
+        assert_eq!(
+            result.tokens[7].part_of_speech.as_deref(),
+            Some("動詞-自立")
+        );
+        assert_eq!(
+            result.text,
+            "さいきんにんきの\nですくとっぷなりなっくすをよんだ"
+        );
+        assert_eq!(result.lines.len(), 2);
+    }
+
+    #[test]
+    fn test_convert_with_built_unidic() {
+        // a UniDic entry (lemma and kana columns rather than IPADIC's base form and reading), built beforehand and
+        // then loaded as a lindera dictionary
+        let source =
+            std::env::temp_dir().join(format!("lenzu_test_mecab_unidic_{}", std::process::id()));
+        let built = std::env::temp_dir().join(format!(
+            "lenzu_test_mecab_unidic_built_{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&source).unwrap();
+        for file in ["char.def", "matrix.def", "unk.def"] {
+            fs::copy(
+                Path::new("../assets/fixtures/mecab_ipadic").join(file),
+                source.join(file),
+            )
+            .unwrap();
+        }
+        fs::write(
+            source.join("lex.csv"),
+            "読ん,1,1,100,動詞,一般,*,*,五段-マ行,連用形-撥音便,ヨム,読む,読ん,ヨン,読む,ヨム,和,*,*,*,*,*,*,用,ヨン,ヨム,ヨン,ヨム\n",
+        )
+        .unwrap();
+        MecabDictionary::build(&source, &built).unwrap();
+        assert!(built.join(LINDERA_METADATA).is_file());
+        let interpreter = InterpreterMecab::from_dir(&built).unwrap();
+        fs::remove_dir_all(&source).unwrap();
+        fs::remove_dir_all(&built).unwrap();
+
+        let result = interpreter.convert("読ん だ").unwrap();
+        assert_eq!(result.tokens[0].surface, "読ん");
+        assert_eq!(result.tokens[0].base_form, "読む");
+        assert_eq!(result.tokens[0].reading, "よん");
+        assert_eq!(
+            result.tokens[0].part_of_speech.as_deref(),
+            Some("動詞-一般")
+        );
+        assert_eq!((result.tokens[1].start, result.tokens[1].end), (3, 4));
+        assert_eq!(result.text, "よん だ");
+
+        // a source without a lexicon
+        assert!(MecabDictionary::build(Path::new("../assets/fixtures"), &built).is_err());
+    }
+}
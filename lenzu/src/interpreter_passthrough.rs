@@ -0,0 +1,32 @@
+// Passthrough interpreter: the text is returned as is, for the text that there is nothing to interpret (i.e.
+// English in a Japanese manga, see interpreter_router.rs)
+use crate::interpreter_traits::{InterpreterTrait, InterpreterTraitResult};
+use anyhow::Error;
+
+pub(crate) struct InterpreterPassthrough {}
+
+impl InterpreterTrait for InterpreterPassthrough {
+    fn new() -> Self
+    where
+        Self: Sized,
+    {
+        InterpreterPassthrough {}
+    }
+
+    fn name(&self) -> &str {
+        "passthrough"
+    }
+
+    fn init(&self) -> Vec<String> {
+        vec![]
+    }
+
+    fn convert(&self, text: &str) -> Result<InterpreterTraitResult, Error> {
+        Ok(InterpreterTraitResult {
+            text: text.to_string(),
+            lines: text.lines().map(|line| line.to_string()).collect(),
+            tokens: vec![],
+            translations: vec![],
+        })
+    }
+}
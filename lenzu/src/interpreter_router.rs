@@ -0,0 +1,244 @@
+// Routing interpreter: the language of the text is detected (see language_detect.rs) and the text is passed to the
+// interpreter registered for that language (i.e. Japanese to kakasi), anything else to the fallback (passthrough,
+// or a translator); lines that mix languages (i.e. "人気のLinux") are split into runs that are routed one by one
+use crate::interpreter_ja::InterpreterJa;
+use crate::interpreter_passthrough::InterpreterPassthrough;
+use crate::interpreter_traits::{InterpreterTrait, InterpreterTraitResult};
+use crate::kana::OutputScript;
+use crate::language_detect::{detect, segments, Language, Segment};
+use anyhow::Error;
+
+pub(crate) struct InterpreterRouter {
+    routes: Vec<(Language, Box<dyn InterpreterTrait>)>,
+    fallback: Box<dyn InterpreterTrait>,
+}
+
+impl InterpreterRouter {
+    pub fn new_with_routes(
+        routes: Vec<(Language, Box<dyn InterpreterTrait>)>,
+        fallback: Box<dyn InterpreterTrait>,
+    ) -> Self {
+        InterpreterRouter { routes, fallback }
+    }
+
+    // index into routes, None for the fallback
+    fn route_index(&self, language: Language) -> Option<usize> {
+        self.routes
+            .iter()
+            .position(|(route_language, _)| *route_language == language)
+    }
+
+    fn route(&self, route_index: Option<usize>) -> &dyn InterpreterTrait {
+        match route_index {
+            Some(index) => self.routes[index].1.as_ref(),
+            None => self.fallback.as_ref(),
+        }
+    }
+
+    // the interpreter the lens is about (the first route, Japanese unless --lang says otherwise), which decides the
+    // name/output script of the router
+    fn primary(&self) -> &dyn InterpreterTrait {
+        match self.routes.first() {
+            Some((_, interpreter)) => interpreter.as_ref(),
+            None => self.fallback.as_ref(),
+        }
+    }
+
+    // routes the text (or its runs) to the interpreters, and converts them
+    fn route_text(
+        &self,
+        text: &str,
+        language: Option<&str>,
+    ) -> Result<InterpreterTraitResult, Error> {
+        let text_language = detect(text, language);
+        let line_segments: Vec<(&str, Vec<Segment>)> = text
+            .split('\n')
+            .map(|line| (line, segments(line, text_language)))
+            .collect();
+        let route_indices: Vec<Option<usize>> = line_segments
+            .iter()
+            .flat_map(|(_, segments)| segments.iter())
+            .map(|segment| self.route_index(segment.language))
+            .collect();
+        if route_indices
+            .iter()
+            .all(|route_index| *route_index == route_indices[0])
+        {
+            // the whole text goes to one interpreter, as is (so that it sees the lines in context)
+            let route_index = route_indices
+                .first()
+                .cloned()
+                .unwrap_or(self.route_index(text_language));
+            return self.route(route_index).convert(text);
+        }
+
+        let mut lines: Vec<String> = vec![];
+        let mut tokens = vec![];
+        // the runs that are not translated are kept as they are in the translated lines
+        let mut translations: Vec<String> = vec![];
+        let mut is_translated = false;
+        let mut line_offset = 0;
+        for (line, segments) in line_segments {
+            let chars: Vec<char> = line.chars().collect();
+            let mut converted_line = String::new();
+            let mut translated_line = String::new();
+            for segment in segments {
+                let segment_text: String = chars[segment.start..segment.end].iter().collect();
+                let converted = self
+                    .route(self.route_index(segment.language))
+                    .convert(&segment_text)?;
+                converted_line.push_str(&converted.text.replace('\n', " "));
+                if converted.translations.is_empty() {
+                    translated_line.push_str(&segment_text);
+                } else {
+                    translated_line.push_str(&converted.translations.join(" "));
+                    is_translated = true;
+                }
+                tokens.extend(converted.tokens.into_iter().map(|mut token| {
+                    token.start += line_offset + segment.start;
+                    token.end += line_offset + segment.start;
+                    token
+                }));
+            }
+            lines.push(converted_line);
+            translations.push(translated_line);
+            line_offset += chars.len() + 1;
+        }
+        Ok(InterpreterTraitResult {
+            text: lines.join("\n"),
+            lines,
+            tokens,
+            translations: if is_translated { translations } else { vec![] },
+        })
+    }
+}
+
+impl InterpreterTrait for InterpreterRouter {
+    fn new() -> Self
+    where
+        Self: Sized,
+    {
+        InterpreterRouter::new_with_routes(
+            vec![(Language::Japanese, Box::new(InterpreterJa::new()))],
+            Box::new(InterpreterPassthrough::new()),
+        )
+    }
+
+    fn name(&self) -> &str {
+        self.primary().name()
+    }
+
+    fn init(&self) -> Vec<String> {
+        let mut languages: Vec<String> = vec![];
+        for language in self
+            .routes
+            .iter()
+            .flat_map(|(_, interpreter)| interpreter.init())
+            .chain(self.fallback.init())
+        {
+            if !languages.contains(&language) {
+                languages.push(language);
+            }
+        }
+        languages
+    }
+
+    fn convert(&self, text: &str) -> Result<InterpreterTraitResult, Error> {
+        self.convert_with_language(text, None)
+    }
+
+    fn convert_with_language(
+        &self,
+        text: &str,
+        language: Option<&str>,
+    ) -> Result<InterpreterTraitResult, Error> {
+        self.route_text(text, language)
+    }
+
+    fn output_script(&self) -> OutputScript {
+        self.primary().output_script()
+    }
+
+    fn set_output_script(&mut self, output_script: OutputScript) -> bool {
+        match self.routes.first_mut() {
+            Some((_, interpreter)) => interpreter.set_output_script(output_script),
+            None => self.fallback.set_output_script(output_script),
+        }
+    }
+
+    fn translates(&self) -> bool {
+        self.primary().translates()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter_traits::InterpreterToken;
+
+    // stands in for kakasi, so that we can tell which runs of the text it got
+    struct InterpreterBrackets {}
+
+    impl InterpreterTrait for InterpreterBrackets {
+        fn new() -> Self {
+            InterpreterBrackets {}
+        }
+
+        fn name(&self) -> &str {
+            "brackets"
+        }
+
+        fn init(&self) -> Vec<String> {
+            vec!["ja".to_string()]
+        }
+
+        fn convert(&self, text: &str) -> Result<InterpreterTraitResult, Error> {
+            let text = text.replace('\n', "][");
+            Ok(InterpreterTraitResult {
+                text: format!("[{}]", text),
+                lines: vec![format!("[{}]", text)],
+                tokens: vec![InterpreterToken {
+                    surface: text.clone(),
+                    reading: text.clone(),
+                    base_form: text.clone(),
+                    part_of_speech: None,
+                    start: 0,
+                    end: text.chars().count(),
+                }],
+                translations: vec![],
+            })
+        }
+    }
+
+    #[test]
+    fn test_route_by_language() {
+        let router = InterpreterRouter::new_with_routes(
+            vec![(Language::Japanese, Box::new(InterpreterBrackets::new()))],
+            Box::new(InterpreterPassthrough::new()),
+        );
+        assert_eq!(router.name(), "brackets");
+        assert_eq!(router.init(), ["ja"]);
+
+        assert_eq!(
+            router.convert("人気の本\n読んだ").unwrap().text,
+            "[人気の本][読んだ]"
+        );
+        assert_eq!(router.convert("Hello\nworld").unwrap().text, "Hello\nworld");
+        assert_eq!(
+            router
+                .convert_with_language("人气很高", Some("zh-Hans"))
+                .unwrap()
+                .text,
+            "人气很高"
+        );
+
+        let result = router.convert("人気のLinux\nOK、本").unwrap();
+        assert_eq!(result.lines, ["[人気の]Linux", "OK、[本]"]);
+        let offsets: Vec<(&str, usize, usize)> = result
+            .tokens
+            .iter()
+            .map(|token| (token.surface.as_str(), token.start, token.end))
+            .collect();
+        assert_eq!(offsets, [("人気の", 0, 3), ("本", 12, 13)]);
+    }
+}
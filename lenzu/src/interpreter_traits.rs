@@ -1,3 +1,4 @@
+use crate::kana::OutputScript;
 use anyhow::Error; // the most easiest way to handle errors
 use core::result::Result;
 use std::fmt::{self, Display, Formatter};
@@ -9,18 +10,65 @@ pub trait InterpreterTrait {
     where
         Self: Sized;
 
+    // short (stable) name of the backend (i.e. "kakasi"), which is recorded in the results (see result_schema.rs)
+    fn name(&self) -> &str;
+
     // returns array of Strings of supported languages
     fn init(&self) -> Vec<String>;
 
     // interpretes/translates the text to locale native  language
     // i.e. Japanese to English, English to Japanese, etc
     fn convert(&self, text: &str) -> Result<InterpreterTraitResult, Error>;
+
+    // same as convert(), with the language that the OCR backend reported (i.e. "ja", "jpn_vert"), if it did; only
+    // the interpreters that route by language (see interpreter_router.rs) care
+    fn convert_with_language(
+        &self,
+        text: &str,
+        _language: Option<&str>,
+    ) -> Result<InterpreterTraitResult, Error> {
+        self.convert(text)
+    }
+
+    // script of the readings in the text/lines of the result (the readings of the tokens stay in hiragana), see
+    // kana::OutputScript; interpreters that only output hiragana keep the defaults
+    fn output_script(&self) -> OutputScript {
+        OutputScript::Hiragana
+    }
+
+    // returns false if the interpreter does not support the script
+    fn set_output_script(&mut self, _output_script: OutputScript) -> bool {
+        false
+    }
+
+    // true if the lines of the result are translations of the lines of the text (rather than readings of it), so
+    // there are no furigana (see InterpreterTraitResult::translations for what is drawn instead)
+    fn translates(&self) -> bool {
+        false
+    }
+}
+
+// A token is a word (or, if the interpreter cannot tell words apart, a run of the same script) of the text that
+// was passed to convert(); start/end are char (not byte) offsets into that text, so that the tokens can be mapped
+// back to the OCR words (and their rects)
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct InterpreterToken {
+    pub surface: String,                // as it appears in the text (i.e. "読んだ")
+    pub reading: String, // in hiragana (i.e. "よんだ"), same as surface if there is nothing to read
+    pub base_form: String, // dictionary form (i.e. "読む"), same as surface if the interpreter does not know
+    pub part_of_speech: Option<String>, // i.e. "noun", "verb", None if the interpreter does not know
+    pub start: usize,
+    pub end: usize,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub(crate) struct InterpreterTraitResult {
     pub text: String,
     pub lines: Vec<String>,
+    pub tokens: Vec<InterpreterToken>, // in the order of the text, empty if the interpreter does not tokenize
+    // translations of the lines of the text (a line per line), which are drawn over the OCR lines; empty unless
+    // the interpreter (or one of a chain, see interpreter_chain.rs) translates
+    pub translations: Vec<String>,
 }
 impl InterpreterTraitResult {
     pub(crate) fn new() -> InterpreterTraitResult {
@@ -28,6 +76,8 @@ impl InterpreterTraitResult {
         InterpreterTraitResult {
             text: "".to_string(),
             lines: vec![],
+            tokens: vec![],
+            translations: vec![],
         }
     }
 }
@@ -0,0 +1,458 @@
+// Translation interpreter: the lines of the OCR text are translated into the native language of the user (one
+// translated line per line of the text) by one of the backends:
+//  - libretranslate: a local LibreTranslate (argos-translate) server, see https://libretranslate.com/docs
+//  - openai: an OpenAI compatible chat-completions endpoint, i.e. a local LLM served by llama.cpp, ollama, vLLM...
+//  - gloss: offline, the words of the line are looked up in the local dictionary store (see dictionary.rs) and
+//    their first glosses are listed, which is not a translation, but it is better than nothing
+// if the server cannot be reached (or fails) and the dictionary store is available, the lines are glossed instead
+// NOTE: the endpoints default to localhost (we do not want the text of the screen to leave the machine unless the
+// user explicitly points LENZU_TRANSLATE_ENDPOINT elsewhere, see README on privacy)
+use crate::dictionary::{Dictionary, DEFAULT_DICTIONARY_STORE, ENV_DICTIONARY};
+use crate::interpreter_traits::{InterpreterTrait, InterpreterTraitResult};
+use anyhow::Error;
+use std::path::Path;
+use std::sync::Arc;
+
+const ENV_TRANSLATOR: &str = "LENZU_TRANSLATOR";
+const ENV_TRANSLATE_ENDPOINT: &str = "LENZU_TRANSLATE_ENDPOINT";
+const ENV_TRANSLATE_API_KEY: &str = "LENZU_TRANSLATE_API_KEY";
+const ENV_TRANSLATE_MODEL: &str = "LENZU_TRANSLATE_MODEL";
+const ENV_TRANSLATE_SOURCE: &str = "LENZU_TRANSLATE_SOURCE";
+const ENV_TRANSLATE_TARGET: &str = "LENZU_TRANSLATE_TARGET";
+const DEFAULT_LIBRETRANSLATE_ENDPOINT: &str = "http://localhost:5000/translate";
+const DEFAULT_OPENAI_ENDPOINT: &str = "http://localhost:8080/v1/chat/completions";
+const DEFAULT_OPENAI_MODEL: &str = "default"; // llama.cpp and most single-model servers ignore it
+const DEFAULT_SOURCE_LANGUAGE: &str = "ja";
+const DEFAULT_TARGET_LANGUAGE: &str = "en";
+const REQUEST_TIMEOUT_IN_SECONDS: u64 = 60; // a local LLM on CPU can be slow
+const MAX_GLOSSES: usize = 2; // per word, the translated line has to fit into the rect of the OCR line
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum TranslateBackend {
+    LibreTranslate,
+    OpenAi,
+    Gloss,
+}
+
+impl TranslateBackend {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TranslateBackend::LibreTranslate => "libretranslate",
+            TranslateBackend::OpenAi => "openai",
+            TranslateBackend::Gloss => "gloss",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<TranslateBackend> {
+        [
+            TranslateBackend::LibreTranslate,
+            TranslateBackend::OpenAi,
+            TranslateBackend::Gloss,
+        ]
+        .into_iter()
+        .find(|backend| backend.name() == name)
+    }
+
+    fn default_endpoint(&self) -> &'static str {
+        match self {
+            TranslateBackend::LibreTranslate => DEFAULT_LIBRETRANSLATE_ENDPOINT,
+            TranslateBackend::OpenAi => DEFAULT_OPENAI_ENDPOINT,
+            TranslateBackend::Gloss => "",
+        }
+    }
+}
+
+// JMdict languages are ISO 639-2/B (i.e. "ger"), the translation servers want ISO 639-1 (i.e. "de"); English is
+// the last resort since every JMdict entry has English glosses
+fn gloss_languages(target_language: &str) -> Vec<String> {
+    let language = match target_language {
+        "de" => "ger",
+        "es" => "spa",
+        "fr" => "fre",
+        "hu" => "hun",
+        "nl" => "dut",
+        "ru" => "rus",
+        "sl" => "slv",
+        "sv" => "swe",
+        _ => "eng",
+    };
+    let mut languages = vec![language.to_string()];
+    if language != "eng" {
+        languages.push("eng".to_string());
+    }
+    languages
+}
+
+pub(crate) struct InterpreterTranslate {
+    backend: TranslateBackend,
+    endpoint: String,
+    api_key: Option<String>,
+    model: String,
+    source_language: String,
+    target_language: String,
+    dictionary: Option<Arc<Dictionary>>,
+}
+
+impl InterpreterTranslate {
+    // backend with the endpoint/key/model/languages of the environment variables (target_language overrides
+    // LENZU_TRANSLATE_TARGET), and the dictionary store (or else the one of LENZU_DICTIONARY) for glossing, if
+    // there is one
+    pub fn from_backend(
+        backend: TranslateBackend,
+        target_language: Option<&str>,
+        dictionary_store: Option<&Path>,
+    ) -> Self {
+        let path = dictionary_store.map(|path| path.to_path_buf()).unwrap_or(
+            std::env::var(ENV_DICTIONARY)
+                .unwrap_or(DEFAULT_DICTIONARY_STORE.to_string())
+                .into(),
+        );
+        let dictionary = if path.exists() {
+            Dictionary::load_shared(&path)
+                .map_err(|e| eprintln!("Error: {:?}", e))
+                .ok()
+        } else {
+            None
+        };
+        let mut interpreter = InterpreterTranslate::new_with_endpoint(
+            backend,
+            &std::env::var(ENV_TRANSLATE_ENDPOINT)
+                .unwrap_or(backend.default_endpoint().to_string()),
+            std::env::var(ENV_TRANSLATE_API_KEY).ok(),
+            dictionary,
+        );
+        if let Ok(model) = std::env::var(ENV_TRANSLATE_MODEL) {
+            interpreter.model = model;
+        }
+        if let Ok(source_language) = std::env::var(ENV_TRANSLATE_SOURCE) {
+            interpreter.source_language = source_language;
+        }
+        if let Some(target_language) = target_language
+            .map(|language| language.to_string())
+            .or(std::env::var(ENV_TRANSLATE_TARGET).ok())
+        {
+            interpreter.target_language = target_language;
+        }
+        interpreter
+    }
+
+    pub fn new_with_endpoint(
+        backend: TranslateBackend,
+        endpoint: &str,
+        api_key: Option<String>,
+        dictionary: Option<Arc<Dictionary>>,
+    ) -> Self {
+        InterpreterTranslate {
+            backend,
+            endpoint: endpoint.to_string(),
+            api_key,
+            model: DEFAULT_OPENAI_MODEL.to_string(),
+            source_language: DEFAULT_SOURCE_LANGUAGE.to_string(),
+            target_language: DEFAULT_TARGET_LANGUAGE.to_string(),
+            dictionary,
+        }
+    }
+
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    // i.e. "auto" (LibreTranslate detects the language), for translating the text that is not Japanese
+    pub fn set_source_language(&mut self, source_language: &str) {
+        self.source_language = source_language.to_string();
+    }
+
+    // the words of the line that are in the dictionary, i.e. "popularity / book / to read"
+    fn gloss(&self, line: &str) -> Result<String, Error> {
+        let dictionary = self.dictionary.as_ref().ok_or(anyhow::anyhow!(
+            "Dictionary is not loaded (see --dictionary or {})",
+            ENV_DICTIONARY
+        ))?;
+        let languages = gloss_languages(&self.target_language);
+        Ok(dictionary
+            .lookup_text(line)
+            .iter()
+            .filter_map(|dictionary_match| {
+                let glosses = dictionary_match.entries[0].entry.glosses(&languages);
+                if glosses.is_empty() {
+                    None
+                } else {
+                    Some(
+                        glosses
+                            .into_iter()
+                            .take(MAX_GLOSSES)
+                            .collect::<Vec<&str>>()
+                            .join(", "),
+                    )
+                }
+            })
+            .collect::<Vec<String>>()
+            .join(" / "))
+    }
+
+    fn post(&self, request_body: &serde_json::Value) -> Result<serde_json::Value, Error> {
+        let client = reqwest::blocking::Client::builder()
+            .timeout(std::time::Duration::from_secs(REQUEST_TIMEOUT_IN_SECONDS))
+            .build()?;
+        let mut request = client.post(self.endpoint.as_str()).json(request_body);
+        if let (TranslateBackend::OpenAi, Some(api_key)) = (self.backend, &self.api_key) {
+            request = request.bearer_auth(api_key);
+        }
+        let response = request.send()?;
+        let status = response.status();
+        let body = response.text()?;
+        if !status.is_success() {
+            return Err(anyhow::anyhow!(
+                "{} returned HTTP {}: {}",
+                self.backend.name(),
+                status,
+                body
+            ));
+        }
+        Ok(serde_json::from_str(&body)?)
+    }
+
+    // LibreTranslate translates an array of texts in one request, and answers with an array of the same length
+    fn translate_libretranslate(&self, lines: &[&str]) -> Result<Vec<String>, Error> {
+        let mut request_body = serde_json::json!({
+            "q": lines,
+            "source": self.source_language,
+            "target": self.target_language,
+            "format": "text",
+        });
+        if let Some(api_key) = &self.api_key {
+            request_body["api_key"] = serde_json::json!(api_key);
+        }
+        let response = self.post(&request_body)?;
+        match response["translatedText"].as_array() {
+            Some(translated) if translated.len() == lines.len() => Ok(translated
+                .iter()
+                .map(|text| text.as_str().unwrap_or_default().to_string())
+                .collect()),
+            _ => Err(anyhow::anyhow!(
+                "libretranslate returned an unexpected response: {}",
+                response
+            )),
+        }
+    }
+
+    // the lines are numbered so that we can tell which translation belongs to which line (LLMs like to merge and
+    // split lines); lines that the model did not answer stay empty
+    fn translate_openai(&self, lines: &[&str]) -> Result<Vec<String>, Error> {
+        let numbered_lines: Vec<String> = lines
+            .iter()
+            .enumerate()
+            .map(|(index, line)| format!("{}. {}", index + 1, line))
+            .collect();
+        let request_body = serde_json::json!({
+            "model": self.model,
+            "temperature": 0,
+            "messages": [
+                {
+                    "role": "system",
+                    "content": format!(
+                        "Translate each numbered line from language '{}' to language '{}'. Answer with the translated lines only, keeping the numbering.",
+                        self.source_language, self.target_language
+                    ),
+                },
+                { "role": "user", "content": numbered_lines.join("\n") },
+            ],
+        });
+        let response = self.post(&request_body)?;
+        let content = response["choices"][0]["message"]["content"]
+            .as_str()
+            .ok_or(anyhow::anyhow!(
+                "openai returned an unexpected response: {}",
+                response
+            ))?;
+        let mut translated = vec![String::new(); lines.len()];
+        for answer in content.lines() {
+            let numbered = answer
+                .trim()
+                .split_once(['.', ')', ':'])
+                .and_then(|(number, text)| {
+                    number
+                        .trim()
+                        .parse::<usize>()
+                        .ok()
+                        .map(|number| (number, text.trim()))
+                });
+            if let Some((number, text)) = numbered {
+                if (1..=lines.len()).contains(&number) {
+                    translated[number - 1] = text.to_string();
+                }
+            }
+        }
+        Ok(translated)
+    }
+
+    fn translate(&self, lines: &[&str]) -> Result<Vec<String>, Error> {
+        match self.backend {
+            TranslateBackend::LibreTranslate => self.translate_libretranslate(lines),
+            TranslateBackend::OpenAi => self.translate_openai(lines),
+            TranslateBackend::Gloss => lines.iter().map(|line| self.gloss(line)).collect(),
+        }
+    }
+}
+
+impl InterpreterTrait for InterpreterTranslate {
+    fn new() -> Self
+    where
+        Self: Sized,
+    {
+        let backend = std::env::var(ENV_TRANSLATOR)
+            .ok()
+            .and_then(|name| TranslateBackend::parse(&name))
+            .unwrap_or(TranslateBackend::LibreTranslate);
+        InterpreterTranslate::from_backend(backend, None, None)
+    }
+
+    fn name(&self) -> &str {
+        self.backend.name()
+    }
+
+    fn init(&self) -> Vec<String> {
+        if self.backend != TranslateBackend::Gloss {
+            eprintln!(
+                "Translate - {} endpoint: {} ({} -> {})",
+                self.backend.name(),
+                self.endpoint,
+                self.source_language,
+                self.target_language
+            );
+        }
+        if self.dictionary.is_none() {
+            eprintln!(
+                "Translate - Warning: no dictionary, there is no offline fallback (see `lenzu dictionary import` and {})",
+                ENV_DICTIONARY
+            );
+        }
+        vec![self.source_language.clone()]
+    }
+
+    fn convert(&self, text: &str) -> Result<InterpreterTraitResult, Error> {
+        // empty lines are not sent, but are kept so that the lines of the result match the lines of the text
+        let text_lines: Vec<&str> = text.split('\n').collect();
+        let non_empty_lines: Vec<&str> = text_lines
+            .iter()
+            .filter(|line| !line.trim().is_empty())
+            .cloned()
+            .collect();
+        if non_empty_lines.is_empty() {
+            let lines: Vec<String> = text_lines.iter().map(|line| line.to_string()).collect();
+            return Ok(InterpreterTraitResult {
+                text: text.to_string(),
+                lines: lines.clone(),
+                tokens: vec![],
+                translations: lines,
+            });
+        }
+
+        // reqwest::blocking will panic if it is created/dropped within an async (tokio) context (see ocr_gcloud.rs)
+        let translated = std::thread::scope(|scope| {
+            scope
+                .spawn(|| self.translate(&non_empty_lines))
+                .join()
+                .unwrap_or_else(|_| Err(anyhow::anyhow!("Translate request thread panicked")))
+        });
+        let mut translated = match translated {
+            Ok(translated) => translated,
+            Err(e) if self.backend != TranslateBackend::Gloss && self.dictionary.is_some() => {
+                eprintln!(
+                    "Translate - Error: {:?} - falling back to dictionary glosses",
+                    e
+                );
+                non_empty_lines
+                    .iter()
+                    .map(|line| self.gloss(line))
+                    .collect::<Result<Vec<String>, Error>>()?
+            }
+            Err(e) => return Err(e),
+        }
+        .into_iter();
+        let lines: Vec<String> = text_lines
+            .iter()
+            .map(|line| {
+                if line.trim().is_empty() {
+                    String::new()
+                } else {
+                    translated.next().unwrap_or_default()
+                }
+            })
+            .collect();
+        Ok(InterpreterTraitResult {
+            text: lines.join("\n"),
+            lines: lines.clone(),
+            tokens: vec![],
+            translations: lines,
+        })
+    }
+
+    fn translates(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::serve_once;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_convert_with_translate_backends() {
+        let (endpoint, handle) = serve_once(
+            "/translate",
+            "HTTP/1.1 200 OK",
+            r#"{"translatedText":["Popular book","I read it"]}"#.to_string(),
+        );
+        let interpreter = InterpreterTranslate::new_with_endpoint(
+            TranslateBackend::LibreTranslate,
+            &endpoint,
+            None,
+            None,
+        );
+        // as many lines as the text, including the empty one after its last newline (which lines() would drop)
+        let result = interpreter.convert("人気の本\n\n読んだ\n").unwrap();
+        assert_eq!(result.lines, ["Popular book", "", "I read it", ""]);
+        let request: serde_json::Value = serde_json::from_str(&handle.join().unwrap().1).unwrap();
+        assert_eq!(request["q"], serde_json::json!(["人気の本", "読んだ"]));
+        assert_eq!(request["target"], "en");
+
+        let (endpoint, handle) = serve_once(
+            "/v1/chat/completions",
+            "HTTP/1.1 200 OK",
+            serde_json::json!({
+                "choices": [{ "message": { "role": "assistant", "content": "1. A popular book\n2) I read it." } }]
+            })
+            .to_string(),
+        );
+        let interpreter = InterpreterTranslate::new_with_endpoint(
+            TranslateBackend::OpenAi,
+            &endpoint,
+            None,
+            None,
+        );
+        let result = interpreter.convert("人気の本\n読んだ").unwrap();
+        assert_eq!(result.lines, ["A popular book", "I read it."]);
+        let request: serde_json::Value = serde_json::from_str(&handle.join().unwrap().1).unwrap();
+        assert_eq!(request["messages"][1]["content"], "1. 人気の本\n2. 読んだ");
+
+        // nothing listens on the port of a dropped listener, so the request fails and the lines are glossed
+        let endpoint = {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            format!("http://{}/translate", listener.local_addr().unwrap())
+        };
+        let dictionary =
+            Dictionary::import(&[PathBuf::from("../assets/fixtures/jmdict_mini.xml")]).unwrap();
+        let mut interpreter = InterpreterTranslate::new_with_endpoint(
+            TranslateBackend::LibreTranslate,
+            &endpoint,
+            None,
+            Some(Arc::new(dictionary)),
+        );
+        interpreter.target_language = "de".to_string();
+        let result = interpreter.convert("人気の本を読んだ").unwrap();
+        assert_eq!(result.lines, ["popularity, public favor / Buch / lesen"]);
+    }
+}
@@ -0,0 +1,425 @@
+use std::ops::Range;
+
+// Japanese script helpers shared by the interpreters (readings of the tokens) and the overlay (furigana)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Script {
+    Kanji,
+    Hiragana,
+    Katakana, // including the long-vowel mark (ー)
+    Other,    // latin, digits, punctuation, etc.
+}
+
+pub(crate) fn script_of(c: char) -> Script {
+    match c {
+        '\u{4E00}'..='\u{9FFF}'
+        | '\u{3400}'..='\u{4DBF}'
+        | '\u{F900}'..='\u{FAFF}'
+        | '々'
+        | '〆' => Script::Kanji,
+        '\u{3041}'..='\u{309F}' => Script::Hiragana,
+        '\u{30A0}'..='\u{30FF}' | '\u{31F0}'..='\u{31FF}' | '\u{FF66}'..='\u{FF9F}' => {
+            Script::Katakana
+        }
+        _ => Script::Other,
+    }
+}
+
+pub(crate) fn is_kanji(c: char) -> bool {
+    script_of(c) == Script::Kanji
+}
+
+// katakana to hiragana, so that "デスクトップ" matches a reading that the interpreter rendered in hiragana
+pub(crate) fn to_hiragana(c: char) -> char {
+    match c {
+        '\u{30A1}'..='\u{30F6}' => char::from_u32(c as u32 - 0x60).unwrap_or(c),
+        _ => c,
+    }
+}
+
+// hiragana to katakana, for the katakana output script
+pub(crate) fn to_katakana(c: char) -> char {
+    match c {
+        '\u{3041}'..='\u{3096}' => char::from_u32(c as u32 + 0x60).unwrap_or(c),
+        _ => c,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RomajiSystem {
+    Hepburn,    // shi, chi, tsu, fu, ji, sha (as on signs and in textbooks)
+    Kunrei,     // si, ti, tu, hu, zi, sya (ISO 3602)
+    NihonShiki, // Kunrei plus di, du, wo, dya (one to one with the kana)
+}
+
+// Script of the readings that the interpreters output (the text/lines of InterpreterTraitResult, and the furigana),
+// chosen via --output-script <name>, or cycled with a hotkey in the lens window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum OutputScript {
+    Hiragana,
+    Katakana,
+    Romaji(RomajiSystem),
+    KanjiReading, // the text as-is, with the reading after each kanji run, i.e. "最近[さいきん]"
+}
+
+impl OutputScript {
+    pub const ALL: [OutputScript; 6] = [
+        OutputScript::Hiragana,
+        OutputScript::Katakana,
+        OutputScript::Romaji(RomajiSystem::Hepburn),
+        OutputScript::Romaji(RomajiSystem::Kunrei),
+        OutputScript::Romaji(RomajiSystem::NihonShiki),
+        OutputScript::KanjiReading,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            OutputScript::Hiragana => "hiragana",
+            OutputScript::Katakana => "katakana",
+            OutputScript::Romaji(RomajiSystem::Hepburn) => "hepburn",
+            OutputScript::Romaji(RomajiSystem::Kunrei) => "kunrei",
+            OutputScript::Romaji(RomajiSystem::NihonShiki) => "nihon-shiki",
+            OutputScript::KanjiReading => "kanji-reading",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .find(|output_script| output_script.name() == name)
+            .copied()
+    }
+
+    // the one after this, for the hotkey
+    #[cfg(any(target_os = "windows", test))]
+    pub fn next(&self) -> Self {
+        let index = Self::ALL
+            .iter()
+            .position(|output_script| output_script == self)
+            .unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    // a reading (in hiragana) in this script; kanji-reading keeps the reading in hiragana
+    pub fn convert_kana(&self, hiragana: &str) -> String {
+        match self {
+            OutputScript::Hiragana | OutputScript::KanjiReading => hiragana.to_string(),
+            OutputScript::Katakana => hiragana.chars().map(to_katakana).collect(),
+            OutputScript::Romaji(system) => to_romaji(hiragana, *system),
+        }
+    }
+}
+
+// romaji of a single kana (hiragana), which only differs between the systems for a handful of them
+fn kana_romaji(c: char, system: RomajiSystem) -> Option<&'static str> {
+    let (hepburn, kunrei, nihon_shiki) = match c {
+        'し' => ("shi", "si", "si"),
+        'ち' => ("chi", "ti", "ti"),
+        'つ' => ("tsu", "tu", "tu"),
+        'ふ' => ("fu", "hu", "hu"),
+        'じ' => ("ji", "zi", "zi"),
+        'ぢ' => ("ji", "zi", "di"),
+        'づ' => ("zu", "zu", "du"),
+        'ゐ' => ("i", "i", "wi"),
+        'ゑ' => ("e", "e", "we"),
+        'を' => ("o", "o", "wo"),
+        _ => {
+            let romaji = match c {
+                'あ' | 'ぁ' => "a",
+                'い' | 'ぃ' => "i",
+                'う' | 'ぅ' => "u",
+                'え' | 'ぇ' => "e",
+                'お' | 'ぉ' => "o",
+                'か' => "ka",
+                'き' => "ki",
+                'く' => "ku",
+                'け' => "ke",
+                'こ' => "ko",
+                'が' => "ga",
+                'ぎ' => "gi",
+                'ぐ' => "gu",
+                'げ' => "ge",
+                'ご' => "go",
+                'さ' => "sa",
+                'す' => "su",
+                'せ' => "se",
+                'そ' => "so",
+                'ざ' => "za",
+                'ず' => "zu",
+                'ぜ' => "ze",
+                'ぞ' => "zo",
+                'た' => "ta",
+                'て' => "te",
+                'と' => "to",
+                'だ' => "da",
+                'で' => "de",
+                'ど' => "do",
+                'な' => "na",
+                'に' => "ni",
+                'ぬ' => "nu",
+                'ね' => "ne",
+                'の' => "no",
+                'は' => "ha",
+                'ひ' => "hi",
+                'へ' => "he",
+                'ほ' => "ho",
+                'ば' => "ba",
+                'び' => "bi",
+                'ぶ' => "bu",
+                'べ' => "be",
+                'ぼ' => "bo",
+                'ぱ' => "pa",
+                'ぴ' => "pi",
+                'ぷ' => "pu",
+                'ぺ' => "pe",
+                'ぽ' => "po",
+                'ま' => "ma",
+                'み' => "mi",
+                'む' => "mu",
+                'め' => "me",
+                'も' => "mo",
+                'や' | 'ゃ' => "ya",
+                'ゆ' | 'ゅ' => "yu",
+                'よ' | 'ょ' => "yo",
+                'ら' => "ra",
+                'り' => "ri",
+                'る' => "ru",
+                'れ' => "re",
+                'ろ' => "ro",
+                'わ' | 'ゎ' => "wa",
+                'ん' => "n",
+                'ゔ' => "vu",
+                _ => return None,
+            };
+            (romaji, romaji, romaji)
+        }
+    };
+    Some(match system {
+        RomajiSystem::Hepburn => hepburn,
+        RomajiSystem::Kunrei => kunrei,
+        RomajiSystem::NihonShiki => nihon_shiki,
+    })
+}
+
+// kana (hiragana or katakana) to romaji, anything else is kept as-is; long vowels are spelled out (i.e. "toukyou",
+// "kōhī" would need the words), and は/へ as particles are "ha"/"he" since we do not know the words either
+pub(crate) fn to_romaji(text: &str, system: RomajiSystem) -> String {
+    let chars: Vec<char> = text.chars().map(to_hiragana).collect();
+    let mut romaji = String::new();
+    let mut sokuon = false; // っ, doubles the consonant that follows
+    let mut after_n = false; // ん, which is "n'" before a vowel or y (i.e. "kin'en" versus "kinen")
+    let mut index = 0;
+    while index < chars.len() {
+        let c = chars[index];
+        let next = chars.get(index + 1).copied();
+        let (syllable, length) = match (kana_romaji(c, system), next) {
+            // palatalized (i.e. きゃ "kya", しゃ "sha"/"sya")
+            (Some(base), Some(small @ ('ゃ' | 'ゅ' | 'ょ')))
+                if base.len() > 1 && base.ends_with('i') =>
+            {
+                let consonant = &base[..base.len() - 1];
+                let vowel = &kana_romaji(small, system).unwrap_or_default()[1..];
+                let is_hepburn_palatal = system == RomajiSystem::Hepburn
+                    && (consonant.ends_with("sh") || consonant.ends_with("ch") || consonant == "j");
+                if is_hepburn_palatal {
+                    (format!("{}{}", consonant, vowel), 2)
+                } else {
+                    (format!("{}y{}", consonant, vowel), 2)
+                }
+            }
+            // small vowels of loanwords (i.e. ファ "fa", ティ "ti", ウィ "wi")
+            (Some(base), Some(small @ ('ぁ' | 'ぃ' | 'ぅ' | 'ぇ' | 'ぉ')))
+                if base.len() > 1 || c == 'う' =>
+            {
+                let consonant = if c == 'う' {
+                    "w"
+                } else {
+                    &base[..base.len() - 1]
+                };
+                let vowel = kana_romaji(small, system).unwrap_or_default();
+                (format!("{}{}", consonant, vowel), 2)
+            }
+            (Some(base), _) => (base.to_string(), 1),
+            (None, _) if c == 'っ' => {
+                sokuon = true;
+                index += 1;
+                continue;
+            }
+            (None, _) if c == 'ー' => {
+                let vowel = romaji.chars().rev().find(|c| "aiueo".contains(*c));
+                (
+                    vowel
+                        .map(|vowel| vowel.to_string())
+                        .unwrap_or("-".to_string()),
+                    1,
+                )
+            }
+            (None, _) => (c.to_string(), 1),
+        };
+        if after_n && syllable.starts_with(['a', 'i', 'u', 'e', 'o', 'y']) {
+            romaji.push('\'');
+        }
+        if sokuon {
+            match syllable.chars().next() {
+                Some(_) if system == RomajiSystem::Hepburn && syllable.starts_with("ch") => {
+                    romaji.push('t')
+                }
+                Some(consonant)
+                    if consonant.is_ascii_alphabetic() && !"aiueon".contains(consonant) =>
+                {
+                    romaji.push(consonant)
+                }
+                _ => (),
+            }
+            sokuon = false;
+        }
+        after_n = c == 'ん';
+        romaji.push_str(&syllable);
+        index += length;
+    }
+    romaji
+}
+
+// The interpreters give us the reading of a whole text (i.e. "最近人気の" -> "さいきんにんきの"), so to find the
+// reading of each kanji run, we align the two: the kana (and anything else that is not kanji) of the text must
+// appear as-is in the reading, hence they act as anchors and each kanji run gets the part of the reading in
+// between them.  Returns the char ranges (of the surface) of each kanji run along with its reading, or None if the
+// reading does not fit the surface (i.e. the interpreter changed the kana, or left kanji as-is)
+pub(crate) fn align_reading(surface: &str, reading: &str) -> Option<Vec<(Range<usize>, String)>> {
+    let surface: Vec<char> = surface.chars().collect();
+    let reading: Vec<char> = reading
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(to_hiragana)
+        .collect();
+
+    // split into kanji runs, anchors, and whitespace (which the reading does not have to keep)
+    #[derive(PartialEq)]
+    enum Segment {
+        Kanji,
+        Anchor,
+        Whitespace,
+    }
+    let segment_of = |c: char| {
+        if c.is_whitespace() {
+            Segment::Whitespace
+        } else if is_kanji(c) {
+            Segment::Kanji
+        } else {
+            Segment::Anchor
+        }
+    };
+    let mut segments: Vec<(Range<usize>, Segment)> = vec![];
+    for (index, c) in surface.iter().enumerate() {
+        match segments.last_mut() {
+            Some((range, segment)) if *segment == segment_of(*c) => range.end = index + 1,
+            _ => segments.push((index..index + 1, segment_of(*c))),
+        }
+    }
+
+    fn align(
+        segments: &[(Range<usize>, Segment)],
+        surface: &[char],
+        reading: &[char],
+        rubies: &mut Vec<(Range<usize>, String)>,
+    ) -> bool {
+        let Some(((range, segment), rest)) = segments.split_first() else {
+            return reading.is_empty();
+        };
+        match segment {
+            Segment::Whitespace => align(rest, surface, reading, rubies),
+            Segment::Anchor => {
+                let kana: Vec<char> = surface[range.clone()]
+                    .iter()
+                    .map(|c| to_hiragana(*c))
+                    .collect();
+                reading.starts_with(&kana) && align(rest, surface, &reading[kana.len()..], rubies)
+            }
+            Segment::Kanji => {
+                // the anchors that follow must still fit, so try the shortest reading first and backtrack
+                let min_rest: usize = rest
+                    .iter()
+                    .map(|(range, segment)| match segment {
+                        Segment::Kanji => 1,
+                        Segment::Anchor => range.len(),
+                        Segment::Whitespace => 0,
+                    })
+                    .sum();
+                let max_len = reading.len().saturating_sub(min_rest);
+                let min_len = if rest
+                    .iter()
+                    .any(|(_, segment)| *segment != Segment::Whitespace)
+                {
+                    1
+                } else {
+                    max_len
+                };
+                for len in min_len.max(1)..=max_len {
+                    rubies.push((range.clone(), reading[..len].iter().collect()));
+                    if align(rest, surface, &reading[len..], rubies) {
+                        return true;
+                    }
+                    rubies.pop();
+                }
+                false
+            }
+        }
+    }
+
+    let mut rubies = vec![];
+    if align(&segments, &surface, &reading, &mut rubies) {
+        Some(rubies)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_align_reading() {
+        assert_eq!(script_of('読'), Script::Kanji);
+        assert_eq!(script_of('ー'), Script::Katakana);
+        assert_eq!(to_hiragana('デ'), 'で');
+        assert_eq!(
+            align_reading("最近人気の", "さいきんにんきの"),
+            Some(vec![(0..4, "さいきんにんき".to_string())])
+        );
+        assert_eq!(
+            align_reading("日本語を読む\nデスクトップ", "にほんごをよむ\nですくとっぷ"),
+            Some(vec![
+                (0..3, "にほんご".to_string()),
+                (4..5, "よ".to_string())
+            ])
+        );
+        assert_eq!(align_reading("人気の", "にんきが"), None);
+    }
+
+    #[test]
+    fn test_output_scripts() {
+        let hepburn = OutputScript::Romaji(RomajiSystem::Hepburn);
+        let kunrei = OutputScript::Romaji(RomajiSystem::Kunrei);
+        let nihon_shiki = OutputScript::Romaji(RomajiSystem::NihonShiki);
+        assert_eq!(hepburn.convert_kana("しんぶんをよんだ"), "shinbun'oyonda");
+        assert_eq!(kunrei.convert_kana("しんぶんをよんだ"), "sinbun'oyonda");
+        assert_eq!(
+            nihon_shiki.convert_kana("しんぶんをよんだ"),
+            "sinbunwoyonda"
+        );
+        assert_eq!(hepburn.convert_kana("きっぷ"), "kippu");
+        assert_eq!(hepburn.convert_kana("まっちゃ"), "matcha");
+        assert_eq!(kunrei.convert_kana("まっちゃ"), "mattya");
+        assert_eq!(nihon_shiki.convert_kana("ちぢむ"), "tidimu");
+        assert_eq!(hepburn.convert_kana("きんえん"), "kin'en");
+        assert_eq!(hepburn.convert_kana("デスクトップ"), "desukutoppu");
+        assert_eq!(hepburn.convert_kana("コーヒー"), "koohii");
+        assert_eq!(hepburn.convert_kana("ファイル!"), "fairu!");
+        assert_eq!(
+            OutputScript::Katakana.convert_kana("りなっくす"),
+            "リナックス"
+        );
+        assert_eq!(OutputScript::parse("nihon-shiki"), Some(nihon_shiki));
+        assert_eq!(OutputScript::KanjiReading.next(), OutputScript::Hiragana);
+    }
+}
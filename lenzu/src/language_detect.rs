@@ -0,0 +1,252 @@
+// Script/language detection of the OCR text, so that only Japanese is passed to the Japanese interpreters (see
+// interpreter_router.rs): the letters of the text are counted per script (kana, kanji, hangul, latin), and the
+// language that the OCR backend reported (i.e. "ja", "jpn_vert", "zh-Hans") only breaks the tie of kanji without
+// kana, which could be either Japanese or Chinese
+use crate::kana::{script_of, Script};
+use crate::ocr_traits::OcrTraitResult;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Language {
+    Japanese,
+    Chinese,
+    Korean,
+    Latin,   // English and other languages written in latin letters
+    Other,   // i.e. cyrillic, thai, ...
+    Unknown, // no letters at all (i.e. digits and punctuation only)
+}
+
+impl Language {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Language::Japanese => "ja",
+            Language::Chinese => "zh",
+            Language::Korean => "ko",
+            Language::Latin => "latin",
+            Language::Other => "other",
+            Language::Unknown => "unknown",
+        }
+    }
+
+    // ISO 639-1/639-2 codes, BCP-47 tags and tesseract language names (i.e. "jpn_vert", "jpn+eng", the first
+    // language is the primary one)
+    pub fn from_code(code: &str) -> Option<Language> {
+        let primary = code
+            .split(['-', '_', '+'])
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+        match primary.as_str() {
+            "ja" | "jpn" => Some(Language::Japanese),
+            "zh" | "chi" | "zho" => Some(Language::Chinese),
+            "ko" | "kor" => Some(Language::Korean),
+            "en" | "eng" | "de" | "deu" | "ger" | "fr" | "fra" | "fre" | "es" | "spa" | "it"
+            | "ita" | "pt" | "por" | "nl" | "nld" | "dut" => Some(Language::Latin),
+            _ => None,
+        }
+    }
+}
+
+// script of a char, as far as the detection is concerned; neutral chars (whitespace, digits, punctuation) belong
+// to whatever they are next to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CharScript {
+    Kana,
+    Kanji,
+    Hangul,
+    Latin,
+    Other,
+    Neutral,
+}
+
+fn char_script(c: char) -> CharScript {
+    match script_of(c) {
+        Script::Kanji => CharScript::Kanji,
+        Script::Hiragana | Script::Katakana => CharScript::Kana,
+        Script::Other => match c {
+            _ if !c.is_alphabetic() => CharScript::Neutral,
+            '\u{AC00}'..='\u{D7AF}' | '\u{1100}'..='\u{11FF}' | '\u{3130}'..='\u{318F}' => {
+                CharScript::Hangul
+            }
+            '\u{0000}'..='\u{024F}' | '\u{1E00}'..='\u{1EFF}' | '\u{FF21}'..='\u{FF5A}' => {
+                CharScript::Latin
+            }
+            _ => CharScript::Other,
+        },
+    }
+}
+
+// fraction of the letters of the text per script (neutral chars are not letters)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub(crate) struct ScriptRatios {
+    pub letters: usize,
+    pub kana: f32,
+    pub kanji: f32,
+    pub hangul: f32,
+    pub latin: f32,
+    pub other: f32,
+}
+
+impl ScriptRatios {
+    pub fn of(text: &str) -> Self {
+        let mut counts = [0usize; 5];
+        for c in text.chars() {
+            match char_script(c) {
+                CharScript::Kana => counts[0] += 1,
+                CharScript::Kanji => counts[1] += 1,
+                CharScript::Hangul => counts[2] += 1,
+                CharScript::Latin => counts[3] += 1,
+                CharScript::Other => counts[4] += 1,
+                CharScript::Neutral => (),
+            }
+        }
+        let letters: usize = counts.iter().sum();
+        let ratio = |count: usize| {
+            if letters == 0 {
+                0.0
+            } else {
+                count as f32 / letters as f32
+            }
+        };
+        ScriptRatios {
+            letters,
+            kana: ratio(counts[0]),
+            kanji: ratio(counts[1]),
+            hangul: ratio(counts[2]),
+            latin: ratio(counts[3]),
+            other: ratio(counts[4]),
+        }
+    }
+}
+
+// the dominant language of the text; kanji without any kana is Japanese unless the backend said Chinese (lenzu is
+// a lens for Japanese after all, and titles/names often are kanji only)
+pub(crate) fn detect(text: &str, backend_language: Option<&str>) -> Language {
+    let hint = backend_language.and_then(Language::from_code);
+    let ratios = ScriptRatios::of(text);
+    if ratios.letters == 0 {
+        return hint.unwrap_or(Language::Unknown);
+    }
+    let cjk = ratios.kana + ratios.kanji;
+    if ratios.hangul > 0.0 && ratios.hangul >= cjk && ratios.hangul >= ratios.latin {
+        Language::Korean
+    } else if cjk > 0.0 && cjk >= ratios.latin && cjk >= ratios.other {
+        if ratios.kana == 0.0 && hint == Some(Language::Chinese) {
+            Language::Chinese
+        } else {
+            Language::Japanese
+        }
+    } else if ratios.latin >= ratios.other {
+        Language::Latin
+    } else {
+        Language::Other
+    }
+}
+
+pub(crate) fn detect_result(ocr_result: &OcrTraitResult) -> Language {
+    detect(&ocr_result.text, ocr_result.language.as_deref())
+}
+
+// run of the line in a single language; start/end are char (not byte) offsets into the line
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Segment {
+    pub language: Language,
+    pub start: usize,
+    pub end: usize,
+}
+
+// splits the line into runs of the same script, where kana and kanji runs are in the language of the whole text
+// (Japanese or Chinese, see detect()); neutral chars go with the run before them (or the first run, at the start)
+pub(crate) fn segments(line: &str, text_language: Language) -> Vec<Segment> {
+    let cjk_language = if text_language == Language::Chinese {
+        Language::Chinese
+    } else {
+        Language::Japanese
+    };
+    let mut segments: Vec<Segment> = vec![];
+    for (index, c) in line.chars().enumerate() {
+        let language = match char_script(c) {
+            CharScript::Kana | CharScript::Kanji => cjk_language,
+            CharScript::Hangul => Language::Korean,
+            CharScript::Latin => Language::Latin,
+            CharScript::Other => Language::Other,
+            CharScript::Neutral => Language::Unknown,
+        };
+        match segments.last_mut() {
+            Some(segment) if segment.language == language || language == Language::Unknown => {
+                segment.end = index + 1
+            }
+            Some(segment) if segment.language == Language::Unknown => {
+                segment.language = language;
+                segment.end = index + 1;
+            }
+            _ => segments.push(Segment {
+                language,
+                start: index,
+                end: index + 1,
+            }),
+        }
+    }
+    // a line without letters goes wherever the text goes
+    for segment in segments.iter_mut() {
+        if segment.language == Language::Unknown {
+            segment.language = text_language;
+        }
+    }
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_and_segment() {
+        let ratios = ScriptRatios::of("人気の本 (Linux)");
+        assert_eq!(ratios.letters, 9);
+        assert_eq!(
+            (ratios.kanji, ratios.kana, ratios.latin),
+            (3.0 / 9.0, 1.0 / 9.0, 5.0 / 9.0)
+        );
+
+        assert_eq!(detect("最近人気のデスクトップ", None), Language::Japanese);
+        assert_eq!(detect("人气很高", None), Language::Japanese);
+        assert_eq!(detect("人气很高", Some("zh-Hans")), Language::Chinese);
+        assert_eq!(detect("한국어 텍스트", Some("jpn")), Language::Korean);
+        assert_eq!(detect("Hello, world!", Some("jpn_vert")), Language::Latin);
+        assert_eq!(detect("Привет", None), Language::Other);
+        assert_eq!(detect("123 !?", Some("ja")), Language::Japanese);
+        assert_eq!(detect("123 !?", None), Language::Unknown);
+        assert_eq!(Language::from_code("jpn+eng"), Some(Language::Japanese));
+
+        let line = "「OK」人気のLinux, 2024年";
+        let runs: Vec<(Language, String)> = segments(line, Language::Japanese)
+            .iter()
+            .map(|segment| {
+                (
+                    segment.language,
+                    line.chars()
+                        .skip(segment.start)
+                        .take(segment.end - segment.start)
+                        .collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            runs,
+            [
+                (Language::Latin, "「OK」".to_string()),
+                (Language::Japanese, "人気の".to_string()),
+                (Language::Latin, "Linux, 2024".to_string()),
+                (Language::Japanese, "年".to_string()),
+            ]
+        );
+        assert_eq!(
+            segments("123", Language::Japanese),
+            [Segment {
+                language: Language::Japanese,
+                start: 0,
+                end: 3
+            }]
+        );
+    }
+}
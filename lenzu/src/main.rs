@@ -1,16 +1,51 @@
+#[cfg(target_os = "windows")]
 extern crate winapi;
+mod batch_ocr;
 mod cursor_data;
+mod deinflect;
+mod dictionary;
+mod eval_ocr;
+mod export_alto;
+mod export_hocr;
+mod export_mokuro;
+mod export_pdf;
+mod furigana;
 mod image_handling;
+mod interpreter_chain;
+mod interpreter_command;
 mod interpreter_ja;
+mod interpreter_jmdict;
+mod interpreter_mecab;
+mod interpreter_passthrough;
+mod interpreter_router;
 mod interpreter_traits;
+mod interpreter_translate;
+mod kana;
+mod language_detect;
 mod ocr_gcloud;
 mod ocr_tesseract;
 mod ocr_traits;
+#[cfg(target_os = "windows")]
 mod ocr_winmedia;
-use crate::image_handling::OCRImage;
+mod reading_order;
+mod registry;
+mod result_schema;
+mod screen_capture_file;
+mod screen_capture_traits;
+#[cfg(target_os = "windows")]
+mod screen_capture_win32;
+#[cfg(target_os = "linux")]
+mod screen_capture_x11;
+#[cfg(test)]
+mod test_support;
+use crate::furigana::ruby_for_result;
+use crate::image_handling::{OCRImage, PreprocessPipeline};
 //use crate::interpreter_traits::InterpreterTrait;
 use crate::interpreter_traits::{InterpreterTrait, InterpreterTraitResult};
-use crate::ocr_traits::OcrTrait;
+use crate::kana::OutputScript;
+use crate::language_detect::{detect_result, Language};
+use crate::ocr_traits::{OcrTrait, OcrTraitResult};
+use crate::reading_order::to_reading_order;
 
 use image::DynamicImage; // the "real" DynamicImage, not the one from imageproc or rusty_tesseract
 use imageproc::drawing::text_size;
@@ -29,9 +64,11 @@ use imageproc::{
 
 use ab_glyph::FontRef;
 use rusty_tesseract::image::Luma;
-use winapi::um::winuser::InvalidateRect;
 use std::io::Read;
 use std::{cmp::max, ffi::CString, path::Path, ptr, thread::current};
+#[cfg(target_os = "windows")]
+use winapi::um::winuser::InvalidateRect;
+#[cfg(target_os = "windows")]
 use winapi::{
     shared::minwindef::BYTE,
     um::{
@@ -42,18 +79,26 @@ use winapi::{
         },
         winuser::{
             CreateWindowExW, DefWindowProcW, DispatchMessageW, GetDC, GetMessageW, GetWindowLongW,
-            PostQuitMessage, RegisterClassW, ReleaseDC, ShowWindow, TranslateMessage,
-            CW_USEDEFAULT, GWL_EXSTYLE, MSG, SW_HIDE, SW_SHOW, VK_ESCAPE, VK_SPACE, WM_KEYDOWN,
-            WS_OVERLAPPEDWINDOW,
+            GetWindowRect, PostQuitMessage, RegisterClassW, ReleaseDC, ShowWindow,
+            TranslateMessage, CW_USEDEFAULT, GWL_EXSTYLE, MSG, SW_HIDE, SW_SHOW, VK_ESCAPE,
+            VK_SPACE, VK_TAB, WM_KEYDOWN, WS_OVERLAPPEDWINDOW,
         },
     },
 };
 
 const MAGNIFY_SCALE_FACTOR: u32 = 2;
+#[cfg(target_os = "windows")]
 const TOGGLE_WINDOW_MOVE_KEY: std::ffi::c_int = VK_SPACE;
+#[cfg(target_os = "windows")]
+const NEXT_OUTPUT_SCRIPT_KEY: std::ffi::c_int = VK_TAB; // cycles the script of the readings (see kana::OutputScript)
 const DEFAULT_WINDOW_WIDTH: i32 = 1024;
 const DEFAULT_WINDOW_HEIGHT: i32 = 768;
+// lines which OCR is less confident about than this are dropped (override via --min-confidence <0.0..1.0>)
+const DEFAULT_MIN_OCR_CONFIDENCE: f32 = 0.3;
+// preprocessing per OCR backend, i.e. LENZU_PREPROCESS_TESSERACT="upscale:2,sauvola:15" (see image_handling.rs)
+const ENV_PREPROCESS_PREFIX: &str = "LENZU_PREPROCESS_";
 
+#[cfg(target_os = "windows")]
 enum ToggleState {
     Free,
     MoveWindow,
@@ -61,111 +106,239 @@ enum ToggleState {
     Captured, // past-tense
 }
 
+#[cfg(target_os = "windows")]
 static mut TOGGLE_STATE: ToggleState = ToggleState::Free;
 
-fn create_ocr(args: &Vec<String>) -> Box<dyn crate::ocr_traits::OcrTrait> {
-    let force_windows_ocr = cfg!(target_os = "windows");
-    if force_windows_ocr {
-        // even if tesseract is installed, if on Windows, use the most reliable OCR available instead if no arguments are passed
-        if args.len() > 1 && args[1] != "--use-winmedia-ocr" {
-            return Box::new(ocr_tesseract::OcrTesseract::new()); //  if the first arg is not --use-winmedia-ocr, then use Tesseract
+// --ocr <tesseract|winmedia|gcloud> (see registry.rs and `lenzu list-backends`), or else --use-winmedia-ocr, or else
+// the registry's default (winmedia on Windows, tesseract elsewhere); --lang <lang> for the language of the text (i.e. "ja", "en", or a tesseract language such as
+// "jpn_vert"), which is also the language that goes to the interpreter (see create_interpreter()); an --ocr <name>
+// that cannot be created is an error rather than falling back to another OCR (which is not what was asked for)
+fn create_ocr(args: &[String]) -> Result<Box<dyn crate::ocr_traits::OcrTrait>, anyhow::Error> {
+    let mut ocr = create_ocr_backend(args)?;
+    if let Some(language) = registry::option_value(args, "--lang") {
+        if !ocr.set_language(language) {
+            eprintln!(
+                "Warning: '{}' OCR does not support --lang {}",
+                ocr.name(),
+                language
+            );
         }
-        // just use default windows OCR
-        return Box::new(ocr_winmedia::OcrWinMedia::new());
     }
-    // Not on Windows, so use Tesseract OCR
-    Box::new(ocr_tesseract::OcrTesseract::new()) // default to Tesseract (because even if it unreliable, at least it is cross-platform and can be used on Linux)
+    Ok(ocr)
 }
 
-fn create_interpreter(_args: &Vec<String>) -> Box<dyn crate::interpreter_traits::InterpreterTrait> {
-    Box::new(interpreter_ja::InterpreterJa::new())
-}
-
-// NOTE: Make sure to call ShowWindow(hwnd, SW_IDE) prior to calling this method and ShowWindow(hwnd, SW_SHOW) after image is captured
-// this is so that we do not get the image-echo effect (like a mirror reflecting a mirror) when we capture the screen
-// will need to experiment, but it seems we do not need to invalidate since ShowWindow() will implicitly refresh window
-fn from_screen_to_image(cursor_pos: CursorData) -> DynamicImage {
-    // first, get DC of the entire desktop (hence we do not need HWND passed here) via calling GetDC(NULL) - NULL means the entire desktop
-    let source_desktop_dc = unsafe { GetDC(ptr::null_mut()) };
-
-    // Create a compatible device context and bitmap
-    let destination_memory_dc = unsafe { CreateCompatibleDC(source_desktop_dc) };
-    let destination_bitmap = unsafe {
-        CreateCompatibleBitmap(
-            source_desktop_dc,
-            cursor_pos.window_width() as i32,
-            cursor_pos.window_height() as i32,
-        )
+fn create_ocr_backend(
+    args: &[String],
+) -> Result<Box<dyn crate::ocr_traits::OcrTrait>, anyhow::Error> {
+    // online OCR is always opt-in (the captured image is sent to Google, see README on privacy), so it is never the
+    // default
+    let name = match registry::option_value(args, "--ocr") {
+        Some(name) => name,
+        None if args.iter().any(|arg| arg == "--use-winmedia-ocr") => "winmedia",
+        None => registry::DEFAULT_OCR,
     };
+    registry::create_ocr(name, args)
+}
 
-    // select the bitmap into the memory device context
-    let previous_screen_for_restore_dc = unsafe {
-        SelectObject(
-            destination_memory_dc,
-            destination_bitmap as *mut winapi::ctypes::c_void,
-        )
-    };
-    let image: DynamicImage;
-    unsafe {
-        // BitBlt from the screen DC to the memory DC
-        BitBlt(
-            destination_memory_dc, // destination device context
-            0,                     // destination x
-            0,                     // destination y
-            cursor_pos.window_width() as i32,
-            cursor_pos.window_height() as i32,
-            source_desktop_dc,     // source device context
-            cursor_pos.window_x(), // source x - note that coordinate can be negative value (e.g. cursor is on the left side of the PRIMARY monitor)
-            cursor_pos.window_y(), // source y
-            SRCCOPY,
-        );
+// --capture-from-file <image> uses the image as if it was the desktop (no display needed, i.e. for CI or Xvfb-less boxes)
+fn create_screen_capture(
+    args: &[String],
+) -> Result<Box<dyn crate::screen_capture_traits::ScreenCaptureTrait>, anyhow::Error> {
+    if let Some(index) = args.iter().position(|arg| arg == "--capture-from-file") {
+        return match args.get(index + 1) {
+            Some(image_path) => Ok(Box::new(
+                screen_capture_file::ScreenCaptureFile::new(image_path)?,
+            )),
+            None => Err(anyhow::anyhow!("--capture-from-file expects an image path")),
+        };
+    }
+    create_platform_screen_capture()
+}
 
-        // Clean up: Select the OLD bitmap back into the memory DC
-        SelectObject(destination_memory_dc, previous_screen_for_restore_dc);
+#[cfg(target_os = "windows")]
+fn create_platform_screen_capture(
+) -> Result<Box<dyn crate::screen_capture_traits::ScreenCaptureTrait>, anyhow::Error> {
+    Ok(Box::new(screen_capture_win32::ScreenCaptureWin32::new()))
+}
 
-        // At this point, destination_bitmap contains the captured image
-        // Create a BITMAPINFO structure to receive the bitmap data
-        let mut info: BITMAPINFO = std::mem::zeroed();
-        info.bmiHeader.biSize = std::mem::size_of::<BITMAPINFO>() as u32;
-        info.bmiHeader.biWidth = cursor_pos.window_width() as i32;
-        info.bmiHeader.biHeight = -(cursor_pos.window_height() as i32); // top-down bitmap
-        info.bmiHeader.biPlanes = 1;
-        info.bmiHeader.biBitCount = 32; // each pixel is a 32-bit RGB color
-        info.bmiHeader.biCompression = BI_RGB;
+#[cfg(target_os = "linux")]
+fn create_platform_screen_capture(
+) -> Result<Box<dyn crate::screen_capture_traits::ScreenCaptureTrait>, anyhow::Error> {
+    Ok(Box::new(screen_capture_x11::ScreenCaptureX11::open(None)?))
+}
 
-        // Allocate a buffer to receive the bitmap data
-        let mut data: Vec<BYTE> =
-            vec![0; (cursor_pos.window_width() * cursor_pos.window_height() * 4) as usize];
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+fn create_platform_screen_capture(
+) -> Result<Box<dyn crate::screen_capture_traits::ScreenCaptureTrait>, anyhow::Error> {
+    Err(anyhow::anyhow!(
+        "Screen capture is not supported on this platform, use --capture-from-file <image>"
+    ))
+}
 
-        // Get the bitmap data
-        GetDIBits(
-            destination_memory_dc,
-            destination_bitmap,
-            0,
-            cursor_pos.window_height(),
-            data.as_mut_ptr() as *mut _,
-            &mut info,
-            DIB_RGB_COLORS,
-        );
+fn parse_min_confidence(args: &[String]) -> f32 {
+    match args.iter().position(|arg| arg == "--min-confidence") {
+        Some(index) => match args.get(index + 1).map(|value| value.parse::<f32>()) {
+            Some(Ok(min_confidence)) => min_confidence.clamp(0.0, 1.0),
+            _ => {
+                eprintln!(
+                    "--min-confidence expects a value between 0.0 and 1.0, using default {}",
+                    DEFAULT_MIN_OCR_CONFIDENCE
+                );
+                DEFAULT_MIN_OCR_CONFIDENCE
+            }
+        },
+        None => DEFAULT_MIN_OCR_CONFIDENCE,
+    }
+}
 
-        // Convert the data to a DynamicImage
-        image = ImageBuffer::from_fn(
-            cursor_pos.window_width(),
-            cursor_pos.window_height(),
-            |x, y| {
-                let i = ((y * cursor_pos.window_width() + x) * 4) as usize;
-                image::Rgba([data[i + 2], data[i + 1], data[i], 255])
-            },
-        )
-        .into(); // At this point, image is a DynamicImage containing the bitmap image
+// --preprocess <spec> (i.e. "upscale:2,contrast,otsu") for the OCR backend in use, otherwise LENZU_PREPROCESS_<BACKEND>
+// from the environment, otherwise the backend's default; --dump-preprocess <dir> saves each step's image
+fn create_preprocess_pipeline(args: &[String], ocr_name: &str) -> PreprocessPipeline {
+    let env_name = format!("{}{}", ENV_PREPROCESS_PREFIX, ocr_name.to_uppercase());
+    let spec = match args.iter().position(|arg| arg == "--preprocess") {
+        Some(index) => args.get(index + 1).cloned(),
+        None => std::env::var(&env_name).ok(),
+    };
+    let mut pipeline = match spec.map(|spec| PreprocessPipeline::parse(&spec)) {
+        Some(Ok(pipeline)) => pipeline,
+        Some(Err(e)) => {
+            eprintln!(
+                "Error: {} - using default preprocessing for '{}'",
+                e, ocr_name
+            );
+            PreprocessPipeline::default_for_backend(ocr_name)
+        }
+        None => PreprocessPipeline::default_for_backend(ocr_name),
+    };
+    if let Some(index) = args.iter().position(|arg| arg == "--dump-preprocess") {
+        pipeline.dump_dir = args.get(index + 1).map(std::path::PathBuf::from);
+    }
+    eprintln!("Preprocessing for '{}': '{}'", ocr_name, pipeline.spec());
+    pipeline
+}
 
-        DeleteDC(destination_memory_dc);
-        ReleaseDC(ptr::null_mut(), source_desktop_dc);
-        DeleteObject(destination_bitmap as *mut winapi::ctypes::c_void);
+// --output-script <hiragana|katakana|hepburn|kunrei|nihon-shiki|kanji-reading> for the readings (see
+// kana::OutputScript), hiragana by default; only the text in the --lang language (Japanese by default) goes to the
+// interpreter, the rest (i.e. English) is passed through, or translated with --translate-other
+// <libretranslate|openai> (see interpreter_router.rs)
+fn create_interpreter(
+    args: &[String],
+) -> Result<Box<dyn crate::interpreter_traits::InterpreterTrait>, anyhow::Error> {
+    let language = match registry::option_value(args, "--lang") {
+        Some(code) => Language::from_code(code).unwrap_or_else(|| {
+            eprintln!("Warning: unknown --lang {}, assuming Japanese", code);
+            Language::Japanese
+        }),
+        None => Language::Japanese,
     };
-    image
+    let mut interpreter: Box<dyn crate::interpreter_traits::InterpreterTrait> =
+        Box::new(interpreter_router::InterpreterRouter::new_with_routes(
+            vec![(language, create_interpreter_backend(args)?)],
+            create_fallback_interpreter(args),
+        ));
+    if let Some(index) = args.iter().position(|arg| arg == "--output-script") {
+        let output_script = args
+            .get(index + 1)
+            .and_then(|name| OutputScript::parse(name));
+        match output_script {
+            Some(output_script) if interpreter.set_output_script(output_script) => (),
+            Some(output_script) => eprintln!(
+                "Warning: '{}' interpreter does not support --output-script {}, using {}",
+                interpreter.name(),
+                output_script.name(),
+                interpreter.output_script().name()
+            ),
+            None => eprintln!(
+                "--output-script expects one of {}, using {}",
+                OutputScript::ALL
+                    .iter()
+                    .map(|output_script| output_script.name())
+                    .collect::<Vec<&str>>()
+                    .join(", "),
+                interpreter.output_script().name()
+            ),
+        }
+    }
+    eprintln!(
+        "Interpreter '{}' - languages: {:?}",
+        interpreter.name(),
+        interpreter.init()
+    );
+    Ok(interpreter)
 }
 
+fn create_fallback_interpreter(
+    args: &[String],
+) -> Box<dyn crate::interpreter_traits::InterpreterTrait> {
+    if let Some(index) = args.iter().position(|arg| arg == "--translate-other") {
+        match args
+            .get(index + 1)
+            .and_then(|arg| interpreter_translate::TranslateBackend::parse(arg))
+        {
+            Some(backend) if backend != interpreter_translate::TranslateBackend::Gloss => {
+                let target_language = args
+                    .iter()
+                    .position(|arg| arg == "--translate-to")
+                    .and_then(|index| args.get(index + 1));
+                let mut interpreter = interpreter_translate::InterpreterTranslate::from_backend(
+                    backend,
+                    target_language.map(|language| language.as_str()),
+                    None,
+                );
+                interpreter.set_source_language("auto");
+                return Box::new(interpreter);
+            }
+            _ => eprintln!("--translate-other expects libretranslate or openai"),
+        }
+    }
+    Box::new(interpreter_passthrough::InterpreterPassthrough::new())
+}
+
+// --interpreter <name>[,<name>...] (see registry.rs and `lenzu list-backends`), kakasi otherwise; as with --ocr, an
+// interpreter that cannot be created is an error rather than falling back to kakasi
+fn create_interpreter_backend(
+    args: &[String],
+) -> Result<Box<dyn crate::interpreter_traits::InterpreterTrait>, anyhow::Error> {
+    let names = registry::option_value(args, "--interpreter").unwrap_or("kakasi");
+    registry::create_interpreter(names, args)
+}
+
+// the options that main() (and the backends of the registry) handle, along with whether they take a value; they are
+// dropped from the args of the 'ocr' and 'eval' subcommands (see without_main_options()), so that the subcommands
+// only see their own
+const MAIN_OPTIONS: [(&str, bool); 14] = [
+    ("--ocr", true),
+    ("--use-winmedia-ocr", false),
+    ("--lang", true),
+    ("--min-confidence", true),
+    ("--preprocess", true),
+    ("--dump-preprocess", true),
+    ("--interpreter", true),
+    ("--interpreter-command", true),
+    ("--mecab-dictionary", true),
+    ("--dictionary", true),
+    ("--output-script", true),
+    ("--translate-to", true),
+    ("--translate-other", true),
+    ("--capture-from-file", true),
+];
+
+fn without_main_options(args: &[String]) -> Vec<String> {
+    let mut remaining = vec![];
+    let mut arg_iter = args.iter();
+    while let Some(arg) = arg_iter.next() {
+        match MAIN_OPTIONS.iter().find(|(option, _)| option == arg) {
+            Some((_, true)) => {
+                arg_iter.next();
+            }
+            Some((_, false)) => (),
+            None => remaining.push(arg.clone()),
+        }
+    }
+    remaining
+}
+
+#[cfg(target_os = "windows")]
 fn from_image_to_window(
     application_window_handle: *mut winapi::shared::windef::HWND__,
     image: DynamicImage,
@@ -230,27 +403,58 @@ fn from_image_to_window(
     }
 }
 
+#[cfg(target_os = "windows")]
 fn capture_and_ocr(
     hwnd: *mut winapi::shared::windef::HWND__,
+    screen_capture: &dyn crate::screen_capture_traits::ScreenCaptureTrait,
     ocr: &mut Box<dyn crate::ocr_traits::OcrTrait>,
     cursor_pos: CursorData,
     ocr_font: &mut OCRImage,
     _supported_lang: &str, // '+' separated list of supported languages(i.e. "jpn+jpn_ver+osd"), note that longer this list, longer it takes to OCR (ie. 10sec/lang so if there are 4 in this list, it can take 40 seconds!)
     interpreter: &mut Box<dyn crate::interpreter_traits::InterpreterTrait>,
+    preprocess: &PreprocessPipeline,
+    min_confidence: f32,
 ) {
     // first, set transparancy of the window to 99% (i.e. almost invisible) using SetLayeredWindowAttributes()
     hide_window(hwnd);
 
     // now capture the screen
-    let screenshot = from_screen_to_image(cursor_pos);
+    let possible_screenshot = screen_capture.capture(cursor_pos.window_rect());
 
     // show the application window again
     show_window(hwnd);
 
+    match possible_screenshot {
+        Ok(screenshot) => {
+            let (recognized_image, _) = ocr_and_interpret(
+                screenshot,
+                ocr.as_ref(),
+                interpreter.as_ref(),
+                ocr_font,
+                preprocess,
+                min_confidence,
+            );
+            // render translated text (or what we've captured originally if OCR failed) onto the window
+            from_image_to_window(hwnd, recognized_image);
+        }
+        Err(e) => println!("Error: Unable to capture screen - {:?}", e),
+    }
+}
+
+// Platform (and window) independent part of the lens: OCR the captured image, interpret the text, and overlay the
+// interpreted text onto the captured image; returns the image to be rendered, and the results if OCR succeeded
+fn ocr_and_interpret(
+    screenshot: DynamicImage,
+    ocr: &dyn crate::ocr_traits::OcrTrait,
+    interpreter: &dyn crate::interpreter_traits::InterpreterTrait,
+    ocr_font: &mut OCRImage,
+    preprocess: &PreprocessPipeline,
+    min_confidence: f32,
+) -> (DynamicImage, Option<(OcrTraitResult, InterpreterTraitResult)>) {
     // the image we just captured, we'll need to now pass it down to OCR and get the text back
     // We will (for now) assume it is either "jpn" or "jpn_vert" and we'll just pass it down
     // to kakasi and convert all kanji to hiragana
-    // 1. convert to grayscale
+    // 1. preprocess (by default, convert to grayscale)
     // 2. pass it down to OCR
     // 3. get the text back
     // 4. draw the text onto the mem_dc_topmost
@@ -258,9 +462,16 @@ fn capture_and_ocr(
     // 6. scale/magnify
     // 7. draw the magnified image onto the window
     // convert DC to RGBA - probably can get away with 24-bit but for better byte alignment, will stay at 32-bit
-    let gray_scale_image = screenshot.grayscale(); // Convert the image to grayscale
     let ocr_start_time = std::time::Instant::now();
-    let ocr_result = ocr.evaluate(&gray_scale_image);
+    let ocr_result = preprocess
+        .apply(&screenshot, "capture")
+        .and_then(|preprocessed| {
+            ocr.evaluate(&preprocessed.image)
+                .map(|recognized_result| preprocessed.to_original_result(recognized_result))
+        })
+        .map(|recognized_result| {
+            to_reading_order(recognized_result).filter_by_confidence(min_confidence)
+        });
     let ocr_time = ocr_start_time.elapsed().as_millis();
 
     // now run kakasi to convert the kanji to hiragana
@@ -268,8 +479,16 @@ fn capture_and_ocr(
     let start_interpreter = std::time::Instant::now();
     let possible_result_tupled = match ocr_result {
         Ok(recognized_result) => {
-            println!("OCR Result: '{:?}' {} mSec", recognized_result, ocr_time);
-            let possible_translate_result = interpreter.convert(recognized_result.text.as_str());
+            println!(
+                "OCR Result ({}): '{:?}' {} mSec",
+                detect_result(&recognized_result).name(),
+                recognized_result,
+                ocr_time
+            );
+            let possible_translate_result = interpreter.convert_with_language(
+                recognized_result.text.as_str(),
+                recognized_result.language.as_deref(),
+            );
             match possible_translate_result {
                 Ok(translate_result) => {
                     println!(
@@ -306,8 +525,40 @@ fn capture_and_ocr(
             // And then, layer this PNG onto the original image (blend  png_buffer onto gray_scale_image)
             // image width and height is based on max of the two
             // now create a PNG with alpha channel and draw the text onto the image
+            // furigana goes next to the kanji where the OCR told us the words are, and translations (of a translator,
+            // or of the translators in a chain such as kakasi,libretranslate) are drawn over the OCR lines
+            // (horizontally, since they usually are not Japanese); if the OCR could not tell us where the lines are
+            // (no word rects), we fall back to the interpreted text as a block
             let mut recognized_image = screenshot;
-            if !translate_result.text.is_empty() {
+            let rubies = if interpreter.translates() {
+                vec![]
+            } else {
+                ruby_for_result(
+                    &recognized_result,
+                    &translate_result,
+                    interpreter.output_script(),
+                )
+            };
+            let has_translations = !translate_result.translations.is_empty()
+                && translate_result.translations.len() == recognized_result.rects.len();
+            if has_translations {
+                for (line, ocr_line) in translate_result
+                    .translations
+                    .iter()
+                    .zip(recognized_result.rects.iter())
+                {
+                    ocr_font.set_image(recognized_image);
+                    recognized_image = ocr_font.overlay_text_in_rect(
+                        line,
+                        &ocr_line.rect(),
+                        crate::ocr_traits::OcrWritingDirection::HorizontalLtr,
+                    );
+                }
+            }
+            if !rubies.is_empty() {
+                ocr_font.set_image(recognized_image);
+                recognized_image = ocr_font.overlay_ruby(&rubies);
+            } else if !has_translations && !translate_result.text.is_empty() {
                 ocr_font.set_image(recognized_image);
                 recognized_image = ocr_font.overlay_text(translate_result.text.as_str(), 0, 0);
             }
@@ -318,8 +569,7 @@ fn capture_and_ocr(
                 recognized_image.save("recognized_image.png").unwrap();
             }
 
-            // render translated text onto the window
-            from_image_to_window(hwnd, recognized_image);
+            (recognized_image, Some((recognized_result, translate_result)))
         }
         None => {
             println!(
@@ -328,7 +578,7 @@ fn capture_and_ocr(
                 possible_result_tupled
             );
             // render what we've captured originally instead
-            from_image_to_window(hwnd, screenshot);
+            (screenshot, None)
         }
     }
 }
@@ -337,6 +587,7 @@ fn capture_and_ocr(
 // unfortunately, "hide" isn't based on ShowWindow(SW_HIDE) because that effect is similar/same as when the window is minimized, and
 // you completely loose control of the window (i.e. you cannot move window, nor will hitting the ESCAPE key work because the window is NOT in focus!)
 // Hence, when we "hide" the window, it actually is more like setting the transparancy of the window to 99% (i.e. almost invisible)
+#[cfg(target_os = "windows")]
 fn hide_window(hwnd: *mut winapi::shared::windef::HWND__) {
     unsafe {
         let current_flags = GetWindowLongW(hwnd, GWL_EXSTYLE);
@@ -354,6 +605,7 @@ fn hide_window(hwnd: *mut winapi::shared::windef::HWND__) {
         );
     }
 }
+#[cfg(target_os = "windows")]
 fn show_window(hwnd: *mut winapi::shared::windef::HWND__) {
     unsafe {
         let current_flags = GetWindowLongW(hwnd, GWL_EXSTYLE);
@@ -372,18 +624,44 @@ fn show_window(hwnd: *mut winapi::shared::windef::HWND__) {
     }
 }
 
-fn capture_and_scale(hwnd: *mut winapi::shared::windef::HWND__, cursor_pos: CursorData) {
+#[cfg(target_os = "windows")]
+fn capture_and_scale(
+    hwnd: *mut winapi::shared::windef::HWND__,
+    screen_capture: &dyn crate::screen_capture_traits::ScreenCaptureTrait,
+    cursor_pos: CursorData,
+) {
     // first, set transparancy of the window to 99% (i.e. almost invisible) using SetLayeredWindowAttributes()
     hide_window(hwnd);
 
     // now capture the screen
-    let screenshot = from_screen_to_image(cursor_pos);
+    let possible_screenshot = screen_capture.capture(cursor_pos.window_rect());
 
     // show the application window again
     show_window(hwnd);
 
     // now render what we've captured
-    from_image_to_window(hwnd, screenshot);
+    match possible_screenshot {
+        Ok(screenshot) => from_image_to_window(hwnd, screenshot),
+        Err(e) => println!("Error: Unable to capture screen - {:?}", e),
+    }
+}
+
+// dimension of the application window via GetWindowRect() (GetWindowInfo() can do the same, but it provides more info that we care...)
+#[cfg(target_os = "windows")]
+fn window_size(hwnd: *mut winapi::shared::windef::HWND__) -> (u32, u32) {
+    let mut window_rect = winapi::shared::windef::RECT {
+        left: 0,
+        top: 0,
+        right: 0,
+        bottom: 0,
+    };
+    unsafe {
+        GetWindowRect(hwnd, &mut window_rect);
+    }
+    (
+        (window_rect.right - window_rect.left) as u32,
+        (window_rect.bottom - window_rect.top) as u32,
+    )
 }
 
 //fn my_draw_text_mut(
@@ -420,16 +698,217 @@ fn capture_and_scale(hwnd: *mut winapi::shared::windef::HWND__, cursor_pos: Curs
 async fn main() {
     let args = std::env::args().collect::<Vec<String>>();
 
-    let class_name = "Lenzu";
-    let window_name = "Lenzu-OCR";
+    // headless batch mode: `lenzu ocr <files|dirs|globs>...` (see batch_ocr.rs)
+    if args.get(1).map(|arg| arg.as_str()) == Some("ocr") {
+        std::process::exit(run_batch_ocr(&args));
+    }
+    // OCR accuracy against ground truth: `lenzu eval <files|dirs|globs>...` (see eval_ocr.rs)
+    if args.get(1).map(|arg| arg.as_str()) == Some("eval") {
+        std::process::exit(run_eval(&args));
+    }
+    // which OCR backends and interpreters can be used here: `lenzu list-backends` (see registry.rs)
+    if args.get(1).map(|arg| arg.as_str()) == Some("list-backends") {
+        print!("{}", registry::list_backends(&args[2..]));
+        std::process::exit(0);
+    }
+    // offline dictionary: `lenzu dictionary import|lookup ...` (see dictionary.rs)
+    if args.get(1).map(|arg| arg.as_str()) == Some("dictionary") {
+        std::process::exit(run_dictionary(&args));
+    }
 
-    // default to Tesseract OCR, but if  --use-winmedia-ocr is passed, then use Windows.Media.Ocr
-    let mut ocr = create_ocr(&args);
+    // the registry's default OCR (winmedia on Windows, tesseract elsewhere), unless --ocr <name> is passed
+    let mut ocr = match create_ocr(&args) {
+        Ok(ocr) => ocr,
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            std::process::exit(2);
+        }
+    };
     let ocr_langugages = ocr.init();
-    let mut interpreter = create_interpreter(&args);
+    let mut interpreter = match create_interpreter(&args) {
+        Ok(interpreter) => interpreter,
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            std::process::exit(2);
+        }
+    };
+    let min_confidence = parse_min_confidence(&args);
+    let preprocess = create_preprocess_pipeline(&args, ocr.name());
 
     let mut ocr_font = OCRImage::new(None);
 
+    let screen_capture = match create_screen_capture(&args) {
+        Ok(screen_capture) => screen_capture,
+        Err(e) => {
+            println!("Error: Unable to capture screen - {:?}", e);
+            return;
+        }
+    };
+
+    run_lens(
+        screen_capture.as_ref(),
+        &mut ocr,
+        &ocr_langugages,
+        &mut interpreter,
+        &mut ocr_font,
+        &preprocess,
+        min_confidence,
+    );
+}
+
+// returns the process exit code: 0 if all images were processed, 1 if any of them failed, and 2 on usage errors (or
+// if the --ocr backend or the --interpreter cannot be created)
+fn run_batch_ocr(args: &[String]) -> i32 {
+    let batch_args = match batch_ocr::BatchOcrArgs::parse(&without_main_options(&args[2..])) {
+        Ok(batch_args) => batch_args,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 2;
+        }
+    };
+    // drop the "ocr" subcommand so that create_ocr() sees the same args as in the interactive mode
+    let option_args = std::iter::once(args[0].clone())
+        .chain(args[2..].iter().cloned())
+        .collect::<Vec<String>>();
+    let ocr = match create_ocr(&option_args) {
+        Ok(ocr) => ocr,
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            return 2;
+        }
+    };
+    ocr.init();
+    let interpreter = match create_interpreter(&option_args) {
+        Ok(interpreter) => interpreter,
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            return 2;
+        }
+    };
+    let min_confidence = parse_min_confidence(&option_args);
+    let preprocess = create_preprocess_pipeline(&option_args, ocr.name());
+    match batch_ocr::run(
+        &batch_args,
+        ocr.as_ref(),
+        interpreter.as_ref(),
+        &preprocess,
+        min_confidence,
+    ) {
+        Ok(0) => 0,
+        Ok(failed_count) => {
+            eprintln!("{} image(s) failed", failed_count);
+            1
+        }
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            2
+        }
+    }
+}
+
+// returns the process exit code: 0 if all images were evaluated, 1 if the OCR failed on any of them, and 2 on usage
+// errors (or if there was nothing to evaluate, or the --ocr backend cannot be created)
+fn run_eval(args: &[String]) -> i32 {
+    let eval_args = match eval_ocr::EvalArgs::parse(&without_main_options(&args[2..])) {
+        Ok(eval_args) => eval_args,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 2;
+        }
+    };
+    // same as run_batch_ocr(), drop the "eval" subcommand for create_ocr()
+    let option_args = std::iter::once(args[0].clone())
+        .chain(args[2..].iter().cloned())
+        .collect::<Vec<String>>();
+    let ocr = match create_ocr(&option_args) {
+        Ok(ocr) => ocr,
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            return 2;
+        }
+    };
+    ocr.init();
+    let min_confidence = parse_min_confidence(&option_args);
+    let preprocess = create_preprocess_pipeline(&option_args, ocr.name());
+    match eval_ocr::run(&eval_args, ocr.as_ref(), &preprocess, min_confidence) {
+        Ok(report) if report.total.failed_count == 0 => 0,
+        Ok(report) => {
+            eprintln!("{} image(s) failed", report.total.failed_count);
+            1
+        }
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            2
+        }
+    }
+}
+
+// returns the process exit code: 0 on success, and 2 on usage errors (or if the import/lookup failed)
+fn run_dictionary(args: &[String]) -> i32 {
+    match dictionary::run(&args[2..]) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            2
+        }
+    }
+}
+
+// There is no (interactive) lens window for X11 (yet), so we just capture once, the same size of the lens
+// window centered around the cursor, then OCR+interpret and save the result as an image
+#[cfg(not(target_os = "windows"))]
+fn run_lens(
+    screen_capture: &dyn crate::screen_capture_traits::ScreenCaptureTrait,
+    ocr: &mut Box<dyn crate::ocr_traits::OcrTrait>,
+    _ocr_langugages: &Vec<String>,
+    interpreter: &mut Box<dyn crate::interpreter_traits::InterpreterTrait>,
+    ocr_font: &mut OCRImage,
+    preprocess: &PreprocessPipeline,
+    min_confidence: f32,
+) {
+    let mut cursor = CursorData::new();
+    if let Err(e) = cursor.update(
+        screen_capture,
+        DEFAULT_WINDOW_WIDTH as u32,
+        DEFAULT_WINDOW_HEIGHT as u32,
+    ) {
+        println!("Error: Could not get cursor position - {:?}", e);
+        return;
+    }
+    let screenshot = match screen_capture.capture(cursor.window_rect()) {
+        Ok(screenshot) => screenshot,
+        Err(e) => {
+            println!("Error: Unable to capture screen - {:?}", e);
+            return;
+        }
+    };
+    let (recognized_image, _) = ocr_and_interpret(
+        screenshot,
+        ocr.as_ref(),
+        interpreter.as_ref(),
+        ocr_font,
+        preprocess,
+        min_confidence,
+    );
+    println!("Saving: lenzu_capture.png");
+    if let Err(e) = recognized_image.save("lenzu_capture.png") {
+        println!("Error: Could not save lenzu_capture.png - {}", e);
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn run_lens(
+    screen_capture: &dyn crate::screen_capture_traits::ScreenCaptureTrait,
+    ocr: &mut Box<dyn crate::ocr_traits::OcrTrait>,
+    ocr_langugages: &Vec<String>,
+    interpreter: &mut Box<dyn crate::interpreter_traits::InterpreterTrait>,
+    ocr_font: &mut OCRImage,
+    preprocess: &PreprocessPipeline,
+    min_confidence: f32,
+) {
+    let class_name = "Lenzu";
+    let window_name = "Lenzu-OCR";
+
     let h_instance = ptr::null_mut();
     let class_name_cstr = CString::new(class_name).expect("CString creation failed");
     let window_name_cstr = CString::new(window_name).expect("CString creation failed");
@@ -497,7 +976,13 @@ async fn main() {
             TranslateMessage(&msg);
             DispatchMessageW(&msg);
         }
-        cursor.update(hwnd);
+        let (window_width, window_height) = window_size(hwnd);
+        if let Err(e) = cursor.update(screen_capture, window_width, window_height) {
+            // Handle the error appropriately if necessary.
+            println!("Could not get cursor position - {:?}", e);
+            // post quit
+            unsafe { PostQuitMessage(-1) };
+        }
 
         // either left-click or keydown to toggle states
         if msg.message == WM_KEYDOWN {
@@ -521,6 +1006,18 @@ async fn main() {
                         }
                     }
                 }
+                NEXT_OUTPUT_SCRIPT_KEY => {
+                    let output_script = interpreter.output_script().next();
+                    if interpreter.set_output_script(output_script) {
+                        println!("Output script: {}", output_script.name());
+                        // interpret the capture again, in the new script
+                        unsafe {
+                            if let ToggleState::Captured = TOGGLE_STATE {
+                                TOGGLE_STATE = ToggleState::Capture;
+                            }
+                        }
+                    }
+                }
                 _ => (),
             }
         } else if msg.message == winapi::um::winuser::WM_LBUTTONUP {
@@ -542,7 +1039,7 @@ async fn main() {
 
         unsafe {
             match TOGGLE_STATE {
-                ToggleState::Free => capture_and_scale(hwnd, cursor),
+                ToggleState::Free => capture_and_scale(hwnd, screen_capture, cursor),
                 ToggleState::MoveWindow => {
                     // move the window to the cursor position (a sticky window)
                     winapi::um::winuser::SetWindowPos(
@@ -556,18 +1053,21 @@ async fn main() {
                     );
                     //// invalidate the window so it can redraw the window onto the Desktop/monitor
                     //InvalidateRect(hwnd, ptr::null_mut(), 0);
-                    capture_and_scale(hwnd, cursor); // show contents UNDERNEATH the window (will InvalidateRect() so that it'll also redraw the actual window onto the )
+                    capture_and_scale(hwnd, screen_capture, cursor); // show contents UNDERNEATH the window (will InvalidateRect() so that it'll also redraw the actual window onto the )
                 }
                 ToggleState::Capture => {
                     // capture the screen and magnify it
                     let supported_languages = ocr_langugages.join("+");
                     capture_and_ocr(
                         hwnd,
-                        &mut ocr,
+                        screen_capture,
+                        ocr,
                         cursor,
-                        &mut ocr_font,
+                        ocr_font,
                         supported_languages.clone().as_str(),
-                        &mut interpreter,
+                        interpreter,
+                        preprocess,
+                        min_confidence,
                     );
                     // once it's blitted to that window, stay still..
                     TOGGLE_STATE = ToggleState::Captured;
@@ -584,6 +1084,8 @@ async fn main() {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::screen_capture_traits::ScreenCaptureTrait;
+    use crate::test_support::FakeOcr;
     // NOTE: We want to use imageproc::image rather than image crate because we want to use imageproc::drawing::draw_text_mut()
     use imageproc::{
         drawing::draw_text_mut,
@@ -594,6 +1096,86 @@ mod tests {
     };
     use rusttype::{point, Font, Scale, ScaledGlyph};
 
+    #[test]
+    fn test_headless_capture_ocr_interpret() {
+        let mut screen_capture =
+            screen_capture_file::ScreenCaptureFile::new("../assets/ubunchu01_02.png").unwrap();
+        screen_capture.set_cursor_position(0, 0); // lens is half off the desktop
+        let mut cursor = CursorData::new();
+        cursor.update(&screen_capture, 320, 240).unwrap();
+        assert_eq!(cursor.window_x(), -160);
+        let screenshot = screen_capture.capture(cursor.window_rect()).unwrap();
+        assert_eq!(screenshot.dimensions(), (320, 240));
+
+        let mut ocr_font = OCRImage::new(None);
+        let (recognized_image, possible_results) = ocr_and_interpret(
+            screenshot,
+            &FakeOcr::lines(&["最近人気の"]),
+            &interpreter_ja::InterpreterJa::new(),
+            &mut ocr_font,
+            &PreprocessPipeline::default_for_backend("fake"),
+            DEFAULT_MIN_OCR_CONFIDENCE,
+        );
+        assert_eq!(recognized_image.dimensions(), (320, 240));
+        let (recognized_result, translate_result) = possible_results.unwrap();
+        assert_eq!(recognized_result.text, "最近人気の");
+        assert!(!translate_result.text.is_empty());
+    }
+
+    #[test]
+    fn test_mixed_chain_draws_translations_and_furigana() {
+        // a vertical column "人気の本" on a blank page, read with kakasi and glossed by the dictionary store
+        let mut line = crate::ocr_traits::OcrLine::new(vec![crate::ocr_traits::OcrWord::new(
+            "人気の本".to_string(),
+            0,
+            crate::ocr_traits::OcrRect::new(150, 20, 190, 180),
+        )]);
+        line.set_writing_direction(crate::ocr_traits::OcrWritingDirection::VerticalTtbRtl);
+        let mut ocr_result = OcrTraitResult::new();
+        ocr_result.rects = vec![line];
+        ocr_result.lines = vec!["人気の本".to_string()];
+        ocr_result.text = "人気の本".to_string();
+        let dictionary = dictionary::Dictionary::import(&[std::path::PathBuf::from(
+            "../assets/fixtures/jmdict_mini.xml",
+        )])
+        .unwrap();
+        let chain = interpreter_chain::InterpreterChain::new_with_interpreters(vec![
+            Box::new(interpreter_ja::InterpreterJa::new()),
+            Box::new(
+                interpreter_translate::InterpreterTranslate::new_with_endpoint(
+                    interpreter_translate::TranslateBackend::Gloss,
+                    "",
+                    None,
+                    Some(std::sync::Arc::new(dictionary)),
+                ),
+            ),
+        ]);
+        assert!(!chain.translates());
+
+        let page_image =
+            DynamicImage::ImageRgba8(ImageBuffer::from_pixel(240, 200, Rgba([0xff; 4])));
+        let (recognized_image, possible_results) = ocr_and_interpret(
+            page_image.clone(),
+            &FakeOcr::Result(ocr_result),
+            &chain,
+            &mut OCRImage::new(None),
+            &PreprocessPipeline::parse("none").unwrap(),
+            0.0,
+        );
+        let (_, translate_result) = possible_results.unwrap();
+        assert_eq!(
+            translate_result.translations,
+            ["popularity, public favor / book"]
+        );
+        let changed = |x_min: u32, x_max: u32| {
+            (x_min..x_max)
+                .flat_map(|x| (0..200).map(move |y| (x, y)))
+                .any(|(x, y)| recognized_image.get_pixel(x, y) != page_image.get_pixel(x, y))
+        };
+        assert!(changed(150, 190)); // the translation over the column
+        assert!(changed(190, 240)); // and the furigana to the right of it
+    }
+
     #[test]
     fn test_text_over_image() {
         let mut ocr_image = OCRImage::new(None);
@@ -1,8 +1,38 @@
+pub mod batch_ocr;
 pub mod cursor_data;
+pub mod deinflect;
+pub mod dictionary;
+pub mod eval_ocr;
+pub mod export_alto;
+pub mod export_hocr;
+pub mod export_mokuro;
+pub mod export_pdf;
+pub mod furigana;
+pub mod interpreter_chain;
+pub mod interpreter_command;
 pub mod interpreter_ja;
+pub mod interpreter_jmdict;
+pub mod interpreter_mecab;
+pub mod interpreter_passthrough;
+pub mod interpreter_router;
 pub mod interpreter_traits;
+pub mod interpreter_translate;
+pub mod kana;
+pub mod language_detect;
 pub mod ocr_gcloud;
 pub mod ocr_tesseract;
 pub mod ocr_traits;
+#[cfg(target_os = "windows")]
 pub mod ocr_winmedia;
+pub mod reading_order;
+pub mod registry;
+pub mod result_schema;
+pub mod screen_capture_file;
+pub mod screen_capture_traits;
+#[cfg(target_os = "windows")]
+pub mod screen_capture_win32;
+#[cfg(target_os = "linux")]
+pub mod screen_capture_x11;
+#[cfg(test)]
+pub mod test_support;
 pub mod image_handling;
\ No newline at end of file
@@ -7,44 +7,104 @@ use rusty_tesseract::Args;
 // derive from OcrTrait
 pub struct OcrTesseract {
     ocr_args: rusty_tesseract::Args,
+    language: Option<String>, // --lang (as tesseract languages), otherwise all the installed languages are used
+}
+
+// ISO 639-1 codes/BCP-47 tags to tesseract languages, anything else (i.e. "jpn_vert") is taken as is
+fn to_tesseract_language(language: &str) -> String {
+    match language {
+        "ja" => "jpn+jpn_vert",
+        "en" => "eng",
+        "zh" | "zh-Hans" | "zh-CN" => "chi_sim",
+        "zh-Hant" | "zh-TW" => "chi_tra",
+        "ko" => "kor",
+        _ => language,
+    }
+    .to_string()
 }
 
 impl OcrTesseract {
     pub fn new_with_args(ocr_args: rusty_tesseract::Args) -> Self {
-        OcrTesseract { ocr_args }
+        OcrTesseract {
+            ocr_args,
+            language: None,
+        }
     }
 
-    //fn to_ocr_rect(rect: ) -> OcrRect {
-    //}
+    // Tesseract TSV (image_to_data) levels: 1=page, 2=block, 3=paragraph, 4=line, 5=word
+    const TESSERACT_LEVEL_WORD: i32 = 5;
+    // --psm 5: Assume a single uniform block of vertically aligned text
+    const TESSERACT_PSM_SINGLE_VERTICAL_BLOCK: i32 = 5;
 
-    //fn to_ocr_word(line_index: u16, word: ) -> ocr_traits::OcrWord {
-    //}
+    fn to_ocr_rect(data: &rusty_tesseract::Data) -> OcrRect {
+        OcrRect::from(
+            data.left,
+            data.top,
+            std::cmp::max(data.width, 0) as u32,
+            std::cmp::max(data.height, 0) as u32,
+        )
+    }
 
-    //fn to_ocr_line(
-    //    line_index: u16,
-    //    words:
-    //) -> ocr_traits::OcrLine {
-    //}
+    fn to_ocr_word(
+        block_index: u16,
+        paragraph_index: u16,
+        line_index: u16,
+        data: &rusty_tesseract::Data,
+    ) -> ocr_traits::OcrWord {
+        // Tesseract reports confidence as 0..100 (and -1 for non-word rows), we normalize it to 0.0..=1.0
+        let confidence = if data.conf >= 0.0 {
+            Some((data.conf / 100.0).clamp(0.0, 1.0))
+        } else {
+            None
+        };
+        ocr_traits::OcrWord::new_with_layout(
+            data.text.clone(),
+            block_index,
+            paragraph_index,
+            line_index,
+            Self::to_ocr_rect(data),
+            confidence,
+        )
+    }
 
-    //fn to_ocr_lines(
-    //    lines:
-    //) -> Vec<ocr_traits::OcrLine> {
-    //}
-    fn to_ocr_lines(lines: Vec<String>) -> Vec<ocr_traits::OcrLine> {
-        lines
+    // Tesseract numbers block_num per page, par_num per block and line_num per paragraph, so we
+    // group the word-level rows by (page, block, par, line) in the order Tesseract emitted them and
+    // re-number them page-global so that they match how OcrWinMedia::to_ocr_lines() indexes lines
+    fn to_ocr_lines(data: &[rusty_tesseract::Data]) -> Vec<ocr_traits::OcrLine> {
+        let mut ocr_lines: Vec<ocr_traits::OcrLine> = Vec::new();
+        let mut last_line_key: Option<(i32, i32, i32, i32)> = None;
+        let mut last_block_key: Option<(i32, i32)> = None;
+        let mut last_paragraph_key: Option<(i32, i32, i32)> = None;
+        let mut block_index: u16 = 0;
+        let mut paragraph_index: u16 = 0;
+        for word in data
             .iter()
-            .enumerate()
-            .map(|(i, line)| {
-                let words = line
-                    .split_whitespace()
-                    .map(|word| {
-                        let rect = OcrRect::new(0, 0, 8, 8);
-                        ocr_traits::OcrWord::new(word.to_string(), i as u16, rect)
-                    })
-                    .collect();
-                ocr_traits::OcrLine::new(words)
-            })
-            .collect()
+            .filter(|d| d.level == Self::TESSERACT_LEVEL_WORD && !d.text.trim().is_empty())
+        {
+            let block_key = (word.page_num, word.block_num);
+            if last_block_key.is_some() && last_block_key != Some(block_key) {
+                block_index += 1;
+            }
+            let paragraph_key = (word.page_num, word.block_num, word.par_num);
+            if last_paragraph_key.is_some() && last_paragraph_key != Some(paragraph_key) {
+                paragraph_index += 1;
+            }
+            let line_key = (word.page_num, word.block_num, word.par_num, word.line_num);
+            if last_line_key != Some(line_key) {
+                ocr_lines.push(ocr_traits::OcrLine::new(vec![]));
+            }
+            let line_index = (ocr_lines.len() - 1) as u16;
+            ocr_lines.last_mut().unwrap().add_word(Self::to_ocr_word(
+                block_index,
+                paragraph_index,
+                line_index,
+                word,
+            ));
+            last_block_key = Some(block_key);
+            last_paragraph_key = Some(paragraph_key);
+            last_line_key = Some(line_key);
+        }
+        ocr_lines
     }
 }
 
@@ -56,39 +116,60 @@ impl OcrTrait for OcrTesseract {
         OcrTesseract {
             ocr_args: Args {
                 lang: "jpn+jp_vert+osd".into(),
-                psm: Some(5), // the best we can do that is closest on jpn_vert
+                psm: Some(Self::TESSERACT_PSM_SINGLE_VERTICAL_BLOCK), // the best we can do that is closest on jpn_vert
                 ..rusty_tesseract::Args::default()
             },
+            language: None,
         }
     }
 
+    fn name(&self) -> &'static str {
+        "tesseract"
+    }
+
     fn init(&self) -> Vec<String> {
+        // no languages (rather than a panic) if tesseract is missing, see registry.rs for the backend that is
+        // explicitly asked for
         //tesseract version
-        let tesseract_version = rusty_tesseract::get_tesseract_version().unwrap();
-        println!("Tesseract - Version is: {:?}", tesseract_version);
+        let tesseract_version = match rusty_tesseract::get_tesseract_version() {
+            Ok(tesseract_version) => tesseract_version,
+            Err(e) => {
+                eprintln!("Tesseract - Error: {:?}", e);
+                return vec![];
+            }
+        };
+        eprintln!("Tesseract - Version is: {:?}", tesseract_version);
 
         //available languages
-        let tesseract_langs = rusty_tesseract::get_tesseract_langs().unwrap();
-        println!(
+        let tesseract_langs = rusty_tesseract::get_tesseract_langs().unwrap_or_else(|e| {
+            eprintln!("Tesseract - Error: {:?}", e);
+            vec![]
+        });
+        eprintln!(
             "Tesseract - The available languages are: {:?}",
             tesseract_langs
         );
 
         //available config parameters
-        let parameters = rusty_tesseract::get_tesseract_config_parameters().unwrap();
-        println!(
-            "Tesseract - Config parameter: {}",
-            parameters.config_parameters.first().unwrap()
-        );
+        if let Ok(parameters) = rusty_tesseract::get_tesseract_config_parameters() {
+            if let Some(parameter) = parameters.config_parameters.first() {
+                eprintln!("Tesseract - Config parameter: {}", parameter);
+            }
+        }
 
         tesseract_langs
     }
 
+    fn set_language(&mut self, language: &str) -> bool {
+        self.language = Some(to_tesseract_language(language));
+        true
+    }
+
     fn evaluate_by_paths(
         &self,
         image_path: &str,
     ) -> core::result::Result<ocr_traits::OcrTraitResult, Error> {
-        let img = rusty_tesseract::image::open(image_path).unwrap();
+        let img = rusty_tesseract::image::open(image_path)?;
         self.evaluate(&OCRImage::to_imageproc_dynamic_image(
             img.as_bytes(),
             img.width(),
@@ -105,7 +186,10 @@ impl OcrTrait for OcrTesseract {
             image.width(),
             image.height(),
         );
-        let supported_lang = rusty_tesseract::get_tesseract_langs().unwrap().join("+");
+        let supported_lang = match &self.language {
+            Some(language) => language.clone(),
+            None => rusty_tesseract::get_tesseract_langs()?.join("+"),
+        };
         // Default OEM=3 (based on what is available)
         // For Manga, PSM should be 6 in gener
         let ocr_args: rusty_tesseract::Args = Args {
@@ -117,25 +201,101 @@ impl OcrTrait for OcrTesseract {
         let start_ocr = std::time::Instant::now();
         let ocr_image: Result<rusty_tesseract::Image, rusty_tesseract::TessError> =
             rusty_tesseract::Image::from_dynamic_image(rusty_image); // from_dynamic_image(&gray_scale_image);
-        let ocr_result: Result<String, rusty_tesseract::TessError> = match ocr_image {
-            Ok(img) => rusty_tesseract::image_to_string(&img, &ocr_args),
+        let ocr_result: Result<rusty_tesseract::DataOutput, rusty_tesseract::TessError> =
+            match ocr_image {
+                // image_to_data() rather than image_to_string() so that we get the bounding boxes and confidences
+                Ok(img) => rusty_tesseract::image_to_data(&img, &ocr_args),
+                Err(e) => {
+                    eprintln!("Error: {:?}", e);
+                    return Err(e.into());
+                }
+            };
+        let total_time = start_ocr.elapsed().as_millis();
+        let ocr_data = match ocr_result {
+            Ok(data_output) => data_output,
             Err(e) => {
-                println!("Error: {:?}", e);
+                eprintln!("Error: {:?} - {} mSec", e, total_time);
                 return Err(e.into());
             }
         };
-        let total_time = start_ocr.elapsed().as_millis();
-        println!("OCR Result ({} mSec): '{:?}'", total_time, ocr_result);
-        let ocr_str = ocr_result.unwrap();
-        let x_min = 0;
-        let y_min = 0;
-        let lines = vec![ocr_str.split("\n").collect()];
-        let rect_lines: Vec<ocr_traits::OcrLine> = Self::to_ocr_lines(lines.clone());
+        // the writing direction of each line comes from the layout of its words (see OcrLine::writing_direction()),
+        // rather than from the PSM, which is only what we asked for (i.e. horizontal English under PSM 5)
+        let rect_lines: Vec<ocr_traits::OcrLine> = Self::to_ocr_lines(&ocr_data.data);
+        let lines: Vec<String> = rect_lines.iter().map(|line| line.text()).collect();
+        eprintln!("OCR Result ({} mSec): '{:?}'", total_time, lines);
         let result = OcrTraitResult {
-            text: ocr_str.clone(),
-            lines: lines.clone(),
+            text: lines.join("\n"),
+            lines,
             rects: rect_lines,
+            // tesseract does not tell us which of the traineddata it ended up using, so the best we can do is what we asked for
+            language: Some(ocr_args.lang.clone()),
+            panels: vec![],
         };
         Ok(result)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word_row(
+        block: i32,
+        par: i32,
+        line: i32,
+        word: i32,
+        x: i32,
+        conf: f32,
+        text: &str,
+    ) -> rusty_tesseract::Data {
+        rusty_tesseract::Data {
+            level: OcrTesseract::TESSERACT_LEVEL_WORD,
+            page_num: 1,
+            block_num: block,
+            par_num: par,
+            line_num: line,
+            word_num: word,
+            left: x,
+            top: 10,
+            width: 20,
+            height: 30,
+            conf,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_to_ocr_lines_groups_words_by_line() {
+        // line_num restarts per paragraph, so block 2 line 1 must become page-global line index 2
+        let data = vec![
+            word_row(1, 1, 1, 1, 0, 96.5, "最近"),
+            word_row(1, 1, 1, 2, 20, 80.0, "人"),
+            word_row(1, 1, 2, 1, 0, 50.0, "デス"),
+            word_row(1, 1, 2, 2, 20, -1.0, " "), // whitespace-only words are dropped
+            word_row(2, 1, 1, 1, 100, 90.0, "リナ"),
+        ];
+        let lines = OcrTesseract::to_ocr_lines(&data);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].words().len(), 2);
+        assert_eq!(lines[0].text(), "最近人");
+        assert_eq!(lines[0].rect(), OcrRect::new(0, 10, 40, 40));
+        assert_eq!(lines[1].line_index(), 1);
+        assert_eq!(lines[2].line_index(), 2);
+        assert_eq!(lines[2].block_index(), 1);
+        assert_eq!(lines[2].words()[0].rect(), OcrRect::from(100, 10, 20, 30));
+        assert_eq!(lines[0].words()[0].confidence(), Some(0.965));
+
+        // the writing direction comes from how the words are laid out, whatever the PSM
+        assert_eq!(
+            lines[0].writing_direction(),
+            ocr_traits::OcrWritingDirection::HorizontalLtr
+        );
+        let mut below = word_row(1, 1, 1, 2, 0, 90.0, "人気");
+        below.top = 40;
+        let column = OcrTesseract::to_ocr_lines(&[word_row(1, 1, 1, 1, 0, 90.0, "最近"), below]);
+        assert_eq!(
+            column[0].writing_direction(),
+            ocr_traits::OcrWritingDirection::VerticalTtbRtl
+        );
+    }
+}
@@ -1,5 +1,6 @@
 use anyhow::Error; // the most easiest way to handle errors
 use core::result::Result;
+use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
     fmt::{self, Display, Formatter},
@@ -12,16 +13,25 @@ pub trait OcrTrait {
     where
         Self: Sized;
 
+    // short (stable) name of the backend (i.e. "tesseract"), which is recorded in the results (see result_schema.rs)
+    fn name(&self) -> &'static str;
+
     // returns array of Strings of supported languages
     fn init(&self) -> Vec<String>;
 
+    // language of the text to recognize (--lang, i.e. "ja", "en", or a name of the backend such as "jpn_vert"),
+    // returns false if the backend does not support choosing it
+    fn set_language(&mut self, _language: &str) -> bool {
+        false
+    }
+
     fn evaluate_by_paths(&self, image_path: &str) -> Result<OcrTraitResult, Error>;
 
     fn evaluate(&self, image: &image::DynamicImage) -> Result<OcrTraitResult, Error>;
 }
 
 //
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub(crate) struct OcrRect {
     // NOTE: Because we do not know whether the coordinates are based on text poistion or pixel position,
     // we have to deal with it in signed-integer because if it is in pixels, it can be negative  based on
@@ -54,21 +64,86 @@ impl OcrRect {
     pub fn height(&self) -> u32 {
         (self.y_max - self.y_min) as u32 // TODO: make sure we do not have a negative height!
     }
+    // smallest rectangle that contains both rectangles
+    pub fn union(&self, other: &OcrRect) -> OcrRect {
+        OcrRect::new(
+            std::cmp::min(self.x_min, other.x_min),
+            std::cmp::min(self.y_min, other.y_min),
+            std::cmp::max(self.x_max, other.x_max),
+            std::cmp::max(self.y_max, other.y_max),
+        )
+    }
+    // same rectangle moved by (dx, dy), i.e. from the coordinates of a cropped image back to the whole image
+    pub fn translate(&self, dx: i32, dy: i32) -> OcrRect {
+        OcrRect::new(
+            self.x_min + dx,
+            self.y_min + dy,
+            self.x_max + dx,
+            self.y_max + dy,
+        )
+    }
+    // intersection over union, 0.0 if they do not overlap (or either is empty)
+    pub fn iou(&self, other: &OcrRect) -> f32 {
+        let intersection_width =
+            std::cmp::min(self.x_max, other.x_max) - std::cmp::max(self.x_min, other.x_min);
+        let intersection_height =
+            std::cmp::min(self.y_max, other.y_max) - std::cmp::max(self.y_min, other.y_min);
+        if intersection_width <= 0 || intersection_height <= 0 {
+            return 0.0;
+        }
+        let intersection = intersection_width as f32 * intersection_height as f32;
+        let area = |rect: &OcrRect| rect.width() as f32 * rect.height() as f32;
+        intersection / (area(self) + area(other) - intersection)
+    }
+}
+
+// Direction in which characters (of a line) flow; note that this is per-line, because on manga (and in general,
+// Japanese) it is common to have vertical (tategaki) and horizontal (yokogaki) lines on the same page
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")] // "horizontal_ltr" and "vertical_ttb_rtl" in the JSON results
+pub(crate) enum OcrWritingDirection {
+    HorizontalLtr,  // yokogaki: characters left-to-right, lines top-to-bottom
+    VerticalTtbRtl, // tategaki: characters top-to-bottom, lines (columns) right-to-left
 }
 
 // A word is a collection (one or more) of characters and its position
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+// NOTE: block/paragraph/line indices are page-global (i.e. line_index does not restart per paragraph),
+// and confidence is normalized to 0.0..=1.0 (None when the OCR engine does not report it, i.e. Windows.Media.Ocr)
+#[derive(Debug, Clone, PartialEq)]
 pub(crate) struct OcrWord {
     word: String,
+    block_index: u16,
+    paragraph_index: u16,
     line_index: u16,
     rect: OcrRect,
+    confidence: Option<f32>,
 }
 impl OcrWord {
     pub fn new(word: String, line_index: u16, rect: OcrRect) -> Self {
         OcrWord {
             word,
+            block_index: 0,
+            paragraph_index: 0,
+            line_index,
+            rect,
+            confidence: None,
+        }
+    }
+    pub fn new_with_layout(
+        word: String,
+        block_index: u16,
+        paragraph_index: u16,
+        line_index: u16,
+        rect: OcrRect,
+        confidence: Option<f32>,
+    ) -> Self {
+        OcrWord {
+            word,
+            block_index,
+            paragraph_index,
             line_index,
             rect,
+            confidence,
         }
     }
     pub fn from(
@@ -79,11 +154,7 @@ impl OcrWord {
         width: u32,
         height: u32,
     ) -> Self {
-        OcrWord {
-            word,
-            line_index,
-            rect: OcrRect::from(x_min, y_min, width, height),
-        }
+        OcrWord::new(word, line_index, OcrRect::from(x_min, y_min, width, height))
     }
     pub fn width(&self) -> u32 {
         self.rect.width()
@@ -100,22 +171,43 @@ impl OcrWord {
     pub fn rect(&self) -> OcrRect {
         self.rect
     }
+    pub fn block_index(&self) -> u16 {
+        self.block_index
+    }
+    pub fn paragraph_index(&self) -> u16 {
+        self.paragraph_index
+    }
     pub fn line_index(&self) -> u16 {
         self.line_index
     }
+    pub fn confidence(&self) -> Option<f32> {
+        self.confidence
+    }
     pub fn word(&self) -> String {
         self.word.clone()
     }
 }
 
 // a line is a collection (one or more) of words
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq)]
 pub(crate) struct OcrLine {
     line: Vec<OcrWord>,
+    writing_direction: Option<OcrWritingDirection>, // None means the OCR engine did not tell us, so we'll guess based off of the word rects
+    angle: Option<f32>, // rotation (in degrees, clockwise) of the text if the OCR engine reports it
 }
 impl OcrLine {
     pub fn new(words: Vec<OcrWord>) -> Self {
-        OcrLine { line: words }
+        OcrLine {
+            line: words,
+            writing_direction: None,
+            angle: None,
+        }
+    }
+    pub fn set_writing_direction(&mut self, writing_direction: OcrWritingDirection) {
+        self.writing_direction = Some(writing_direction);
+    }
+    pub fn set_angle(&mut self, angle: f32) {
+        self.angle = Some(angle);
     }
     pub fn add_word(&mut self, word: OcrWord) {
         self.line.push(word);
@@ -146,13 +238,103 @@ impl OcrLine {
             .iter()
             .fold(std::i32::MAX, |acc, word| std::cmp::min(acc, word.y_min()))
     }
+    // bounding rectangle (union) of all the words in the line; unlike width()/height() which are
+    // based on the largest word, this is the actual area on the image that the line occupies
+    pub fn rect(&self) -> OcrRect {
+        if self.line.is_empty() {
+            return OcrRect::new(0, 0, 0, 0);
+        }
+        self.line
+            .iter()
+            .skip(1)
+            .fold(self.line[0].rect(), |acc, word| acc.union(&word.rect()))
+    }
+    // words joined back into a line; Japanese (or any non-ASCII) words are not space separated, but words
+    // such as "ubuntu" next to each other are
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        for word in self.line.iter() {
+            let word_text = word.word();
+            let needs_space = match (text.chars().last(), word_text.chars().next()) {
+                (Some(last), Some(first)) => {
+                    last.is_ascii_alphanumeric() && first.is_ascii_alphanumeric()
+                }
+                _ => false,
+            };
+            if needs_space {
+                text.push(' ');
+            }
+            text.push_str(&word_text);
+        }
+        text
+    }
+    // all words in a line share the same block/paragraph/line, so just peek at the first word
+    pub fn block_index(&self) -> u16 {
+        self.line
+            .first()
+            .map(|word| word.block_index())
+            .unwrap_or(0)
+    }
+    pub fn paragraph_index(&self) -> u16 {
+        self.line
+            .first()
+            .map(|word| word.paragraph_index())
+            .unwrap_or(0)
+    }
+    pub fn line_index(&self) -> u16 {
+        self.line.first().map(|word| word.line_index()).unwrap_or(0)
+    }
+    // average of the words that have confidence (None if none of the words have confidence)
+    pub fn confidence(&self) -> Option<f32> {
+        let confidences: Vec<f32> = self
+            .line
+            .iter()
+            .filter_map(|word| word.confidence())
+            .collect();
+        if confidences.is_empty() {
+            return None;
+        }
+        Some(confidences.iter().sum::<f32>() / confidences.len() as f32)
+    }
+    pub fn angle(&self) -> Option<f32> {
+        self.angle
+    }
+    // if the OCR engine did not report the direction, we'll determine it by how the words are laid out:
+    // if the words progress more downwards than sideways it's vertical, and for single word lines, we'll
+    // see if the word is much taller than it is wide (a single CJK character is square, so that is horizontal)
+    pub fn writing_direction(&self) -> OcrWritingDirection {
+        if let Some(writing_direction) = self.writing_direction {
+            return writing_direction;
+        }
+        let is_vertical = match (self.line.first(), self.line.last()) {
+            (Some(first), Some(last)) if self.line.len() > 1 => {
+                let (first_rect, last_rect) = (first.rect(), last.rect());
+                let dx = ((last_rect.x_min + last_rect.x_max)
+                    - (first_rect.x_min + first_rect.x_max))
+                    .abs();
+                let dy = ((last_rect.y_min + last_rect.y_max)
+                    - (first_rect.y_min + first_rect.y_max))
+                    .abs();
+                dy > dx
+            }
+            (Some(word), _) => word.height() as f32 > word.width() as f32 * 1.5,
+            _ => false,
+        };
+        if is_vertical {
+            OcrWritingDirection::VerticalTtbRtl
+        } else {
+            OcrWritingDirection::HorizontalLtr
+        }
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub(crate) struct OcrTraitResult {
-    pub text: String,        // entier text split via newlines (built from lines)
+    pub text: String,             // entier text split via newlines (built from lines)
     pub lines: Vec<String>, // each line of text (collection of words), sequentially ordered (up to OCR whether it is horizontal:left-to-right, or vertical:top-to-bottom-left-to-right )
     pub rects: Vec<OcrLine>, // for each (rectangle) block of text (collection of words, see lines)
+    pub language: Option<String>, // language/script the OCR engine recognized with (i.e. "ja", "jpn_vert"), if known
+    pub panels: Vec<OcrRect>, // manga panels in reading order if the page was OCR'ed panel by panel, otherwise empty
 }
 
 impl Display for OcrTraitResult {
@@ -176,6 +358,230 @@ impl OcrTraitResult {
             text: "".to_string(),
             lines: vec![],
             rects: vec![],
+            language: None,
+            panels: vec![],
+        }
+    }
+
+    // average confidence of all the lines that have confidence (None if the OCR engine does not report confidence)
+    pub fn confidence(&self) -> Option<f32> {
+        let confidences: Vec<f32> = self
+            .rects
+            .iter()
+            .filter_map(|line| line.confidence())
+            .collect();
+        if confidences.is_empty() {
+            return None;
+        }
+        Some(confidences.iter().sum::<f32>() / confidences.len() as f32)
+    }
+
+    // the writing direction the majority of the lines are in (ties goes to vertical since we are mainly after manga)
+    pub fn writing_direction(&self) -> OcrWritingDirection {
+        let vertical_count = self
+            .rects
+            .iter()
+            .filter(|line| line.writing_direction() == OcrWritingDirection::VerticalTtbRtl)
+            .count();
+        if vertical_count > 0 && vertical_count * 2 >= self.rects.len() {
+            OcrWritingDirection::VerticalTtbRtl
+        } else {
+            OcrWritingDirection::HorizontalLtr
         }
     }
+
+    // lines grouped by block, and then by paragraph within the block, as consecutive runs in the order the OCR
+    // engine reported them (i.e. for hOCR/ALTO, which nests lines in blocks and paragraphs)
+    pub fn blocks(&self) -> Vec<Vec<Vec<&OcrLine>>> {
+        let mut blocks: Vec<Vec<Vec<&OcrLine>>> = Vec::new();
+        let mut previous: Option<(u16, u16)> = None;
+        for line in self.rects.iter() {
+            let current = (line.block_index(), line.paragraph_index());
+            match previous {
+                Some((block_index, _)) if block_index != current.0 => blocks.push(vec![vec![line]]),
+                Some((_, paragraph_index)) if paragraph_index != current.1 => {
+                    blocks.last_mut().unwrap().push(vec![line])
+                }
+                Some(_) => blocks.last_mut().unwrap().last_mut().unwrap().push(line),
+                None => blocks.push(vec![vec![line]]),
+            }
+            previous = Some(current);
+        }
+        blocks
+    }
+
+    // appends the result of evaluating a crop (whose upper left corner is at (x, y) of this image), so that the
+    // rects are in the coordinates of this image, and block/paragraph/line indices stay page-global (i.e. each
+    // crop's indices come after the ones already appended)
+    pub fn append_at(&mut self, other: OcrTraitResult, x: i32, y: i32) {
+        let next_index = |index_of: fn(&OcrLine) -> u16| {
+            self.rects
+                .iter()
+                .map(|line| index_of(line) + 1)
+                .max()
+                .unwrap_or(0)
+        };
+        let block_offset = next_index(OcrLine::block_index);
+        let paragraph_offset = next_index(OcrLine::paragraph_index);
+        let line_offset = next_index(OcrLine::line_index);
+        let is_one_to_one = other.lines.len() == other.rects.len();
+        for (index, line) in other.rects.iter().enumerate() {
+            let mut translated_line = OcrLine::new(
+                line.words()
+                    .iter()
+                    .map(|word| {
+                        OcrWord::new_with_layout(
+                            word.word(),
+                            block_offset + word.block_index(),
+                            paragraph_offset + word.paragraph_index(),
+                            line_offset + word.line_index(),
+                            word.rect().translate(x, y),
+                            word.confidence(),
+                        )
+                    })
+                    .collect(),
+            );
+            translated_line.set_writing_direction(line.writing_direction());
+            if let Some(angle) = line.angle() {
+                translated_line.set_angle(angle);
+            }
+            self.lines.push(if is_one_to_one {
+                other.lines[index].clone()
+            } else {
+                line.text()
+            });
+            self.rects.push(translated_line);
+        }
+        self.text = self.lines.join("\n");
+        if self.language.is_none() {
+            self.language = other.language;
+        }
+        self.panels
+            .extend(other.panels.iter().map(|panel| panel.translate(x, y)));
+    }
+
+    // same result with all the (word) rects mapped, i.e. back to the coordinates of the image before it was
+    // scaled/rotated for the OCR engine
+    pub fn map_rects(self, map: impl Fn(&OcrRect) -> OcrRect) -> Self {
+        let rects = self
+            .rects
+            .iter()
+            .map(|line| {
+                let mut mapped_line = OcrLine::new(
+                    line.words()
+                        .iter()
+                        .map(|word| {
+                            OcrWord::new_with_layout(
+                                word.word(),
+                                word.block_index(),
+                                word.paragraph_index(),
+                                word.line_index(),
+                                map(&word.rect()),
+                                word.confidence(),
+                            )
+                        })
+                        .collect(),
+                );
+                // the direction is from the original rects, since it may not be guessable from the mapped ones
+                mapped_line.set_writing_direction(line.writing_direction());
+                if let Some(angle) = line.angle() {
+                    mapped_line.set_angle(angle);
+                }
+                mapped_line
+            })
+            .collect();
+        let panels = self.panels.iter().map(&map).collect();
+        OcrTraitResult {
+            rects,
+            panels,
+            ..self
+        }
+    }
+
+    // drops lines which the OCR engine is not confident about (i.e. Tesseract reading screentones as "NN", "SS", etc)
+    // lines without confidence (i.e. Windows.Media.Ocr) are always kept since we cannot tell either way
+    pub fn filter_by_confidence(self, min_confidence: f32) -> Self {
+        let is_kept = |line: &OcrLine| match line.confidence() {
+            Some(confidence) => confidence >= min_confidence,
+            None => true,
+        };
+        // lines and rects are one-to-one on all the OCR backends, but in case they are not, we'll leave lines alone
+        let lines: Vec<String> = if self.lines.len() == self.rects.len() {
+            self.lines
+                .into_iter()
+                .zip(self.rects.iter())
+                .filter(|(_, rect)| is_kept(rect))
+                .map(|(line, _)| line)
+                .collect()
+        } else {
+            self.lines
+        };
+        let rects: Vec<OcrLine> = self
+            .rects
+            .into_iter()
+            .filter(|line| is_kept(line))
+            .collect();
+        OcrTraitResult {
+            text: lines.join("\n"),
+            lines,
+            rects,
+            language: self.language,
+            panels: self.panels,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line_of(words: &[(&str, i32, i32, Option<f32>)], line_index: u16) -> OcrLine {
+        OcrLine::new(
+            words
+                .iter()
+                .map(|(word, x, y, confidence)| {
+                    OcrWord::new_with_layout(
+                        word.to_string(),
+                        0,
+                        0,
+                        line_index,
+                        OcrRect::from(*x, *y, 40, 40),
+                        *confidence,
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_filter_by_confidence_and_writing_direction() {
+        // a vertical column (words stacked downwards), a horizontal garbage line, and a line without confidence
+        let vertical = line_of(
+            &[("最近", 300, 0, Some(0.9)), ("人気", 300, 40, Some(0.8))],
+            0,
+        );
+        let garbage = line_of(&[("NN", 0, 300, Some(0.1)), ("SS", 40, 300, Some(0.2))], 1);
+        let unknown = line_of(&[("です", 0, 400, None), ("!", 40, 400, None)], 2);
+        assert_eq!(
+            vertical.writing_direction(),
+            OcrWritingDirection::VerticalTtbRtl
+        );
+        assert_eq!(
+            garbage.writing_direction(),
+            OcrWritingDirection::HorizontalLtr
+        );
+
+        let result = OcrTraitResult {
+            text: "最近人気\nNNSS\nです!".to_string(),
+            lines: vec!["最近人気".into(), "NNSS".into(), "です!".into()],
+            rects: vec![vertical, garbage, unknown],
+            language: Some("ja".to_string()),
+            panels: vec![],
+        };
+        let filtered = result.filter_by_confidence(0.3);
+        assert_eq!(filtered.lines, vec!["最近人気", "です!"]);
+        assert_eq!(filtered.text, "最近人気\nです!");
+        assert_eq!(filtered.rects.len(), 2);
+        assert!((filtered.confidence().unwrap() - 0.85).abs() < 0.0001);
+    }
 }
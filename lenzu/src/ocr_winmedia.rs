@@ -40,6 +40,10 @@ impl OcrTrait for OcrWinMedia {
         }
     }
 
+    fn name(&self) -> &'static str {
+        "winmedia"
+    }
+
     fn init(&self) -> Vec<String> {
         let mut langs = Vec::new();
         langs.push(JAPANESE_LANGUAGE.to_string());
@@ -51,6 +55,17 @@ impl OcrTrait for OcrWinMedia {
         langs
     }
 
+    // Windows wants a BCP-47 language tag (i.e. "ja", "en-US"), which has to be installed in the profile
+    fn set_language(&mut self, language: &str) -> bool {
+        match Language::CreateLanguage(&HSTRING::from(language)) {
+            Ok(language) if OcrEngine::IsLanguageSupported(&language).unwrap_or(false) => {
+                self.language = language;
+                true
+            }
+            _ => false,
+        }
+    }
+
     fn evaluate_by_paths(
         &self,
         image_path: &str,
@@ -99,7 +114,7 @@ impl OcrTrait for OcrWinMedia {
                         match eval_result {
                             Ok(s) => Ok(s),
                             Err(e) => {
-                                println!("#================= Error: {:?}\n\n", e.to_string());
+                                eprintln!("#================= Error: {:?}\n\n", e.to_string());
                                 Err(e.into())
                             }
                         }
@@ -108,7 +123,7 @@ impl OcrTrait for OcrWinMedia {
                 }
             }
             Err(e) => {
-                println!("Error: {:?}", e);
+                eprintln!("Error: {:?}", e);
                 return Err(e.into());
             }
         }
@@ -179,7 +194,7 @@ impl OcrWinMedia {
         // for windows, replace all occurances of '/' with "\\"
         // see 'https://learn.microsoft.com/en-us/uwp/api/windows.storage.storagefile.getfilefrompathasync' for more details
         if cfg!(target_os = "windows") {
-            println!("Windows: Evaluating '{:?}' for forward-slashes", png_paths);
+            eprintln!("Windows: Evaluating '{:?}' for forward-slashes", png_paths);
             for c in png_paths.chars() {
                 if c == '/' {
                     arg_image_path.push_str("\\");
@@ -188,7 +203,7 @@ impl OcrWinMedia {
                 }
             }
         } else {
-            println!("Linux: Evaluating '{:?}' for back-slashes", png_paths);
+            eprintln!("Linux: Evaluating '{:?}' for back-slashes", png_paths);
             // for linux, replace all occurances of '\' with "/"
             for c in png_paths.chars() {
                 if c == '\\' {
@@ -219,7 +234,7 @@ impl OcrWinMedia {
         match storage_file_result {
             Ok(storage_file_operation) => {
                 // verify file exists
-                println!("get_filestream(): Verifying if absolute file paths '{}' exists and/or valid...",
+                eprintln!("get_filestream(): Verifying if absolute file paths '{}' exists and/or valid...",
                     absolute_filepaths_buf.to_str().unwrap()
                 );
                 let storage_file_op_result = storage_file_operation.await;
@@ -229,27 +244,27 @@ impl OcrWinMedia {
                         let open_result = storage_file.OpenAsync(FileAccessMode::Read);
                         match open_result {
                             Ok(open_operation) => {
-                                println!("get_filestream(): Opening file...");
+                                eprintln!("get_filestream(): Opening file...");
                                 let open_file_stream_result = open_operation.await;
                                 match open_file_stream_result {
                                     Ok(file_stream) => {
-                                        println!("get_filestream(): File opened...");
+                                        eprintln!("get_filestream(): File opened...");
                                         Ok(file_stream)
                                     }
                                     Err(e) => {
-                                        println!("Error (get_filestream(match: open_file_stream_result)): Failed to OpenAsync() - {:?}", e);
+                                        eprintln!("Error (get_filestream(match: open_file_stream_result)): Failed to OpenAsync() - {:?}", e);
                                         Err(e.into())
                                     }
                                 }
                             }
                             Err(e) => {
-                                println!("Error (get_filestream(match: open_result)): {:?}", e);
+                                eprintln!("Error (get_filestream(match: open_result)): {:?}", e);
                                 return Err(e.into());
                             }
                         }
                     }
                     Err(e) => {
-                        println!(
+                        eprintln!(
                             "Error (get_filestream(match: storage_file_op_result)): {:?}",
                             e
                         );
@@ -258,7 +273,7 @@ impl OcrWinMedia {
                 }
             }
             Err(e) => {
-                println!(
+                eprintln!(
                     "Error (get_filestream(match: storage_file)): {:?} - paths: '{}",
                     e,
                     absolute_filepaths_buf.to_str().unwrap()
@@ -283,7 +298,7 @@ impl OcrWinMedia {
             panic!("Error: stream_size is 0");
         }
 
-        println!(
+        eprintln!(
             "fstream_to_memstream(): Stream size: {} bytes to be allocated to memory buffer...",
             stream_size
         );
@@ -297,7 +312,7 @@ impl OcrWinMedia {
             .expect(format!("Failed to load {} bytes from fstream", stream_size).as_str())
             .await
             .expect(format!("Failed to load {} bytes from fstream", stream_size).as_str());
-        println!(
+        eprintln!(
             "fstream_to_memstream(): Buffer loaded {} bytes (out of {} bytes)...",
             bytes_read, stream_size
         );
@@ -322,24 +337,24 @@ impl OcrWinMedia {
             .await
             .expect(format!("Failed to transform data to InMemoryRandomAccessStream from {} bytes of vec/array data", buffer_length).as_str());
 
-        println!("fstream_to_memstream(): Memory Stream creation succeeded...");
+        eprintln!("fstream_to_memstream(): Memory Stream creation succeeded...");
         let initial_position = in_memory_stream.Position();
         let initial_seek_result = in_memory_stream.Seek(0);
         let stream_size = in_memory_stream.Size();
         let post_size_postion = in_memory_stream.Position();
         let seek_result = in_memory_stream.Seek(0);
         let post_seek_position = in_memory_stream.Position();
-        println!(
+        eprintln!(
             "fstream_to_memstream(): Initial position: {:?}, initial Seek result: {:?}",
             initial_position,
             initial_seek_result.is_ok(),
         );
 
-        println!(
+        eprintln!(
             "fstream_to_memstream(): Stream size: {:?} bytes, position: {:?}",
             stream_size, post_size_postion,
         );
-        println!(
+        eprintln!(
             "fstream_to_memstream(): Seek result: Ok{:?}, Stream seek position: {:?}",
             seek_result.is_ok(),
             post_seek_position,
@@ -347,7 +362,7 @@ impl OcrWinMedia {
         if initial_position.is_err() {
             panic!("Error: '{:?}'", initial_position)
         }
-        println!("fstream_to_memstream(): >> Stream created...");
+        eprintln!("fstream_to_memstream(): >> Stream created...");
 
         return Ok(in_memory_stream);
     }
@@ -367,7 +382,7 @@ impl OcrWinMedia {
         let stream_size = in_memory_stream
             .Size()
             .expect(format!("Failed to get Size() of in_memory_stream").as_str());
-        println!(
+        eprintln!(
             "copy_stream_to_vec(): Stream size: {} bytes (allocating Vec<u8> of this size)",
             stream_size
         );
@@ -388,7 +403,7 @@ impl OcrWinMedia {
             ret_buffer_vec.len() >= stream_size as usize,
             "Error: buffer is too small"
         );
-        println!(
+        eprintln!(
             "\ncopy_stream_to_vec(): Reading {} bytes from in_memory_stream via DataReader...",
             stream_size
         );
@@ -403,7 +418,7 @@ impl OcrWinMedia {
         //    .DetachStream()
         //    .expect(format!("Failed to detach stream from reader").as_str());
         //for index in 0..stream_size {
-        //    println!("{}", index);
+        //    eprintln!("{}", index);
         //    let byte = data_reader
         //        .ReadByte()
         //        .expect("Failed to read byte from data_reader");
@@ -450,7 +465,7 @@ impl OcrWinMedia {
         from_buffer_reader
             .ReadBytes(&mut ret_buffer_vec)
             .expect(format!("Failed to read {} bytes from in_memory_stream", stream_size).as_str());
-        println!(
+        eprintln!(
             "\ncopy_stream_to_vec(): Reading done...  Bytes read: {} bytes\n\n",
             async_bytes_cached
         );
@@ -465,7 +480,7 @@ impl OcrWinMedia {
     }
 
     async fn dump_stream_to_png(in_memory_stream: &InMemoryRandomAccessStream, filename: &str) {
-        println!("\n######################## Dumping to '{}'", filename);
+        eprintln!("\n######################## Dumping to '{}'", filename);
         // first, check if stream has been closed, and if so, panic
         if in_memory_stream.CanRead().unwrap() == false {
             panic!("Error: stream is closed");
@@ -474,7 +489,7 @@ impl OcrWinMedia {
         let pos = match pos_result {
             Ok(p) => {
                 if cfg!(debug_assertions) {
-                    println!("DEBUG: dump_stream_to_png(): Stream position: {:?}", p);
+                    eprintln!("DEBUG: dump_stream_to_png(): Stream position: {:?}", p);
                 }
                 p
             }
@@ -485,10 +500,10 @@ impl OcrWinMedia {
             }
         };
         if cfg!(debug_assertions) {
-            println!("DEBUG: dump_stream_to_png(): Stream position: {:?}", pos);
+            eprintln!("DEBUG: dump_stream_to_png(): Stream position: {:?}", pos);
             in_memory_stream.Seek(pos).unwrap(); // interesting to find out if pos is not 0, what the Size would be...
             let debug_stream_size = in_memory_stream.Size().unwrap(); // need to re-seek position once size is read
-            println!(
+            eprintln!(
                 "DEBUG: dump_stream_to_png(): Stream size: {:?} bytes, position: {:?}",
                 debug_stream_size, pos
             );
@@ -496,7 +511,7 @@ impl OcrWinMedia {
         in_memory_stream.Seek(0).unwrap(); // I think I have to force seek to 0, because Position() is equal to Size() (which is the end of the stream)
         let stream_size = in_memory_stream.Size().unwrap(); // need to re-seek position once size is read
 
-        println!("dump_stream_to_png(): Stream size: {:?} bytes, position: {:?} - begin creating DataReader..." , stream_size, pos);
+        eprintln!("dump_stream_to_png(): Stream size: {:?} bytes, position: {:?} - begin creating DataReader..." , stream_size, pos);
         let data_from_stream = Self::copy_stream_to_vec(in_memory_stream).await;
         // Now `data` is a Vec<u8> that you can use as a byte slice
         let data_slice = &data_from_stream[..];
@@ -509,10 +524,10 @@ impl OcrWinMedia {
 
         let img = imageproc::image::load_from_memory(&data_slice).unwrap();
         img.save(filename).unwrap();
-        println!(">> Dumped done...");
+        eprintln!(">> Dumped done...");
 
         if in_memory_stream.Position().unwrap() != 0 {
-            println!(">>> Resetting stream position to 0...");
+            eprintln!(">>> Resetting stream position to 0...");
             in_memory_stream
                 .Seek(0)
                 .expect(format!("Failed to seek to 0 in in_memory_stream").as_str());
@@ -520,12 +535,12 @@ impl OcrWinMedia {
 
         // just in case, make sure stream is still valid/open, maybe DataReader may have implicitly closed it?
         if cfg!(debug_assertions) {
-            println!(
+            eprintln!(
                 "DEBUG: dump_stream_to_png(): Stream position: {:?}\n\n",
                 in_memory_stream.Position().unwrap(), // it should panic if stream is closed...
             );
         }
-        println!("########################\n\n");
+        eprintln!("########################\n\n");
     }
 
     async fn slice_to_memstream(&self, slice: &[u8]) -> Result<InMemoryRandomAccessStream> {
@@ -533,7 +548,7 @@ impl OcrWinMedia {
         let in_memory_stream = match in_memory_stream_result {
             Ok(stream) => stream,
             Err(e) => {
-                println!("Error: {:?}", e);
+                eprintln!("Error: {:?}", e);
                 return Err(e.into());
             }
         };
@@ -541,20 +556,20 @@ impl OcrWinMedia {
         // stream (in_memory_stream) will get closed?
         let data_writer = DataWriter::CreateDataWriter(&in_memory_stream)
             .expect(format!("Failed to create DataWriter for in_memory_stream").as_str());
-        println!("slice_to_memstram(): DataWriter created...");
+        eprintln!("slice_to_memstram(): DataWriter created...");
         let _ = data_writer
             .WriteBytes(slice)
             .expect(format!("Failed to write slice to DataWriter").as_str());
 
-        println!("slice_to_memstram(): Data written...");
+        eprintln!("slice_to_memstram(): Data written...");
         let _ = match data_writer.FlushAsync() {
             Ok(flush_operation) => match flush_operation.await {
                 Ok(flushed) => {
-                    println!("slice_to_memstram(): Flushed: {:?}", flushed);
+                    eprintln!("slice_to_memstram(): Flushed: {:?}", flushed);
                 }
-                Err(e) => println!("FlushOp failed: {:?}", e),
+                Err(e) => eprintln!("FlushOp failed: {:?}", e),
             },
-            Err(e) => println!("FlushAsync failed: {:?}", e),
+            Err(e) => eprintln!("FlushAsync failed: {:?}", e),
         };
 
         let bytes_written = data_writer
@@ -566,18 +581,18 @@ impl OcrWinMedia {
         let _ = match data_writer.FlushAsync() {
             Ok(flush_operation) => match flush_operation.await {
                 Ok(flushed) => {
-                    println!("slice_to_memstram(): Flushed: {:?}", flushed);
+                    eprintln!("slice_to_memstram(): Flushed: {:?}", flushed);
                 }
                 Err(e) => {
-                    println!("FlushOp failed: {:?}", e)
+                    eprintln!("FlushOp failed: {:?}", e)
                 }
             },
             Err(e) => {
-                println!("FlushAsync failed: {:?}", e)
+                eprintln!("FlushAsync failed: {:?}", e)
             }
         };
 
-        println!(
+        eprintln!(
             "slice_to_memstream() - Bytes written: {} (slice size: {} bytes)",
             bytes_written,
             slice.len()
@@ -594,7 +609,7 @@ impl OcrWinMedia {
             if in_memory_stream.CanRead().unwrap() == false {
                 panic!("Error: stream is closed");
             }
-            println!(
+            eprintln!(
                 "DEBUG: slice_to_memstream() - Stream size: {:?} bytes, position: {:?}",
                 in_memory_stream.Size(),
                 in_memory_stream.Position()
@@ -630,13 +645,13 @@ impl OcrWinMedia {
             Ok(_) => {
                 // dump some info  if in DEBUG build:
                 if cfg!(debug_assertions) {
-                    println!( "DEBUG: slice_to_memstream() - Seek result: Ok({:?}), Stream seek position: {:?} (Size: {:?})",
+                    eprintln!( "DEBUG: slice_to_memstream() - Seek result: Ok({:?}), Stream seek position: {:?} (Size: {:?})",
                         seek_result.is_ok(),
                         in_memory_stream.Position(),
                         in_memory_stream.Size(),
                     );
                 }
-                println!(
+                eprintln!(
                     "slice_to_memstram(): >> Memory Stream created... Position={:?}",
                     in_memory_stream.Position()
                 );
@@ -663,22 +678,22 @@ impl OcrWinMedia {
 
         match in_memory_stream.Position() {
             Ok(pos) => {
-                println!("create_decoder_with_timeout(): Stream position: {}", pos);
+                eprintln!("create_decoder_with_timeout(): Stream position: {}", pos);
                 if pos != 0 {
-                    println!("create_decoder_with_timeout(): Resetting stream position to 0...");
+                    eprintln!("create_decoder_with_timeout(): Resetting stream position to 0...");
                     in_memory_stream
                         .Seek(0)
                         .expect(format!("Failed to seek to 0 in in_memory_stream").as_str());
                 }
             }
             Err(e) => {
-                println!("Error: {:?}", e);
+                eprintln!("Error: {:?}", e);
             }
         }
 
-        println!("create_decoder_with_timeout(): Creating BitmapDecoder...");
+        eprintln!("create_decoder_with_timeout(): Creating BitmapDecoder...");
         let create_decoder_future_result = BitmapDecoder::CreateAsync(in_memory_stream);
-        println!("create_decoder_with_timeout(): Done creating BitmapDecoder...");
+        eprintln!("create_decoder_with_timeout(): Done creating BitmapDecoder...");
 
         // ######################## DEBUG BEGIN: dump some info  if in DEBUG build:
         if cfg!(debug_assertions) {
@@ -718,23 +733,23 @@ impl OcrWinMedia {
             panic!("Error: stream is closed");
         }
 
-        println!(
+        eprintln!(
             "Evaluating (OCR recognizing) for lanaguage: {:?}",
             language.LanguageTag()?
         );
         let timeout_in_seconds = 5;
         let duration: Duration = Duration::from_secs(timeout_in_seconds);
-        println!("evaluate_async(): Creating BitmapDecoder...");
+        eprintln!("evaluate_async(): Creating BitmapDecoder...");
         let create_decoder_timer_start = std::time::Instant::now();
         let decode: BitmapDecoder =
             match Self::create_decoder_with_timeout(in_memory_stream, timeout_in_seconds).await {
                 Ok(decoder) => decoder,
                 Err(e) => {
-                    println!("Error: {:?}", e);
+                    eprintln!("Error: {:?}", e);
                     return Err(e.into());
                 }
             };
-        println!(
+        eprintln!(
             "evaluate_async(): Stream created...  {} mSec",
             create_decoder_timer_start.elapsed().as_millis()
         );
@@ -744,7 +759,7 @@ impl OcrWinMedia {
                 .await
                 .unwrap()
                 .unwrap();
-        println!(
+        eprintln!(
             "evaluate_async(): Bitmap created... {} mSec",
             software_bitmap_timer_start.elapsed().as_millis()
         );
@@ -753,18 +768,18 @@ impl OcrWinMedia {
         let engine: OcrEngine = match OcrEngine::TryCreateFromLanguage(&self.language) {
             Ok(engine) => engine,
             Err(e) => {
-                println!("Error: {:?}", e);
+                eprintln!("Error: {:?}", e);
                 return Err(e.into());
             }
         };
-        println!("evaluate_async(): Engine created...");
+        eprintln!("evaluate_async(): Engine created...");
 
         let ocr_recognize_start = std::time::Instant::now();
         //let ocr_result: std::prelude::v1::Result<OcrResult, windows::core::Error> = engine.RecognizeAsync(&bitmap)?.await;
         let ocr_result = timeout(duration.clone(), engine.RecognizeAsync(&bitmap).unwrap())
             .await
             .unwrap();
-        println!(
+        eprintln!(
             "evaluate_async(): OCR took: {} mSec, result (OK?): {}",
             ocr_recognize_start.elapsed().as_millis(),
             ocr_result.is_ok()
@@ -772,12 +787,6 @@ impl OcrWinMedia {
 
         if let Ok(result) = ocr_result {
             let str_block: String = result.Text().unwrap().to_string();
-            let lines: Vec<String> = result
-                .Lines()
-                .unwrap()
-                .into_iter()
-                .map(|x| x.Text().unwrap().to_string())
-                .collect::<Vec<_>>();
             // bounding rectangles are based off of "words" (OcrWord) which Lines are collections of Words...
             // Note that we deal with it in 2 phases, first is as-is (as a tuple), then we convert it to a hashmap
             // so that if we want to make sure all keys are unique, we can  handle it (note that in Rust collection,
@@ -794,18 +803,30 @@ impl OcrWinMedia {
             //              OcrRect { x_min: 692, y_min: 238, x_max: 732, y_max: 284 }:あ   <- Line 1, 3rd character
             //              OcrRect { x_min: 631, y_min: 191, x_max: 672, y_max: 234 }:ん   <- Line 2, 2nd character
             //              OcrRect { x_min: 630, y_min: 288, x_max: 671, y_max: 331 }:わ"  <- Line 2, 4th character
-            let rects: Vec<ocr_traits::OcrLine> = Self::to_ocr_lines(&result.Lines().unwrap());
+            let mut rects: Vec<ocr_traits::OcrLine> = Self::to_ocr_lines(&result.Lines().unwrap());
+            // OcrLine::Text() separates each Japanese character with a space ("じ ゃ あ"), so the lines are joined
+            // back the same way as for the other backends
+            let lines: Vec<String> = rects.iter().map(|line| line.text()).collect();
+            // TextAngle is null (and hence Err) if the engine could not determine the angle; note that Windows.Media.Ocr
+            // does not report confidence nor the writing direction, so those are left to OcrLine to guess from the word rects
+            if let Ok(text_angle) = result.TextAngle().and_then(|angle| angle.Value()) {
+                for line in rects.iter_mut() {
+                    line.set_angle(text_angle as f32);
+                }
+            }
 
-            println!("evaluate_async():\n{}", str_block);
+            eprintln!("evaluate_async():\n{}", str_block);
             let x_min = 0;
             let y_min = 0;
             let trait_result = OcrTraitResult {
-                text: str_block,
+                text: lines.join("\n"),
                 lines: lines.clone(),
                 // convert vector or paired-tuple to hashmap
                 rects: rects,
+                language: language.LanguageTag().ok().map(|tag| tag.to_string()),
+                panels: vec![],
             };
-            println!(
+            eprintln!(
                 "evaluate_async(): Recognized text: {:?}",
                 trait_result.lines
             );
@@ -822,7 +843,7 @@ impl OcrWinMedia {
         let mut arg_image_path = String::new();
         // for windows, replace all occurances of '/' with "\\"
         if cfg!(target_os = "windows") {
-            println!("Windows: Evaluating '{:?}' for forward-slashes", png_paths);
+            eprintln!("Windows: Evaluating '{:?}' for forward-slashes", png_paths);
             for c in png_paths.chars() {
                 if c == '/' {
                     arg_image_path.push_str("\\");
@@ -831,7 +852,7 @@ impl OcrWinMedia {
                 }
             }
         } else {
-            println!("Linux: Evaluating '{:?}' for back-slashes", png_paths);
+            eprintln!("Linux: Evaluating '{:?}' for back-slashes", png_paths);
             // for linux, replace all occurances of '\' with "/"
             for c in png_paths.chars() {
                 if c == '\\' {
@@ -846,7 +867,7 @@ impl OcrWinMedia {
         let bitmap = decode.GetSoftwareBitmapAsync()?.await?;
         let engine = OcrEngine::TryCreateFromLanguage(&self.language)?; // NOTE: OcrEngine::TryCreateFromUserProfileLanguages() is unreliable, use TryCreateFromLanguage() and explicitly state what language to use
         if let Ok(result) = engine.RecognizeAsync(&bitmap)?.await {
-            println!(
+            eprintln!(
                 "OCR Success!\n### OCR BEGIN:\n{}\n### OCR END\n",
                 result.Text()?
             );
@@ -868,7 +889,7 @@ impl OcrWinMedia {
             futures::executor::block_on(self.fstream_to_memstream(&file_stream)).unwrap();
 
         // as a test, write it once with no seek(0), then write it  again, then seek(0) and write again twice
-        println!(
+        eprintln!(
             "\n###############\nDumping to test_seek_multiple_1.png - {:?}",
             in_memory_stream.Position().unwrap()
         );
@@ -876,7 +897,7 @@ impl OcrWinMedia {
             &in_memory_stream,
             "test_seek_multiple_1.png",
         ));
-        println!(
+        eprintln!(
             "\n###############\nDumping to test_seek_multiple_2.png - {:?}",
             in_memory_stream.Position().unwrap()
         );
@@ -887,7 +908,7 @@ impl OcrWinMedia {
         in_memory_stream
             .Seek(0)
             .expect(format!("Failed to seek to 0 in in_memory_stream").as_str());
-        println!(
+        eprintln!(
             "\n###############\nDumping to test_seek_multiple_3.png - {:?}",
             in_memory_stream.Position().unwrap()
         );
@@ -895,7 +916,7 @@ impl OcrWinMedia {
             &in_memory_stream,
             "test_seek_multiple_3.png",
         ));
-        println!(
+        eprintln!(
             "\n###############\nDumping to test_seek_multiple_4.png - {:?}",
             in_memory_stream.Position().unwrap()
         );
@@ -0,0 +1,362 @@
+// Reading order reconstruction, independent of the OCR backend: the order of OcrTraitResult::lines is whatever
+// the engine gives (Tesseract and Google Lens often read vertical text left-to-right), so the lines are re-sorted
+// from their rects alone:
+//  1. lines that are close to each other (within a line's thickness) and in the same writing direction make up a
+//     text block (i.e. a speech bubble)
+//  2. within a block, tategaki columns are read right-to-left (and a column that is split into several lines,
+//     top-to-bottom), and yokogaki lines top-to-bottom (and left-to-right on the same row)
+//  3. blocks are read like panels: rows (tiers) top-to-bottom, and within a row, right-to-left if the page is
+//     mostly tategaki, otherwise left-to-right
+// when the page was OCR'ed panel by panel (see image_handling::evaluate_by_panels()), the above is per panel
+// the words of each line are put in order as well, and block/paragraph/line indices are renumbered to match (each
+// block is also a paragraph, since the engines' paragraphs do not survive being reordered anyway)
+// NOTE: rows of blocks are found by vertical overlap, so a tall bubble next to two stacked small ones is one row,
+// and the stacked ones are read by their right edge (then top) rather than strictly top-to-bottom
+use crate::ocr_traits::{OcrLine, OcrRect, OcrTraitResult, OcrWord, OcrWritingDirection};
+
+// of the line thickness (width of a column, height of a row), the gap up to which lines belong to the same block
+const MAX_LINE_GAP_RATIO: f32 = 1.0;
+// of the shorter of the two, the overlap for two rects to be on the same row (or column)
+const MIN_BAND_OVERLAP_RATIO: f32 = 0.5;
+
+// groups the rects into bands by their extent on one axis (a rect joins the band it overlaps the most, if by at
+// least MIN_BAND_OVERLAP_RATIO), then orders the bands by band_key and the rects within each by rect_key (ascending)
+fn order_in_bands(
+    rects: &[OcrRect],
+    extent: impl Fn(&OcrRect) -> (i32, i32),
+    band_key: impl Fn((i32, i32)) -> i32,
+    rect_key: impl Fn(&OcrRect) -> (i32, i32),
+) -> Vec<usize> {
+    let mut indices = (0..rects.len()).collect::<Vec<usize>>();
+    indices.sort_by_key(|index| extent(&rects[*index]));
+    let mut bands: Vec<((i32, i32), Vec<usize>)> = Vec::new();
+    for index in indices {
+        let (start, end) = extent(&rects[index]);
+        let overlap_of = |(band_start, band_end): (i32, i32)| {
+            let overlap = std::cmp::min(end, band_end) - std::cmp::max(start, band_start);
+            let shorter = std::cmp::max(std::cmp::min(end - start, band_end - band_start), 1);
+            overlap as f32 / shorter as f32
+        };
+        let best_band = bands
+            .iter_mut()
+            .map(|band| (overlap_of(band.0), band))
+            .filter(|(overlap, _)| *overlap >= MIN_BAND_OVERLAP_RATIO)
+            .max_by(|a, b| a.0.total_cmp(&b.0));
+        match best_band {
+            Some((_, band)) => {
+                band.0 = (
+                    std::cmp::min(band.0 .0, start),
+                    std::cmp::max(band.0 .1, end),
+                );
+                band.1.push(index);
+            }
+            None => bands.push(((start, end), vec![index])),
+        }
+    }
+    bands.sort_by_key(|band| band_key(band.0));
+    bands
+        .into_iter()
+        .flat_map(|(_, mut band)| {
+            band.sort_by_key(|index| rect_key(&rects[*index]));
+            band
+        })
+        .collect()
+}
+
+// order of the lines within a block
+pub(crate) fn order_lines(rects: &[OcrRect], direction: OcrWritingDirection) -> Vec<usize> {
+    match direction {
+        OcrWritingDirection::VerticalTtbRtl => order_in_bands(
+            rects,
+            |rect| (rect.x_min, rect.x_max),
+            |(_, x_max)| -x_max,
+            |rect| (rect.y_min, -rect.x_max),
+        ),
+        OcrWritingDirection::HorizontalLtr => order_in_bands(
+            rects,
+            |rect| (rect.y_min, rect.y_max),
+            |(y_min, _)| y_min,
+            |rect| (rect.x_min, rect.y_min),
+        ),
+    }
+}
+
+// order of the blocks (or panels) on a page: rows top-to-bottom, right-to-left for tategaki
+pub(crate) fn order_blocks(rects: &[OcrRect], direction: OcrWritingDirection) -> Vec<usize> {
+    order_in_bands(
+        rects,
+        |rect| (rect.y_min, rect.y_max),
+        |(y_min, _)| y_min,
+        |rect| match direction {
+            OcrWritingDirection::VerticalTtbRtl => (-rect.x_max, rect.y_min),
+            OcrWritingDirection::HorizontalLtr => (rect.x_min, rect.y_min),
+        },
+    )
+}
+
+fn thickness(rect: &OcrRect, direction: OcrWritingDirection) -> u32 {
+    match direction {
+        OcrWritingDirection::VerticalTtbRtl => rect.width(),
+        OcrWritingDirection::HorizontalLtr => rect.height(),
+    }
+}
+
+// indices of the lines of each block (in no particular order yet), by merging lines that are within a line gap
+fn group_into_blocks(rects: &[OcrRect], directions: &[OcrWritingDirection]) -> Vec<Vec<usize>> {
+    let mut parents = (0..rects.len()).collect::<Vec<usize>>();
+    fn root_of(parents: &mut [usize], index: usize) -> usize {
+        let mut root = index;
+        while parents[root] != root {
+            parents[root] = parents[parents[root]];
+            root = parents[root];
+        }
+        root
+    }
+    for a in 0..rects.len() {
+        for b in a + 1..rects.len() {
+            if directions[a] != directions[b] {
+                continue;
+            }
+            let gap = (std::cmp::max(
+                thickness(&rects[a], directions[a]),
+                thickness(&rects[b], directions[b]),
+            ) as f32
+                * MAX_LINE_GAP_RATIO) as i32;
+            let is_near = rects[a].x_min - gap <= rects[b].x_max
+                && rects[b].x_min - gap <= rects[a].x_max
+                && rects[a].y_min - gap <= rects[b].y_max
+                && rects[b].y_min - gap <= rects[a].y_max;
+            if is_near {
+                let (root_a, root_b) = (root_of(&mut parents, a), root_of(&mut parents, b));
+                parents[root_b] = root_a;
+            }
+        }
+    }
+    let mut blocks: Vec<(usize, Vec<usize>)> = Vec::new();
+    for index in 0..rects.len() {
+        let root = root_of(&mut parents, index);
+        match blocks
+            .iter_mut()
+            .find(|(block_root, _)| *block_root == root)
+        {
+            Some((_, block)) => block.push(index),
+            None => blocks.push((root, vec![index])),
+        }
+    }
+    blocks.into_iter().map(|(_, block)| block).collect()
+}
+
+// blocks (of the given lines) in reading order, each with its lines in order
+fn blocks_in_reading_order(
+    indices: &[usize],
+    rects: &[OcrRect],
+    directions: &[OcrWritingDirection],
+    page_direction: OcrWritingDirection,
+) -> Vec<Vec<usize>> {
+    let group_rects = indices
+        .iter()
+        .map(|index| rects[*index])
+        .collect::<Vec<OcrRect>>();
+    let group_directions = indices
+        .iter()
+        .map(|index| directions[*index])
+        .collect::<Vec<OcrWritingDirection>>();
+
+    // 1. and 2. blocks, each with its lines in order
+    let blocks = group_into_blocks(&group_rects, &group_directions)
+        .into_iter()
+        .map(|block| {
+            let block_rects = block
+                .iter()
+                .map(|index| group_rects[*index])
+                .collect::<Vec<OcrRect>>();
+            // all the lines of a block are in the same direction (see group_into_blocks())
+            order_lines(&block_rects, group_directions[block[0]])
+                .into_iter()
+                .map(|order| indices[block[order]])
+                .collect::<Vec<usize>>()
+        })
+        .collect::<Vec<Vec<usize>>>();
+
+    // 3. blocks in order
+    let block_rects = blocks
+        .iter()
+        .map(|block| {
+            block
+                .iter()
+                .skip(1)
+                .fold(rects[block[0]], |acc, index| acc.union(&rects[*index]))
+        })
+        .collect::<Vec<OcrRect>>();
+    order_blocks(&block_rects, page_direction)
+        .into_iter()
+        .map(|order| blocks[order].clone())
+        .collect()
+}
+
+// the same result with the lines (and the words of each line) in reading order, and text/lines rebuilt to match;
+// if the page was OCR'ed panel by panel, the panels are read one after the other (in the order they are in, see
+// image_handling::find_panels()) and the lines that are in none of them last
+pub(crate) fn to_reading_order(ocr_result: OcrTraitResult) -> OcrTraitResult {
+    if ocr_result.rects.is_empty() {
+        return ocr_result;
+    }
+    let page_direction = ocr_result.writing_direction();
+    let rects = ocr_result
+        .rects
+        .iter()
+        .map(|line| line.rect())
+        .collect::<Vec<OcrRect>>();
+    let directions = ocr_result
+        .rects
+        .iter()
+        .map(|line| line.writing_direction())
+        .collect::<Vec<OcrWritingDirection>>();
+
+    // lines by the panel that their center is in
+    let mut panel_lines = vec![Vec::new(); ocr_result.panels.len() + 1];
+    for (index, rect) in rects.iter().enumerate() {
+        let (center_x, center_y) = ((rect.x_min + rect.x_max) / 2, (rect.y_min + rect.y_max) / 2);
+        let panel_index = ocr_result
+            .panels
+            .iter()
+            .position(|panel| {
+                center_x >= panel.x_min
+                    && center_x < panel.x_max
+                    && center_y >= panel.y_min
+                    && center_y < panel.y_max
+            })
+            .unwrap_or(ocr_result.panels.len());
+        panel_lines[panel_index].push(index);
+    }
+    let blocks = panel_lines
+        .iter()
+        .filter(|indices| !indices.is_empty())
+        .flat_map(|indices| blocks_in_reading_order(indices, &rects, &directions, page_direction))
+        .collect::<Vec<Vec<usize>>>();
+
+    let mut lines = Vec::new();
+    let mut ordered_rects = Vec::new();
+    for (block_index, block) in blocks.iter().enumerate() {
+        for index in block.iter() {
+            let line = &ocr_result.rects[*index];
+            let mut words = line.words();
+            match directions[*index] {
+                OcrWritingDirection::VerticalTtbRtl => words.sort_by_key(|word| word.y_min()),
+                OcrWritingDirection::HorizontalLtr => words.sort_by_key(|word| word.x_min()),
+            }
+            let mut ordered_line = OcrLine::new(
+                words
+                    .into_iter()
+                    .map(|word| {
+                        OcrWord::new_with_layout(
+                            word.word(),
+                            block_index as u16,
+                            block_index as u16,
+                            ordered_rects.len() as u16,
+                            word.rect(),
+                            word.confidence(),
+                        )
+                    })
+                    .collect(),
+            );
+            ordered_line.set_writing_direction(directions[*index]);
+            if let Some(angle) = line.angle() {
+                ordered_line.set_angle(angle);
+            }
+            // same join rule for every backend (and whether or not the words had to be reordered)
+            lines.push(ordered_line.text());
+            ordered_rects.push(ordered_line);
+        }
+    }
+    OcrTraitResult {
+        text: lines.join("\n"),
+        lines,
+        rects: ordered_rects,
+        language: ocr_result.language,
+        panels: ocr_result.panels,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ocr_gcloud::OcrGcloud;
+
+    // a line of one word per character, stacked downwards (vertical) or side by side (horizontal)
+    fn line_of(text: &str, x: i32, y: i32, direction: OcrWritingDirection) -> OcrLine {
+        let mut line = OcrLine::new(
+            text.chars()
+                .enumerate()
+                .map(|(index, c)| {
+                    let offset = index as i32 * 40;
+                    let (x, y) = match direction {
+                        OcrWritingDirection::VerticalTtbRtl => (x, y + offset),
+                        OcrWritingDirection::HorizontalLtr => (x + offset, y),
+                    };
+                    OcrWord::new(c.to_string(), 0, OcrRect::from(x, y, 40, 40))
+                })
+                .collect(),
+        );
+        line.set_writing_direction(direction);
+        line
+    }
+
+    #[test]
+    fn test_to_reading_order() {
+        // as Tesseract would have it: left-to-right, with the words of the first column bottom-up
+        let mut ocr_result = OcrGcloud::to_ocr_trait_result(
+            &std::fs::read_to_string(
+                "../assets/fixtures/gcloud_synthetic_ubunchu01_02_panel01_section_02.json",
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let expected_text = ocr_result.text.clone();
+        ocr_result.rects.reverse();
+        ocr_result.lines.reverse();
+        let mut first_words = ocr_result.rects[0].words();
+        first_words.reverse();
+        ocr_result.rects[0] = OcrLine::new(first_words);
+        let ordered = to_reading_order(ocr_result);
+        assert_eq!(ordered.text, expected_text);
+        assert_eq!(ordered.rects[2].line_index(), 2);
+
+        // a page of two tiers: a bubble of two columns on the right and a one-column bubble on the left of the
+        // top tier, then a horizontal caption and a one-column bubble in the bottom tier
+        use OcrWritingDirection::{HorizontalLtr, VerticalTtbRtl};
+        let mut page_result = OcrTraitResult::new();
+        page_result.rects = vec![
+            line_of("えお", 100, 40, VerticalTtbRtl),
+            line_of("かきく", 300, 500, HorizontalLtr),
+            line_of("いう", 500, 20, VerticalTtbRtl),
+            line_of("け", 700, 480, VerticalTtbRtl),
+            line_of("あ", 560, 20, VerticalTtbRtl),
+        ];
+        page_result.lines = page_result.rects.iter().map(|line| line.text()).collect();
+        let ordered = to_reading_order(page_result);
+        assert_eq!(ordered.lines, ["あ", "いう", "えお", "け", "かきく"]);
+        assert_eq!(
+            ordered
+                .rects
+                .iter()
+                .map(|line| line.block_index())
+                .collect::<Vec<u16>>(),
+            [0, 0, 1, 2, 3]
+        );
+
+        // panels are read one after the other: the right panel (with a line at its bottom) before the left one
+        let mut panel_result = OcrTraitResult::new();
+        panel_result.rects = vec![
+            line_of("さ", 100, 50, VerticalTtbRtl),
+            line_of("こ", 600, 800, VerticalTtbRtl),
+        ];
+        panel_result.lines = vec!["さ".to_string(), "こ".to_string()];
+        assert_eq!(to_reading_order(panel_result.clone()).lines, ["さ", "こ"]);
+        panel_result.panels = vec![
+            OcrRect::new(500, 0, 1000, 1000),
+            OcrRect::new(0, 0, 500, 1000),
+        ];
+        assert_eq!(to_reading_order(panel_result).lines, ["こ", "さ"]);
+    }
+}
@@ -0,0 +1,424 @@
+// Registry of the OCR backends and the interpreters by name, for --ocr <name>, --interpreter <name>[,<name>...]
+// (a chain, i.e. "kakasi,libretranslate" for the reading and the translation, see interpreter_chain.rs) and
+// `lenzu list-backends`, which tells which of them can be used on this machine (and why the others cannot); the
+// options of the backends (i.e. --mecab-dictionary <dir>, --dictionary <store.bin>) come from the same args
+use crate::dictionary::{DEFAULT_DICTIONARY_STORE, ENV_DICTIONARY};
+use crate::interpreter_chain::InterpreterChain;
+use crate::interpreter_command::{CommandConfig, InterpreterCommand};
+use crate::interpreter_ja::InterpreterJa;
+use crate::interpreter_jmdict::InterpreterJmdict;
+use crate::interpreter_mecab::{InterpreterMecab, ENV_MECAB_DICTIONARY};
+use crate::interpreter_passthrough::InterpreterPassthrough;
+use crate::interpreter_traits::InterpreterTrait;
+use crate::interpreter_translate::{InterpreterTranslate, TranslateBackend};
+use crate::language_detect::Language;
+use crate::ocr_gcloud::{OcrGcloud, ENV_GCLOUD_ACCESS_TOKEN, ENV_GCLOUD_VISION_API_KEY};
+use crate::ocr_tesseract::OcrTesseract;
+use crate::ocr_traits::OcrTrait;
+use anyhow::Error;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+// without --ocr <name>, the most reliable OCR of the platform
+#[cfg(target_os = "windows")]
+pub(crate) const DEFAULT_OCR: &str = "winmedia";
+#[cfg(not(target_os = "windows"))]
+pub(crate) const DEFAULT_OCR: &str = "tesseract";
+
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(500); // the translation servers are local (or close)
+
+// Ok with what there is (i.e. the version), or Err with why the backend cannot be used
+type Availability = Result<String, String>;
+type CreateOcr = fn(&[String]) -> Result<Box<dyn OcrTrait>, Error>;
+type CreateInterpreter = fn(&[String]) -> Result<Box<dyn InterpreterTrait>, Error>;
+
+pub(crate) struct OcrBackend {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub availability: fn(&[String]) -> Availability,
+    pub create: CreateOcr,
+}
+
+pub(crate) struct InterpreterBackend {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub availability: fn(&[String]) -> Availability,
+    pub create: CreateInterpreter,
+}
+
+// value of the option, i.e. "ja" of "--lang ja"
+pub(crate) fn option_value<'a>(args: &'a [String], option: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|arg| arg == option)
+        .and_then(|index| args.get(index + 1))
+        .map(|value| value.as_str())
+}
+
+pub(crate) fn ocr_backends() -> Vec<OcrBackend> {
+    vec![
+        OcrBackend {
+            name: "tesseract",
+            description: "Tesseract (local)",
+            availability: |_| match rusty_tesseract::get_tesseract_version() {
+                Ok(version) => Ok(format!("version {}", version.trim())),
+                Err(e) => Err(format!(
+                    "tesseract is not installed (or not on the PATH) - {}",
+                    e
+                )),
+            },
+            create: |_| match rusty_tesseract::get_tesseract_version() {
+                Ok(_) => Ok(Box::new(OcrTesseract::new())),
+                Err(e) => Err(anyhow::anyhow!(
+                    "tesseract is not installed (or not on the PATH) - {}",
+                    e
+                )),
+            },
+        },
+        OcrBackend {
+            name: "winmedia",
+            description: "Windows.Media.Ocr (local, Windows 10 and later)",
+            availability: winmedia_availability,
+            create: create_winmedia,
+        },
+        OcrBackend {
+            name: "gcloud",
+            description: "Google Cloud Vision (online, the image is sent to Google)",
+            availability: |_| {
+                let ocr = OcrGcloud::new();
+                if ocr.has_credentials() {
+                    Ok(format!("endpoint {}", ocr.endpoint()))
+                } else {
+                    Err(format!(
+                        "neither {} nor {} is set",
+                        ENV_GCLOUD_VISION_API_KEY, ENV_GCLOUD_ACCESS_TOKEN
+                    ))
+                }
+            },
+            create: |_| Ok(Box::new(OcrGcloud::new())),
+        },
+    ]
+}
+
+#[cfg(target_os = "windows")]
+fn winmedia_availability(_args: &[String]) -> Availability {
+    Ok("built into Windows".to_string())
+}
+
+#[cfg(not(target_os = "windows"))]
+fn winmedia_availability(_args: &[String]) -> Availability {
+    Err("only available on Windows".to_string())
+}
+
+#[cfg(target_os = "windows")]
+fn create_winmedia(_args: &[String]) -> Result<Box<dyn OcrTrait>, Error> {
+    Ok(Box::new(crate::ocr_winmedia::OcrWinMedia::new()))
+}
+
+#[cfg(not(target_os = "windows"))]
+fn create_winmedia(_args: &[String]) -> Result<Box<dyn OcrTrait>, Error> {
+    Err(anyhow::anyhow!("winmedia OCR is only available on Windows"))
+}
+
+pub(crate) fn interpreter_backends() -> Vec<InterpreterBackend> {
+    vec![
+        InterpreterBackend {
+            name: "kakasi",
+            description: "kanji to kana (built in)",
+            availability: |_| Ok("built in".to_string()),
+            create: |_| Ok(Box::new(InterpreterJa::new())),
+        },
+        InterpreterBackend {
+            name: "mecab",
+            description: "MeCab compatible analyzer (lindera, with an IPADIC/UniDic dictionary)",
+            availability: |args| {
+                let dir = mecab_dictionary(args).ok_or(format!(
+                    "no dictionary, pass --mecab-dictionary <dir> or set {}",
+                    ENV_MECAB_DICTIONARY
+                ))?;
+                if dir.is_dir() {
+                    Ok(dir.display().to_string())
+                } else {
+                    Err(format!("{} is not a directory", dir.display()))
+                }
+            },
+            create: |args| match option_value(args, "--mecab-dictionary") {
+                Some(dir) => Ok(Box::new(InterpreterMecab::from_dir(Path::new(dir))?)),
+                None => Ok(Box::new(InterpreterMecab::new())),
+            },
+        },
+        InterpreterBackend {
+            name: "jmdict",
+            description: "glosses from the JMdict/Yomitan dictionary store (offline)",
+            availability: dictionary_availability,
+            create: |args| match option_value(args, "--dictionary") {
+                Some(path) => Ok(Box::new(InterpreterJmdict::from_store(Path::new(path))?)),
+                None => Ok(Box::new(InterpreterJmdict::new())),
+            },
+        },
+        InterpreterBackend {
+            name: "command",
+            description: "external command (--interpreter-command <kakasi|mecab|config.json>)",
+            availability: |args| {
+                let config = command_config(args).map_err(|e| e.to_string())?;
+                match config.find_executable() {
+                    Some(path) => Ok(path.display().to_string()),
+                    None => Err(format!("{} is not on the PATH", config.executable)),
+                }
+            },
+            create: |args| {
+                Ok(Box::new(InterpreterCommand::from_config(command_config(
+                    args,
+                )?)))
+            },
+        },
+        InterpreterBackend {
+            name: "passthrough",
+            description: "the text as is",
+            availability: |_| Ok("built in".to_string()),
+            create: |_| Ok(Box::new(InterpreterPassthrough::new())),
+        },
+        InterpreterBackend {
+            name: "libretranslate",
+            description: "translation by a LibreTranslate server",
+            availability: |args| translator_availability(TranslateBackend::LibreTranslate, args),
+            create: |args| {
+                Ok(Box::new(create_translator(
+                    TranslateBackend::LibreTranslate,
+                    args,
+                )))
+            },
+        },
+        InterpreterBackend {
+            name: "openai",
+            description: "translation by an OpenAI compatible (local LLM) server",
+            availability: |args| translator_availability(TranslateBackend::OpenAi, args),
+            create: |args| Ok(Box::new(create_translator(TranslateBackend::OpenAi, args))),
+        },
+        InterpreterBackend {
+            name: "gloss",
+            description: "word by word glosses from the dictionary store (offline)",
+            availability: dictionary_availability,
+            create: |args| Ok(Box::new(create_translator(TranslateBackend::Gloss, args))),
+        },
+    ]
+}
+
+fn mecab_dictionary(args: &[String]) -> Option<PathBuf> {
+    option_value(args, "--mecab-dictionary")
+        .map(PathBuf::from)
+        .or(std::env::var(ENV_MECAB_DICTIONARY).ok().map(PathBuf::from))
+}
+
+fn dictionary_availability(args: &[String]) -> Availability {
+    let path = option_value(args, "--dictionary")
+        .map(PathBuf::from)
+        .unwrap_or(
+            std::env::var(ENV_DICTIONARY)
+                .unwrap_or(DEFAULT_DICTIONARY_STORE.to_string())
+                .into(),
+        );
+    if path.is_file() {
+        Ok(path.display().to_string())
+    } else {
+        Err(format!(
+            "no dictionary store at {}, import one with `lenzu dictionary import`",
+            path.display()
+        ))
+    }
+}
+
+fn command_config(args: &[String]) -> Result<CommandConfig, Error> {
+    match option_value(args, "--interpreter-command") {
+        Some(arg) => CommandConfig::from_arg(arg),
+        None => Ok(CommandConfig::from_env()),
+    }
+}
+
+// --translate-to <lang> for the target, and --lang <lang> for the source
+fn create_translator(backend: TranslateBackend, args: &[String]) -> InterpreterTranslate {
+    let mut interpreter = InterpreterTranslate::from_backend(
+        backend,
+        option_value(args, "--translate-to"),
+        option_value(args, "--dictionary").map(Path::new),
+    );
+    if let Some(language) = option_value(args, "--lang") {
+        interpreter.set_source_language(&source_language(language));
+    }
+    interpreter
+}
+
+// --lang as the translators know it (ISO 639-1), i.e. "jpn_vert" -> "ja", "en-US" -> "en"; what cannot be told
+// (i.e. "eng" is latin, but which language?) is left to the translator to detect
+fn source_language(code: &str) -> String {
+    match Language::from_code(code) {
+        Some(language @ (Language::Japanese | Language::Chinese | Language::Korean)) => {
+            language.name().to_string()
+        }
+        _ => {
+            let primary = code
+                .split(['-', '_', '+'])
+                .next()
+                .unwrap_or_default()
+                .to_lowercase();
+            if primary.len() == 2 {
+                primary
+            } else {
+                "auto".to_string()
+            }
+        }
+    }
+}
+
+// whether something listens on the host/port of the endpoint (the request itself is not tried)
+fn translator_availability(backend: TranslateBackend, args: &[String]) -> Availability {
+    let interpreter = create_translator(backend, args);
+    let endpoint = interpreter.endpoint();
+    let address = reqwest::Url::parse(endpoint)
+        .map_err(|e| format!("invalid endpoint {} - {}", endpoint, e))
+        .and_then(|url| {
+            let host = url.host_str().unwrap_or_default().to_string();
+            let port = url.port_or_known_default().unwrap_or_default();
+            (host.as_str(), port)
+                .to_socket_addrs()
+                .ok()
+                .and_then(|mut addresses| addresses.next())
+                .ok_or(format!("cannot resolve {}", endpoint))
+        })?;
+    TcpStream::connect_timeout(&address, CONNECT_TIMEOUT)
+        .map(|_| format!("endpoint {}", endpoint))
+        .map_err(|e| format!("cannot connect to {} - {}", endpoint, e))
+}
+
+pub(crate) fn create_ocr(name: &str, args: &[String]) -> Result<Box<dyn OcrTrait>, Error> {
+    let backends = ocr_backends();
+    match backends.iter().find(|backend| backend.name == name) {
+        Some(backend) => (backend.create)(args),
+        None => Err(anyhow::anyhow!(
+            "unknown OCR backend '{}', expected one of {}",
+            name,
+            backends
+                .iter()
+                .map(|backend| backend.name)
+                .collect::<Vec<&str>>()
+                .join(", ")
+        )),
+    }
+}
+
+// "kakasi", or a chain "kakasi,libretranslate"
+pub(crate) fn create_interpreter(
+    names: &str,
+    args: &[String],
+) -> Result<Box<dyn InterpreterTrait>, Error> {
+    let backends = interpreter_backends();
+    let mut interpreters = names
+        .split(',')
+        .map(
+            |name| match backends.iter().find(|backend| backend.name == name.trim()) {
+                Some(backend) => (backend.create)(args),
+                None => Err(anyhow::anyhow!(
+                    "unknown interpreter '{}', expected one of {}",
+                    name,
+                    backends
+                        .iter()
+                        .map(|backend| backend.name)
+                        .collect::<Vec<&str>>()
+                        .join(", ")
+                )),
+            },
+        )
+        .collect::<Result<Vec<Box<dyn InterpreterTrait>>, Error>>()?;
+    if interpreters.len() == 1 {
+        Ok(interpreters.remove(0))
+    } else {
+        Ok(Box::new(InterpreterChain::new_with_interpreters(
+            interpreters,
+        )))
+    }
+}
+
+// a line per backend, with the languages (of init()) of the ones that are available
+fn describe(
+    name: &str,
+    description: &str,
+    availability: Availability,
+    init: impl FnOnce() -> Result<Vec<String>, Error>,
+) -> String {
+    let (status, detail) = match availability {
+        Ok(detail) => match init() {
+            Ok(languages) if languages.is_empty() => ("available", detail),
+            Ok(languages) => (
+                "available",
+                format!("{}, languages: {}", detail, languages.join(", ")),
+            ),
+            Err(e) => ("unavailable", e.to_string()),
+        },
+        Err(reason) => ("unavailable", reason),
+    };
+    format!(
+        "  {:<16}{:<13}{}\n  {:<16}{:<13}{}\n",
+        name, status, description, "", "", detail
+    )
+}
+
+// `lenzu list-backends` (args after "list-backends")
+pub(crate) fn list_backends(args: &[String]) -> String {
+    let mut report = "OCR backends (--ocr <name>):\n".to_string();
+    for backend in ocr_backends() {
+        report.push_str(&describe(
+            backend.name,
+            backend.description,
+            (backend.availability)(args),
+            || (backend.create)(args).map(|ocr| ocr.init()),
+        ));
+    }
+    report.push_str("Interpreters (--interpreter <name>[,<name>...]):\n");
+    for backend in interpreter_backends() {
+        report.push_str(&describe(
+            backend.name,
+            backend.description,
+            (backend.availability)(args),
+            || (backend.create)(args).map(|interpreter| interpreter.init()),
+        ));
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry() {
+        let args: Vec<String> = ["--dictionary", "no/such/store.bin", "--lang", "ja"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        assert_eq!(option_value(&args, "--lang"), Some("ja"));
+        assert_eq!(option_value(&args, "--ocr"), None);
+
+        let mut names: Vec<&str> = interpreter_backends()
+            .iter()
+            .map(|backend| backend.name)
+            .collect();
+        names.extend(ocr_backends().iter().map(|backend| backend.name));
+        let count = names.len();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), count, "backend names must be unique");
+
+        let report = list_backends(&args);
+        assert!(report.contains("  passthrough     available    the text as is\n"));
+        assert!(report.contains("no dictionary store at no/such/store.bin"));
+
+        let chain = create_interpreter("passthrough, passthrough", &args).unwrap();
+        assert_eq!(chain.convert("本").unwrap().text, "本 / 本");
+        assert!(create_interpreter("kakasi,nope", &args).is_err());
+        assert_eq!(source_language("jpn_vert"), "ja");
+        assert_eq!(source_language("zh-Hans"), "zh");
+        assert_eq!(source_language("en-US"), "en");
+        assert_eq!(source_language("eng"), "auto");
+        assert!(create_ocr("nope", &args).is_err());
+    }
+}
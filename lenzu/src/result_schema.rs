@@ -0,0 +1,335 @@
+// Versioned JSON schema of the OCR and interpreter results, so that downstream tools can consume lenzu results,
+// and so that results can be diffed across backends (and versions of them)
+// NOTE: these are deliberately separate structs from OcrTraitResult/OcrLine/OcrWord (which are free to change
+// as the backends need), and RESULT_SCHEMA_VERSION must be bumped whenever a field is removed, renamed, or its
+// meaning changes (adding an optional field is fine, older readers will just ignore it)
+use crate::interpreter_traits::InterpreterTraitResult;
+use crate::ocr_traits::{OcrLine, OcrRect, OcrTraitResult, OcrWord, OcrWritingDirection};
+use anyhow::Error;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+pub(crate) const RESULT_SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct ResultDocument {
+    pub schema_version: u32,
+    pub image: ImageJson,
+    pub ocr: OcrResultJson,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interpreter: Option<InterpreterResultJson>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct ImageJson {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>, // None when captured from the screen
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct OcrResultJson {
+    pub backend: String, // OcrTrait::name()
+    pub elapsed_ms: u64,
+    pub language: Option<String>,
+    pub text: String,
+    pub confidence: Option<f32>, // 0.0..=1.0, None if the backend does not report it
+    pub writing_direction: OcrWritingDirection,
+    pub lines: Vec<OcrLineJson>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub panels: Vec<OcrRect>, // in reading order, only when OCR'ed panel by panel
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct OcrLineJson {
+    pub text: String,
+    pub rect: OcrRect,
+    pub confidence: Option<f32>,
+    pub writing_direction: OcrWritingDirection,
+    pub angle: Option<f32>,
+    pub block_index: u16,
+    pub paragraph_index: u16,
+    pub line_index: u16,
+    pub words: Vec<OcrWordJson>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct OcrWordJson {
+    pub text: String,
+    pub rect: OcrRect,
+    pub confidence: Option<f32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct InterpreterResultJson {
+    pub backend: String, // InterpreterTrait::name()
+    pub elapsed_ms: u64,
+    pub text: String,
+    pub lines: Vec<String>, // readings (i.e. hiragana), line by line
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tokens: Vec<InterpreterTokenJson>, // only if the interpreter tokenizes
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub translations: Vec<String>, // only if the interpreter translates, line by line
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct InterpreterTokenJson {
+    pub surface: String,
+    pub reading: String,
+    pub base_form: String,
+    pub part_of_speech: Option<String>,
+    pub start: usize, // char offsets into the OCR text
+    pub end: usize,
+}
+
+impl OcrResultJson {
+    pub fn from_ocr_result(backend: &str, elapsed_ms: u64, ocr_result: &OcrTraitResult) -> Self {
+        // lines and rects are one-to-one on all the OCR backends, but if not, the best we can do is the words
+        let is_one_to_one = ocr_result.lines.len() == ocr_result.rects.len();
+        let lines = ocr_result
+            .rects
+            .iter()
+            .enumerate()
+            .map(|(index, ocr_line)| OcrLineJson {
+                text: if is_one_to_one {
+                    ocr_result.lines[index].clone()
+                } else {
+                    ocr_line
+                        .words()
+                        .iter()
+                        .map(|word| word.word())
+                        .collect::<String>()
+                },
+                rect: ocr_line.rect(),
+                confidence: ocr_line.confidence(),
+                writing_direction: ocr_line.writing_direction(),
+                angle: ocr_line.angle(),
+                block_index: ocr_line.block_index(),
+                paragraph_index: ocr_line.paragraph_index(),
+                line_index: ocr_line.line_index(),
+                words: ocr_line
+                    .words()
+                    .iter()
+                    .map(|word| OcrWordJson {
+                        text: word.word(),
+                        rect: word.rect(),
+                        confidence: word.confidence(),
+                    })
+                    .collect(),
+            })
+            .collect();
+        OcrResultJson {
+            backend: backend.to_string(),
+            elapsed_ms,
+            language: ocr_result.language.clone(),
+            text: ocr_result.text.clone(),
+            confidence: ocr_result.confidence(),
+            writing_direction: ocr_result.writing_direction(),
+            lines,
+            panels: ocr_result.panels.clone(),
+        }
+    }
+
+    pub fn to_ocr_result(&self) -> OcrTraitResult {
+        let rects = self
+            .lines
+            .iter()
+            .map(|line_json| {
+                let mut ocr_line = OcrLine::new(
+                    line_json
+                        .words
+                        .iter()
+                        .map(|word_json| {
+                            OcrWord::new_with_layout(
+                                word_json.text.clone(),
+                                line_json.block_index,
+                                line_json.paragraph_index,
+                                line_json.line_index,
+                                word_json.rect,
+                                word_json.confidence,
+                            )
+                        })
+                        .collect(),
+                );
+                ocr_line.set_writing_direction(line_json.writing_direction);
+                if let Some(angle) = line_json.angle {
+                    ocr_line.set_angle(angle);
+                }
+                ocr_line
+            })
+            .collect();
+        OcrTraitResult {
+            text: self.text.clone(),
+            lines: self.lines.iter().map(|line| line.text.clone()).collect(),
+            rects,
+            language: self.language.clone(),
+            panels: self.panels.clone(),
+        }
+    }
+}
+
+impl InterpreterResultJson {
+    pub fn from_interpreter_result(
+        backend: &str,
+        elapsed_ms: u64,
+        interpreter_result: &InterpreterTraitResult,
+    ) -> Self {
+        InterpreterResultJson {
+            backend: backend.to_string(),
+            elapsed_ms,
+            text: interpreter_result.text.clone(),
+            lines: interpreter_result.lines.clone(),
+            tokens: interpreter_result
+                .tokens
+                .iter()
+                .map(|token| InterpreterTokenJson {
+                    surface: token.surface.clone(),
+                    reading: token.reading.clone(),
+                    base_form: token.base_form.clone(),
+                    part_of_speech: token.part_of_speech.clone(),
+                    start: token.start,
+                    end: token.end,
+                })
+                .collect(),
+            translations: interpreter_result.translations.clone(),
+        }
+    }
+}
+
+impl ResultDocument {
+    pub fn new(
+        image_path: Option<&Path>,
+        width: u32,
+        height: u32,
+        ocr: OcrResultJson,
+        interpreter: Option<InterpreterResultJson>,
+    ) -> Self {
+        ResultDocument {
+            schema_version: RESULT_SCHEMA_VERSION,
+            image: ImageJson {
+                path: image_path.map(|path| path.to_string_lossy().into_owned()),
+                width,
+                height,
+            },
+            ocr,
+            interpreter,
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    // single line, for writing one document per line (JSON Lines) to stdout
+    pub fn to_json_line(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        // peek at the version first, so that a newer (incompatible) document fails with a meaningful error rather
+        // than with whichever field happened to be missing
+        #[derive(Deserialize)]
+        struct VersionOnly {
+            schema_version: u32,
+        }
+        let version_only: VersionOnly = serde_json::from_str(json)?;
+        if version_only.schema_version > RESULT_SCHEMA_VERSION {
+            return Err(anyhow::anyhow!(
+                "Result schema version {} is newer than supported version {}",
+                version_only.schema_version,
+                RESULT_SCHEMA_VERSION
+            ));
+        }
+        Ok(serde_json::from_str(json)?)
+    }
+
+    // a result saved by `lenzu ocr --format json`, to export it to the other formats (see batch_ocr.rs)
+    pub fn load(json_path: &Path) -> Result<Self, Error> {
+        let json = std::fs::read_to_string(json_path)
+            .map_err(|e| anyhow::anyhow!("Error reading '{}': {}", json_path.display(), e))?;
+        Self::from_json(json.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_document() -> ResultDocument {
+        let mut ocr_result = OcrTraitResult::new();
+        let mut ocr_line = OcrLine::new(vec![OcrWord::new_with_layout(
+            "最近人気の".to_string(),
+            0,
+            0,
+            0,
+            OcrRect::new(300, 20, 340, 230),
+            Some(0.75),
+        )]);
+        ocr_line.set_writing_direction(OcrWritingDirection::VerticalTtbRtl);
+        ocr_result.rects = vec![ocr_line];
+        ocr_result.lines = vec!["最近人気の".to_string()];
+        ocr_result.text = ocr_result.lines.join("\n");
+        ocr_result.language = Some("ja".to_string());
+        let interpreter_result = InterpreterTraitResult {
+            text: "さいきんにんきの".to_string(),
+            lines: vec!["さいきんにんきの".to_string()],
+            tokens: vec![],
+            translations: vec![],
+        };
+        ResultDocument::new(
+            Some(Path::new("ubunchu01_02_panel01_section_02.png")),
+            360,
+            250,
+            OcrResultJson::from_ocr_result("tesseract", 1234, &ocr_result),
+            Some(InterpreterResultJson::from_interpreter_result(
+                "kakasi",
+                5,
+                &interpreter_result,
+            )),
+        )
+    }
+
+    #[test]
+    fn test_schema_matches_fixture_and_round_trips() {
+        // the fixture is what downstream tools are expecting, so if this fails because a field was added/changed,
+        // either keep it backward compatible, or bump RESULT_SCHEMA_VERSION (and then update the fixture)
+        let document = sample_document();
+        let fixture_json =
+            std::fs::read_to_string("../assets/fixtures/result_schema_v1.json").unwrap();
+        let fixture_value: serde_json::Value = serde_json::from_str(&fixture_json).unwrap();
+        let document_value: serde_json::Value =
+            serde_json::from_str(&document.to_json().unwrap()).unwrap();
+        assert_eq!(document_value, fixture_value);
+
+        let loaded = ResultDocument::from_json(&fixture_json).unwrap();
+        assert_eq!(loaded, document);
+        let ocr_result = loaded.ocr.to_ocr_result();
+        assert_eq!(ocr_result.text, "最近人気の");
+        assert_eq!(
+            ocr_result.writing_direction(),
+            OcrWritingDirection::VerticalTtbRtl
+        );
+        assert_eq!(ocr_result.rects[0].rect(), OcrRect::new(300, 20, 340, 230));
+        // and once more through OcrTraitResult to make sure nothing is lost on the way back
+        assert_eq!(
+            OcrResultJson::from_ocr_result("tesseract", 1234, &ocr_result),
+            document.ocr
+        );
+
+        // the translations are only written by the interpreters that translate, so v1 results without them still load
+        assert!(loaded.interpreter.as_ref().unwrap().translations.is_empty());
+        let mut translated = document.clone();
+        translated.interpreter.as_mut().unwrap().translations =
+            vec!["Recently popular".to_string()];
+        let translated_json = translated.to_json().unwrap();
+        assert!(translated_json.contains("\"translations\": [\n      \"Recently popular\"\n    ]"));
+        assert_eq!(
+            ResultDocument::from_json(&translated_json).unwrap(),
+            translated
+        );
+
+        let newer_json = fixture_json.replace("\"schema_version\": 1", "\"schema_version\": 99");
+        assert!(ResultDocument::from_json(&newer_json).is_err());
+    }
+}
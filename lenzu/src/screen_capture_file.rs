@@ -0,0 +1,90 @@
+// File-backed (fake) screen capture, where the "desktop" is just an image file, so that the
+// capture->OCR->interpret pipeline can be exercised without any display (i.e. on CI)
+use crate::screen_capture_traits::{self, MonitorInfo, ScreenCaptureTrait, ScreenRect};
+use anyhow::Error;
+use image::DynamicImage; // the "real" DynamicImage, not the one from imageproc or rusty_tesseract
+
+pub struct ScreenCaptureFile {
+    desktop_image: DynamicImage, // the entire desktop, with upper left corner at (0, 0)
+    cursor_x: i32,
+    cursor_y: i32,
+}
+
+impl ScreenCaptureFile {
+    pub fn new(image_path: &str) -> Result<Self, Error> {
+        let desktop_image = image::open(image_path)
+            .map_err(|e| anyhow::anyhow!("Error loading image file '{}': {}", image_path, e))?;
+        Ok(Self::from_image(desktop_image))
+    }
+
+    // cursor defaults to center of the desktop
+    pub fn from_image(desktop_image: DynamicImage) -> Self {
+        ScreenCaptureFile {
+            cursor_x: desktop_image.width() as i32 / 2,
+            cursor_y: desktop_image.height() as i32 / 2,
+            desktop_image,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn set_cursor_position(&mut self, x: i32, y: i32) {
+        self.cursor_x = x;
+        self.cursor_y = y;
+    }
+}
+
+impl ScreenCaptureTrait for ScreenCaptureFile {
+    fn capture(&self, rect: ScreenRect) -> Result<DynamicImage, Error> {
+        Ok(screen_capture_traits::crop_onto_canvas(
+            &self.desktop_image,
+            0,
+            0,
+            rect,
+        ))
+    }
+
+    fn cursor_position(&self) -> Result<(i32, i32), Error> {
+        Ok((self.cursor_x, self.cursor_y))
+    }
+
+    fn monitors(&self) -> Result<Vec<MonitorInfo>, Error> {
+        let rect = ScreenRect::new(
+            0,
+            0,
+            self.desktop_image.width(),
+            self.desktop_image.height(),
+        );
+        Ok(vec![MonitorInfo {
+            name: "file".to_string(),
+            rect,
+            work_area: rect,
+            is_primary: true,
+        }])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::GenericImageView;
+
+    #[test]
+    fn test_capture_partially_off_desktop() {
+        let mut desktop = image::RgbaImage::from_pixel(100, 50, image::Rgba([255, 255, 255, 255]));
+        desktop.put_pixel(0, 0, image::Rgba([255, 0, 0, 255]));
+        let screen_capture = ScreenCaptureFile::from_image(DynamicImage::ImageRgba8(desktop));
+        assert_eq!(screen_capture.cursor_position().unwrap(), (50, 25));
+
+        // 10 pixels left of and above the desktop should come back as black, then the red pixel at (0, 0)
+        let captured = screen_capture
+            .capture(ScreenRect::new(-10, -10, 20, 20))
+            .unwrap();
+        assert_eq!(captured.dimensions(), (20, 20));
+        assert_eq!(captured.get_pixel(0, 0), image::Rgba([0, 0, 0, 255]));
+        assert_eq!(captured.get_pixel(10, 10), image::Rgba([255, 0, 0, 255]));
+        assert_eq!(
+            captured.get_pixel(19, 19),
+            image::Rgba([255, 255, 255, 255])
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use anyhow::Error; // the most easiest way to handle errors
+use core::result::Result;
+use image::DynamicImage; // the "real" DynamicImage, not the one from imageproc or rusty_tesseract
+
+// Platform-neutral access to the desktop, so that capture->OCR->interpret does not need to know whether
+// we are on Win32 (GDI), X11 (Xlib), or just reading from an image file (headless tests)
+// NOTE: unlike OcrTrait, there is no new() here because opening a display (i.e. XOpenDisplay()) can fail,
+// hence each implementation has its own constructor that returns Result<Self, Error>
+pub trait ScreenCaptureTrait {
+    // captures the rectangle (in desktop coordinates, which can be negative for monitors left/above the PRIMARY monitor)
+    // areas of the rectangle that are not on any monitor comes back as black
+    fn capture(&self, rect: ScreenRect) -> Result<DynamicImage, Error>;
+
+    // cursor (mouse pointer) position in desktop coordinates
+    fn cursor_position(&self) -> Result<(i32, i32), Error>;
+
+    // all monitors that makes up the desktop
+    fn monitors(&self) -> Result<Vec<MonitorInfo>, Error>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct ScreenRect {
+    pub x: i32, // upper left corner, can be negative (see ScreenCaptureTrait::capture())
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+impl ScreenRect {
+    pub fn new(x: i32, y: i32, width: u32, height: u32) -> Self {
+        ScreenRect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+    pub fn right(&self) -> i32 {
+        self.x + self.width as i32
+    }
+    pub fn bottom(&self) -> i32 {
+        self.y + self.height as i32
+    }
+    // overlapping area of the two rectangles (None if they do not overlap)
+    pub fn intersect(&self, other: &ScreenRect) -> Option<ScreenRect> {
+        let x_min = std::cmp::max(self.x, other.x);
+        let y_min = std::cmp::max(self.y, other.y);
+        let x_max = std::cmp::min(self.right(), other.right());
+        let y_max = std::cmp::min(self.bottom(), other.bottom());
+        if x_min >= x_max || y_min >= y_max {
+            return None;
+        }
+        Some(ScreenRect::new(
+            x_min,
+            y_min,
+            (x_max - x_min) as u32,
+            (y_max - y_min) as u32,
+        ))
+    }
+    // squared distance from the point to the nearest edge of the rectangle (0 if inside)
+    pub fn distance_squared(&self, x: i32, y: i32) -> i64 {
+        let dx = std::cmp::max(std::cmp::max(self.x - x, x - (self.right() - 1)), 0) as i64;
+        let dy = std::cmp::max(std::cmp::max(self.y - y, y - (self.bottom() - 1)), 0) as i64;
+        dx * dx + dy * dy
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct MonitorInfo {
+    pub name: String,
+    pub rect: ScreenRect,      // entire display area of the monitor
+    pub work_area: ScreenRect, // area not obscured by taskbar and toolbars (same as rect if the platform does not tell us)
+    pub is_primary: bool,
+}
+
+// what the areas of the desktop that are not on any monitor look like
+pub(crate) fn black_canvas(width: u32, height: u32) -> image::RgbaImage {
+    image::RgbaImage::from_pixel(width, height, image::Rgba([0, 0, 0, 255]))
+}
+
+// copies the part of the source image that overlaps with the rect onto a black canvas of the rect size; the
+// source image is assumed to be located at source_x/source_y in desktop coordinates
+pub(crate) fn crop_onto_canvas(
+    source: &DynamicImage,
+    source_x: i32,
+    source_y: i32,
+    rect: ScreenRect,
+) -> DynamicImage {
+    let mut canvas = black_canvas(rect.width, rect.height);
+    let source_rect = ScreenRect::new(source_x, source_y, source.width(), source.height());
+    if let Some(overlap) = source_rect.intersect(&rect) {
+        let cropped = source
+            .crop_imm(
+                (overlap.x - source_x) as u32,
+                (overlap.y - source_y) as u32,
+                overlap.width,
+                overlap.height,
+            )
+            .to_rgba8();
+        image::imageops::overlay(
+            &mut canvas,
+            &cropped,
+            (overlap.x - rect.x) as i64,
+            (overlap.y - rect.y) as i64,
+        );
+    }
+    DynamicImage::ImageRgba8(canvas)
+}
@@ -0,0 +1,165 @@
+// Win32 (GDI) screen capture; this is what used to be main.rs::from_screen_to_image() and CursorData::update()
+use crate::screen_capture_traits::{MonitorInfo, ScreenCaptureTrait, ScreenRect};
+use anyhow::Error;
+use image::{DynamicImage, ImageBuffer}; // the "real" DynamicImage, not the one from imageproc or rusty_tesseract
+use std::ptr;
+use winapi::{
+    shared::{
+        minwindef::{BOOL, BYTE, LPARAM, TRUE},
+        windef::{HDC, HMONITOR, LPRECT, RECT},
+    },
+    um::{
+        wingdi::{
+            BitBlt, CreateCompatibleBitmap, CreateCompatibleDC, DeleteDC, DeleteObject, GetDIBits,
+            SelectObject, BITMAPINFO, BI_RGB, DIB_RGB_COLORS, SRCCOPY,
+        },
+        winuser::{
+            EnumDisplayMonitors, GetCursorPos, GetDC, GetMonitorInfoW, ReleaseDC, MONITORINFO,
+            MONITORINFOF_PRIMARY,
+        },
+    },
+};
+
+pub struct ScreenCaptureWin32 {}
+
+impl ScreenCaptureWin32 {
+    pub fn new() -> Self {
+        ScreenCaptureWin32 {}
+    }
+
+    fn to_screen_rect(rect: &RECT) -> ScreenRect {
+        // even if both are negative, the width should come out as positive - i.e. (0 - -1024 = 1024), (-1024 - -2048 = 1024), etc
+        ScreenRect::new(
+            rect.left,
+            rect.top,
+            (rect.right - rect.left) as u32,
+            (rect.bottom - rect.top) as u32,
+        )
+    }
+
+    // callback for EnumDisplayMonitors(), where LPARAM is the Vec<MonitorInfo> we are collecting into
+    unsafe extern "system" fn enum_monitor_callback(
+        h_monitor: HMONITOR,
+        _hdc: HDC,
+        _rect: LPRECT,
+        monitors_ptr: LPARAM,
+    ) -> BOOL {
+        let monitors = &mut *(monitors_ptr as *mut Vec<MonitorInfo>);
+        let mut monitor_info: MONITORINFO = std::mem::zeroed();
+        monitor_info.cbSize = std::mem::size_of::<MONITORINFO>() as u32;
+        if GetMonitorInfoW(h_monitor, &mut monitor_info) != 0 {
+            monitors.push(MonitorInfo {
+                name: format!("monitor-{}", monitors.len()),
+                rect: Self::to_screen_rect(&monitor_info.rcMonitor),
+                // work area rectangle (rectangle not obscured by taskbar and toolbar)
+                work_area: Self::to_screen_rect(&monitor_info.rcWork),
+                is_primary: (monitor_info.dwFlags & MONITORINFOF_PRIMARY) != 0,
+            });
+        }
+        TRUE // continue enumerating
+    }
+}
+
+impl ScreenCaptureTrait for ScreenCaptureWin32 {
+    // NOTE: Make sure to hide the lens window prior to calling this method and show it after image is captured
+    // this is so that we do not get the image-echo effect (like a mirror reflecting a mirror) when we capture the screen
+    fn capture(&self, rect: ScreenRect) -> Result<DynamicImage, Error> {
+        // first, get DC of the entire desktop (hence we do not need HWND passed here) via calling GetDC(NULL) - NULL means the entire desktop
+        let source_desktop_dc = unsafe { GetDC(ptr::null_mut()) };
+        if source_desktop_dc.is_null() {
+            return Err(anyhow::anyhow!("GetDC(NULL) failed"));
+        }
+
+        // Create a compatible device context and bitmap
+        let destination_memory_dc = unsafe { CreateCompatibleDC(source_desktop_dc) };
+        let destination_bitmap = unsafe {
+            CreateCompatibleBitmap(source_desktop_dc, rect.width as i32, rect.height as i32)
+        };
+
+        // select the bitmap into the memory device context
+        let previous_screen_for_restore_dc = unsafe {
+            SelectObject(
+                destination_memory_dc,
+                destination_bitmap as *mut winapi::ctypes::c_void,
+            )
+        };
+        let image: DynamicImage;
+        unsafe {
+            // BitBlt from the screen DC to the memory DC
+            BitBlt(
+                destination_memory_dc, // destination device context
+                0,                     // destination x
+                0,                     // destination y
+                rect.width as i32,
+                rect.height as i32,
+                source_desktop_dc, // source device context
+                rect.x, // source x - note that coordinate can be negative value (e.g. cursor is on the left side of the PRIMARY monitor)
+                rect.y, // source y
+                SRCCOPY,
+            );
+
+            // Clean up: Select the OLD bitmap back into the memory DC
+            SelectObject(destination_memory_dc, previous_screen_for_restore_dc);
+
+            // At this point, destination_bitmap contains the captured image
+            // Create a BITMAPINFO structure to receive the bitmap data
+            let mut info: BITMAPINFO = std::mem::zeroed();
+            info.bmiHeader.biSize = std::mem::size_of::<BITMAPINFO>() as u32;
+            info.bmiHeader.biWidth = rect.width as i32;
+            info.bmiHeader.biHeight = -(rect.height as i32); // top-down bitmap
+            info.bmiHeader.biPlanes = 1;
+            info.bmiHeader.biBitCount = 32; // each pixel is a 32-bit RGB color
+            info.bmiHeader.biCompression = BI_RGB;
+
+            // Allocate a buffer to receive the bitmap data
+            let mut data: Vec<BYTE> = vec![0; (rect.width * rect.height * 4) as usize];
+
+            // Get the bitmap data
+            GetDIBits(
+                destination_memory_dc,
+                destination_bitmap,
+                0,
+                rect.height,
+                data.as_mut_ptr() as *mut _,
+                &mut info,
+                DIB_RGB_COLORS,
+            );
+
+            // Convert the data to a DynamicImage
+            image = ImageBuffer::from_fn(rect.width, rect.height, |x, y| {
+                let i = ((y * rect.width + x) * 4) as usize;
+                image::Rgba([data[i + 2], data[i + 1], data[i], 255])
+            })
+            .into(); // At this point, image is a DynamicImage containing the bitmap image
+
+            DeleteDC(destination_memory_dc);
+            ReleaseDC(ptr::null_mut(), source_desktop_dc);
+            DeleteObject(destination_bitmap as *mut winapi::ctypes::c_void);
+        };
+        Ok(image)
+    }
+
+    fn cursor_position(&self) -> Result<(i32, i32), Error> {
+        let mut cursor_pos = winapi::shared::windef::POINT { x: 0, y: 0 };
+        if unsafe { GetCursorPos(&mut cursor_pos) } == 0 {
+            return Err(anyhow::anyhow!("Could not get cursor position"));
+        }
+        Ok((cursor_pos.x, cursor_pos.y))
+    }
+
+    fn monitors(&self) -> Result<Vec<MonitorInfo>, Error> {
+        let mut monitors: Vec<MonitorInfo> = Vec::new();
+        let succeeded = unsafe {
+            EnumDisplayMonitors(
+                ptr::null_mut(), // NULL: entire virtual desktop
+                ptr::null(),
+                Some(Self::enum_monitor_callback),
+                &mut monitors as *mut Vec<MonitorInfo> as LPARAM,
+            )
+        };
+        if succeeded == 0 {
+            return Err(anyhow::anyhow!("EnumDisplayMonitors() failed"));
+        }
+        Ok(monitors)
+    }
+}
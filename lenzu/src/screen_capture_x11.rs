@@ -0,0 +1,271 @@
+// Linux (X11) screen capture via Xlib XGetImage(), and monitors via XRandR
+// NOTE: Under Wayland, this only works on XWayland and (depending on compositor) will most likely only
+// capture other XWayland windows; for headless testing, run under Xvfb (i.e. `xvfb-run cargo test`)
+use crate::screen_capture_traits::{self, MonitorInfo, ScreenCaptureTrait, ScreenRect};
+use anyhow::Error;
+use image::DynamicImage; // the "real" DynamicImage, not the one from imageproc or rusty_tesseract
+use std::{ffi::CString, os::raw::c_ulong, ptr};
+use x11::{xlib, xrandr};
+
+pub struct ScreenCaptureX11 {
+    display: *mut xlib::Display,
+    root_window: xlib::Window,
+}
+
+impl ScreenCaptureX11 {
+    // display_name of None means to use $DISPLAY
+    pub fn open(display_name: Option<&str>) -> Result<Self, Error> {
+        let display_name_cstr = match display_name {
+            Some(name) => Some(CString::new(name)?),
+            None => None,
+        };
+        let display = unsafe {
+            xlib::XOpenDisplay(
+                display_name_cstr
+                    .as_ref()
+                    .map(|name| name.as_ptr())
+                    .unwrap_or(ptr::null()),
+            )
+        };
+        if display.is_null() {
+            return Err(anyhow::anyhow!(
+                "Unable to open X display '{}' (is $DISPLAY set?)",
+                display_name
+                    .map(|name| name.to_string())
+                    .unwrap_or(std::env::var("DISPLAY").unwrap_or_default())
+            ));
+        }
+        let root_window = unsafe { xlib::XDefaultRootWindow(display) };
+        Ok(ScreenCaptureX11 {
+            display,
+            root_window,
+        })
+    }
+
+    fn root_rect(&self) -> ScreenRect {
+        unsafe {
+            let screen = xlib::XDefaultScreen(self.display);
+            ScreenRect::new(
+                0,
+                0,
+                xlib::XDisplayWidth(self.display, screen) as u32,
+                xlib::XDisplayHeight(self.display, screen) as u32,
+            )
+        }
+    }
+
+    // scales the masked channel (which may be 5, 6, 8, 10 bits, etc) to 8 bits
+    fn to_channel(pixel: c_ulong, mask: c_ulong) -> u8 {
+        if mask == 0 {
+            return 0;
+        }
+        let value = (pixel & mask) >> mask.trailing_zeros();
+        let max_value = mask >> mask.trailing_zeros();
+        ((value * 255) / max_value) as u8
+    }
+}
+
+impl Drop for ScreenCaptureX11 {
+    fn drop(&mut self) {
+        unsafe {
+            xlib::XCloseDisplay(self.display);
+        }
+    }
+}
+
+impl ScreenCaptureTrait for ScreenCaptureX11 {
+    fn capture(&self, rect: ScreenRect) -> Result<DynamicImage, Error> {
+        // XGetImage() raises BadMatch (which by default terminates the process) if the rectangle is not fully
+        // inside the root window, so only ask for the visible part and let crop_onto_canvas() pad the rest
+        let root_rect = self.root_rect();
+        let visible_rect = match root_rect.intersect(&rect) {
+            Some(visible_rect) => visible_rect,
+            None => {
+                return Ok(DynamicImage::ImageRgba8(
+                    screen_capture_traits::black_canvas(rect.width, rect.height),
+                ))
+            }
+        };
+        let ximage = unsafe {
+            xlib::XGetImage(
+                self.display,
+                self.root_window,
+                visible_rect.x,
+                visible_rect.y,
+                visible_rect.width,
+                visible_rect.height,
+                xlib::XAllPlanes(),
+                xlib::ZPixmap,
+            )
+        };
+        if ximage.is_null() {
+            return Err(anyhow::anyhow!("XGetImage() failed for {:?}", visible_rect));
+        }
+        let (red_mask, green_mask, blue_mask) = unsafe {
+            (
+                (*ximage).red_mask,
+                (*ximage).green_mask,
+                (*ximage).blue_mask,
+            )
+        };
+        let visible_image: image::RgbaImage =
+            image::ImageBuffer::from_fn(visible_rect.width, visible_rect.height, |x, y| {
+                let pixel = unsafe { xlib::XGetPixel(ximage, x as i32, y as i32) };
+                image::Rgba([
+                    Self::to_channel(pixel, red_mask),
+                    Self::to_channel(pixel, green_mask),
+                    Self::to_channel(pixel, blue_mask),
+                    255,
+                ])
+            });
+        unsafe {
+            xlib::XDestroyImage(ximage);
+        }
+        Ok(screen_capture_traits::crop_onto_canvas(
+            &DynamicImage::ImageRgba8(visible_image),
+            visible_rect.x,
+            visible_rect.y,
+            rect,
+        ))
+    }
+
+    fn cursor_position(&self) -> Result<(i32, i32), Error> {
+        let mut root_return: xlib::Window = 0;
+        let mut child_return: xlib::Window = 0;
+        let (mut root_x, mut root_y, mut window_x, mut window_y) = (0, 0, 0, 0);
+        let mut mask_return: u32 = 0;
+        let is_on_screen = unsafe {
+            xlib::XQueryPointer(
+                self.display,
+                self.root_window,
+                &mut root_return,
+                &mut child_return,
+                &mut root_x,
+                &mut root_y,
+                &mut window_x,
+                &mut window_y,
+                &mut mask_return,
+            )
+        };
+        if is_on_screen == xlib::False {
+            return Err(anyhow::anyhow!("Cursor is not on the same screen"));
+        }
+        Ok((root_x, root_y))
+    }
+
+    fn monitors(&self) -> Result<Vec<MonitorInfo>, Error> {
+        let mut monitor_count: i32 = 0;
+        let xrandr_monitors = unsafe {
+            xrandr::XRRGetMonitors(
+                self.display,
+                self.root_window,
+                xlib::True,
+                &mut monitor_count,
+            )
+        };
+        let mut monitors = Vec::new();
+        if !xrandr_monitors.is_null() {
+            for index in 0..monitor_count as isize {
+                let xrandr_monitor = unsafe { &*xrandr_monitors.offset(index) };
+                let name = unsafe {
+                    let name_ptr = xlib::XGetAtomName(self.display, xrandr_monitor.name);
+                    if name_ptr.is_null() {
+                        format!("monitor-{}", index)
+                    } else {
+                        let name = std::ffi::CStr::from_ptr(name_ptr)
+                            .to_string_lossy()
+                            .into_owned();
+                        xlib::XFree(name_ptr as *mut _);
+                        name
+                    }
+                };
+                let rect = ScreenRect::new(
+                    xrandr_monitor.x,
+                    xrandr_monitor.y,
+                    xrandr_monitor.width as u32,
+                    xrandr_monitor.height as u32,
+                );
+                monitors.push(MonitorInfo {
+                    name,
+                    rect,
+                    work_area: rect, // X11 does not have a notion of taskbar (_NET_WORKAREA is per-desktop, not per-monitor)
+                    is_primary: xrandr_monitor.primary != 0,
+                });
+            }
+            unsafe { xrandr::XRRFreeMonitors(xrandr_monitors) };
+        }
+        if monitors.is_empty() {
+            // no XRandR (i.e. plain Xvfb), so the root window is the only monitor
+            let rect = self.root_rect();
+            monitors.push(MonitorInfo {
+                name: "root".to_string(),
+                rect,
+                work_area: rect,
+                is_primary: true,
+            });
+        }
+        Ok(monitors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::GenericImageView;
+    use std::process::{Child, Command};
+    use std::time::Duration;
+
+    // Xvfb on a display of its own, killed when the test is done
+    struct Xvfb(Child);
+
+    impl Drop for Xvfb {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+
+    #[test]
+    #[ignore = "needs Xvfb (i.e. `apt install xvfb`), run with `cargo test -- --ignored`"]
+    fn test_capture_on_xvfb() {
+        let display_name = format!(":{}", 90 + std::process::id() % 100);
+        // -wr for a white root window, so that the padding (black) can be told apart from the desktop
+        let _xvfb = Xvfb(
+            Command::new("Xvfb")
+                .args([&display_name, "-screen", "0", "320x240x24", "-wr"])
+                .args(["-nolisten", "tcp"])
+                .spawn()
+                .expect("Xvfb is not installed"),
+        );
+        let screen_capture = (0..50)
+            .find_map(|_| {
+                std::thread::sleep(Duration::from_millis(100));
+                ScreenCaptureX11::open(Some(&display_name)).ok()
+            })
+            .expect("Xvfb did not start");
+
+        let root_rect = ScreenRect::new(0, 0, 320, 240);
+        assert!(screen_capture
+            .monitors()
+            .unwrap()
+            .iter()
+            .any(|monitor| monitor.rect == root_rect));
+        let (x, y) = screen_capture.cursor_position().unwrap();
+        assert_eq!(root_rect.distance_squared(x, y), 0);
+
+        // 10 pixels left of and above the root window come back as black, the rest is the white root window
+        let captured = screen_capture
+            .capture(ScreenRect::new(-10, -10, 20, 20))
+            .unwrap();
+        assert_eq!(captured.dimensions(), (20, 20));
+        assert_eq!(captured.get_pixel(0, 0), image::Rgba([0, 0, 0, 255]));
+        assert_eq!(
+            captured.get_pixel(19, 19),
+            image::Rgba([255, 255, 255, 255])
+        );
+        let off_screen = screen_capture
+            .capture(ScreenRect::new(1000, 1000, 8, 8))
+            .unwrap();
+        assert_eq!(off_screen.get_pixel(7, 7), image::Rgba([0, 0, 0, 255]));
+    }
+}
@@ -0,0 +1,109 @@
+// Helpers shared by the tests of several modules (only compiled for `cargo test`)
+use crate::ocr_gcloud::OcrGcloud;
+use crate::ocr_traits::{OcrLine, OcrRect, OcrTrait, OcrTraitResult, OcrWord};
+use anyhow::Error;
+use std::io::{BufRead, BufReader, Read, Write};
+
+// OCR for testing the pipelines without Tesseract (nor Windows, nor credentials)
+pub(crate) enum FakeOcr {
+    Result(OcrTraitResult), // the same result regardless of the image
+    WholeImage(String),     // the text as a single word that covers the whole image it is given
+}
+
+impl FakeOcr {
+    // a synthetic (hand-written, in the shape of the API reference) Cloud Vision result for
+    // ../assets/ubunchu01_02_panel01_section_02.png, see ocr_gcloud.rs's test_record_fixture() for a real one
+    pub(crate) fn gcloud_fixture() -> Self {
+        let response_body = std::fs::read_to_string(
+            "../assets/fixtures/gcloud_synthetic_ubunchu01_02_panel01_section_02.json",
+        )
+        .unwrap();
+        FakeOcr::Result(OcrGcloud::to_ocr_trait_result(&response_body).unwrap())
+    }
+
+    // lines without any boxes
+    pub(crate) fn lines(lines: &[&str]) -> Self {
+        let mut ocr_result = OcrTraitResult::new();
+        ocr_result.lines = lines.iter().map(|line| line.to_string()).collect();
+        ocr_result.text = ocr_result.lines.join("\n");
+        FakeOcr::Result(ocr_result)
+    }
+}
+
+impl OcrTrait for FakeOcr {
+    fn new() -> Self {
+        FakeOcr::WholeImage("字".to_string())
+    }
+    fn name(&self) -> &'static str {
+        "fake"
+    }
+    fn init(&self) -> Vec<String> {
+        vec!["ja".to_string()]
+    }
+    fn evaluate_by_paths(&self, image_path: &str) -> Result<OcrTraitResult, Error> {
+        self.evaluate(&image::open(image_path)?)
+    }
+    fn evaluate(&self, image: &image::DynamicImage) -> Result<OcrTraitResult, Error> {
+        match self {
+            FakeOcr::Result(ocr_result) => Ok(ocr_result.clone()),
+            FakeOcr::WholeImage(text) => {
+                let mut ocr_result = OcrTraitResult::new();
+                ocr_result.rects = vec![OcrLine::new(vec![OcrWord::new(
+                    text.clone(),
+                    0,
+                    OcrRect::from(0, 0, image.width(), image.height()),
+                )])];
+                ocr_result.lines = vec![text.clone()];
+                ocr_result.text = text.clone();
+                Ok(ocr_result)
+            }
+        }
+    }
+}
+
+// Minimal single-shot HTTP server that replies with the given status and (JSON) body, so that the online backends
+// (Cloud Vision, LibreTranslate, ...) can be tested without credentials nor network; the handle returns the
+// request line (i.e. "POST /v1/images:annotate?key=... HTTP/1.1") and the request body
+pub(crate) fn serve_once(
+    path: &str,
+    status_line: &'static str,
+    response_body: String,
+) -> (String, std::thread::JoinHandle<(String, String)>) {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let endpoint = format!("http://{}{}", listener.local_addr().unwrap(), path);
+    let handle = std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line).unwrap();
+        let mut content_length = 0;
+        loop {
+            let mut header = String::new();
+            reader.read_line(&mut header).unwrap();
+            if header.trim().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    content_length = value.trim().parse().unwrap();
+                }
+            }
+        }
+        let mut request_body = vec![0u8; content_length];
+        reader.read_exact(&mut request_body).unwrap();
+        let mut stream = stream;
+        write!(
+            stream,
+            "{}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status_line,
+            response_body.len(),
+            response_body
+        )
+        .unwrap();
+        (
+            request_line.trim_end().to_string(),
+            String::from_utf8(request_body).unwrap(),
+        )
+    });
+    (endpoint, handle)
+}